@@ -1,5 +1,5 @@
 fn main() {
+    // Only the desktop app needs the Tauri context
+    #[cfg(feature = "gui")]
     tauri_build::build()
 }
-
-// NO_STRIP=true pnpm tauri build
\ No newline at end of file
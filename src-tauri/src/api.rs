@@ -0,0 +1,731 @@
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, put};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use specta::Type;
+use std::fs;
+use futures::future::{self, Either};
+use std::future::{Future, IntoFuture};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::path::Path as FsPath;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+use crate::backend::Backend;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::query::PortQuery;
+use crate::services::{machines, notes, ports, scans, workspaces};
+use crate::store::Store;
+use crate::PortNotes;
+
+const DEFAULT_PORT: u16 = 8085;
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+// Kept next to the database so the CLI's `serve` picks up the same token
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct ApiSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub token: String,
+}
+
+fn default_port() -> u16 {
+    DEFAULT_PORT
+}
+
+fn new_token() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+fn settings_path(dir: &FsPath) -> std::path::PathBuf {
+    dir.join("api.json")
+}
+
+// A token is generated and saved the first time the settings are read
+pub(crate) fn load_settings(dir: &FsPath) -> AppResult<ApiSettings> {
+    let mut settings = fs::read_to_string(settings_path(dir))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or(ApiSettings { enabled: false, port: DEFAULT_PORT, token: String::new() });
+    if settings.token.is_empty() {
+        settings.token = new_token();
+        save_settings(dir, &settings)?;
+    }
+    Ok(settings)
+}
+
+pub(crate) fn save_settings(dir: &FsPath, settings: &ApiSettings) -> AppResult<()> {
+    let json = serde_json::to_string_pretty(settings)?;
+    fs::write(settings_path(dir), json).map_err(|e| AppError::storage("Failed to write API settings", e))
+}
+
+#[derive(Serialize, Type)]
+pub(crate) struct ApiStatus {
+    pub enabled: bool,
+    pub port: u16,
+    pub token: String,
+    pub running: bool,
+    pub url: String,
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = match self.code() {
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Validation => StatusCode::BAD_REQUEST,
+            ErrorCode::BackendUnavailable => StatusCode::BAD_GATEWAY,
+            ErrorCode::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            ErrorCode::Storage | ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+#[derive(Clone)]
+struct ApiState {
+    store: Store,
+    backend: Backend,
+    token: Arc<str>,
+}
+
+// Looks at every byte whichever one differs, so the time taken doesn't give the token away
+pub(crate) fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+async fn authorize(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let authorized = request.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| tokens_match(token, &state.token));
+    if !authorized {
+        let body = json!({ "code": "unauthorized", "message": "Missing or invalid API token" });
+        return (StatusCode::UNAUTHORIZED, Json(body)).into_response();
+    }
+    next.run(request).await
+}
+
+fn created(id: impl Serialize) -> Response {
+    (StatusCode::CREATED, Json(json!({ "id": id }))).into_response()
+}
+
+fn done(result: AppResult<()>) -> AppResult<StatusCode> {
+    result.map(|_| StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct TagFilter {
+    tag: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    q: String,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct WorkspaceInput {
+    name: String,
+    ip_range: String,
+}
+
+#[derive(Deserialize)]
+struct WorkspacePatch {
+    name: Option<String>,
+    ip_range: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MachineInput {
+    hostname: String,
+    ip: String,
+}
+
+#[derive(Deserialize)]
+struct MachinePatch {
+    hostname: Option<String>,
+    ip: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NoteInput {
+    #[serde(default = "default_stage")]
+    stage: String,
+    content: String,
+}
+
+fn default_stage() -> String {
+    "Information Gathering".to_string()
+}
+
+#[derive(Deserialize)]
+struct NotePatch {
+    stage: Option<String>,
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CredentialsInput {
+    username: Option<String>,
+    password: Option<String>,
+    hash: Option<String>,
+}
+
+async fn list_workspaces(State(state): State<ApiState>, Query(filter): Query<TagFilter>) -> Response {
+    workspaces::list(&state.store, filter.tag.as_deref()).map(Json).into_response()
+}
+
+async fn add_workspace(State(state): State<ApiState>, Json(input): Json<WorkspaceInput>) -> AppResult<Response> {
+    workspaces::add(&state.store, input.name, input.ip_range).map(created)
+}
+
+async fn get_workspace(State(state): State<ApiState>, Path(wid): Path<u32>) -> Response {
+    workspaces::get(&state.store, wid).map(Json).into_response()
+}
+
+async fn update_workspace(State(state): State<ApiState>, Path(wid): Path<u32>, Json(patch): Json<WorkspacePatch>) -> AppResult<StatusCode> {
+    done(workspaces::update(&state.store, wid, patch.name, patch.ip_range))
+}
+
+async fn delete_workspace(State(state): State<ApiState>, Path(wid): Path<u32>) -> AppResult<StatusCode> {
+    done(workspaces::delete(&state.store, wid))
+}
+
+async fn query_ports(State(state): State<ApiState>, Path(wid): Path<u32>, Json(query): Json<PortQuery>) -> Response {
+    workspaces::query_ports(&state.store, wid, &query).map(Json).into_response()
+}
+
+async fn discover(State(state): State<ApiState>, Path(wid): Path<u32>) -> Response {
+    scans::discover_hosts(&state.store, &state.backend, wid).await
+        .map(|count| Json(json!({ "machines": count })))
+        .into_response()
+}
+
+async fn workspace_notes(State(state): State<ApiState>, Path(wid): Path<u32>, Query(filter): Query<TagFilter>) -> Response {
+    notes::list(&state.store, wid, None, filter.tag.as_deref()).map(Json).into_response()
+}
+
+async fn add_workspace_note(State(state): State<ApiState>, Path(wid): Path<u32>, Json(input): Json<NoteInput>) -> AppResult<Response> {
+    notes::add(&state.store, wid, None, input.stage, input.content).map(created)
+}
+
+async fn update_workspace_note(
+    State(state): State<ApiState>,
+    Path((wid, note_id)): Path<(u32, String)>,
+    Json(input): Json<NoteInput>,
+) -> AppResult<StatusCode> {
+    done(notes::update(&state.store, wid, None, &note_id, input.stage, input.content))
+}
+
+async fn delete_workspace_note(State(state): State<ApiState>, Path((wid, note_id)): Path<(u32, String)>) -> AppResult<StatusCode> {
+    done(notes::delete(&state.store, wid, None, &note_id))
+}
+
+async fn list_machines(State(state): State<ApiState>, Path(wid): Path<u32>, Query(filter): Query<TagFilter>) -> Response {
+    machines::list(&state.store, wid, filter.tag.as_deref()).map(Json).into_response()
+}
+
+async fn add_machine(State(state): State<ApiState>, Path(wid): Path<u32>, Json(input): Json<MachineInput>) -> AppResult<Response> {
+    machines::add(&state.store, wid, input.hostname, input.ip).map(created)
+}
+
+async fn get_machine(State(state): State<ApiState>, Path((wid, mid)): Path<(u32, u32)>) -> Response {
+    machines::get(&state.store, wid, mid).map(Json).into_response()
+}
+
+async fn update_machine(
+    State(state): State<ApiState>,
+    Path((wid, mid)): Path<(u32, u32)>,
+    Json(patch): Json<MachinePatch>,
+) -> AppResult<StatusCode> {
+    done(machines::update(&state.store, wid, mid, patch.hostname, patch.ip))
+}
+
+async fn delete_machine(State(state): State<ApiState>, Path((wid, mid)): Path<(u32, u32)>) -> AppResult<StatusCode> {
+    done(machines::delete(&state.store, wid, mid))
+}
+
+async fn scan_machine(State(state): State<ApiState>, Path((wid, mid)): Path<(u32, u32)>) -> Response {
+    scans::scan_machine(&state.store, &state.backend, wid, mid).await
+        .map(|count| Json(json!({ "ports": count })))
+        .into_response()
+}
+
+async fn machine_notes(State(state): State<ApiState>, Path((wid, mid)): Path<(u32, u32)>, Query(filter): Query<TagFilter>) -> Response {
+    notes::list(&state.store, wid, Some(mid), filter.tag.as_deref()).map(Json).into_response()
+}
+
+async fn add_machine_note(
+    State(state): State<ApiState>,
+    Path((wid, mid)): Path<(u32, u32)>,
+    Json(input): Json<NoteInput>,
+) -> AppResult<Response> {
+    notes::add(&state.store, wid, Some(mid), input.stage, input.content).map(created)
+}
+
+async fn update_machine_note(
+    State(state): State<ApiState>,
+    Path((wid, mid, note_id)): Path<(u32, u32, String)>,
+    Json(input): Json<NoteInput>,
+) -> AppResult<StatusCode> {
+    done(notes::update(&state.store, wid, Some(mid), &note_id, input.stage, input.content))
+}
+
+async fn delete_machine_note(State(state): State<ApiState>, Path((wid, mid, note_id)): Path<(u32, u32, String)>) -> AppResult<StatusCode> {
+    done(notes::delete(&state.store, wid, Some(mid), &note_id))
+}
+
+async fn list_ports(State(state): State<ApiState>, Path((wid, mid)): Path<(u32, u32)>, Query(filter): Query<TagFilter>) -> Response {
+    ports::list(&state.store, wid, mid, filter.tag.as_deref()).map(Json).into_response()
+}
+
+async fn get_port(State(state): State<ApiState>, Path((wid, mid, port)): Path<(u32, u32, u16)>) -> Response {
+    ports::get(&state.store, wid, mid, port).map(Json).into_response()
+}
+
+// Creates or updates the port, answering 201 or 200 with the stored port
+async fn save_port(
+    State(state): State<ApiState>,
+    Path((wid, mid, port)): Path<(u32, u32, u16)>,
+    Json(input): Json<ports::PortInput>,
+) -> AppResult<Response> {
+    let status = if ports::save(&state.store, wid, mid, port, input)? { StatusCode::CREATED } else { StatusCode::OK };
+    Ok((status, Json(ports::get(&state.store, wid, mid, port)?)).into_response())
+}
+
+async fn delete_port(State(state): State<ApiState>, Path((wid, mid, port)): Path<(u32, u32, u16)>) -> AppResult<StatusCode> {
+    done(ports::delete(&state.store, wid, mid, port))
+}
+
+async fn add_port_note(
+    State(state): State<ApiState>,
+    Path((wid, mid, port)): Path<(u32, u32, u16)>,
+    Json(input): Json<NoteInput>,
+) -> AppResult<Response> {
+    ports::add_note(&state.store, wid, mid, port, input.stage, input.content).map(created)
+}
+
+async fn replace_port_notes(
+    State(state): State<ApiState>,
+    Path((wid, mid, port)): Path<(u32, u32, u16)>,
+    Json(notes): Json<Vec<PortNotes>>,
+) -> AppResult<StatusCode> {
+    done(ports::update_notes(&state.store, wid, mid, port, notes))
+}
+
+async fn edit_port_note(
+    State(state): State<ApiState>,
+    Path((wid, mid, port, note_id)): Path<(u32, u32, u16, String)>,
+    Json(patch): Json<NotePatch>,
+) -> AppResult<StatusCode> {
+    done(ports::edit_note(&state.store, wid, mid, port, &note_id, patch.stage, patch.content))
+}
+
+async fn delete_port_note(
+    State(state): State<ApiState>,
+    Path((wid, mid, port, note_id)): Path<(u32, u32, u16, String)>,
+) -> AppResult<StatusCode> {
+    done(ports::delete_note(&state.store, wid, mid, port, &note_id))
+}
+
+async fn add_credentials(
+    State(state): State<ApiState>,
+    Path((wid, mid, port)): Path<(u32, u32, u16)>,
+    Json(input): Json<CredentialsInput>,
+) -> AppResult<StatusCode> {
+    ports::add_credentials(&state.store, wid, mid, port, input.username, input.hash, input.password)?;
+    Ok(StatusCode::CREATED)
+}
+
+async fn search(State(state): State<ApiState>, Query(params): Query<SearchParams>) -> Response {
+    workspaces::search(&state.store, &params.q, params.limit).map(Json).into_response()
+}
+
+fn router(state: ApiState) -> Router {
+    let workspace = "/api/workspaces/{workspace}";
+    let machine = "/api/workspaces/{workspace}/machines/{machine}";
+    let port = "/api/workspaces/{workspace}/machines/{machine}/ports/{port}";
+    Router::new()
+        .route("/api/workspaces", get(list_workspaces).post(add_workspace))
+        .route(workspace, get(get_workspace).patch(update_workspace).delete(delete_workspace))
+        .route(&format!("{}/query", workspace), post(query_ports))
+        .route(&format!("{}/discover", workspace), post(discover))
+        .route(&format!("{}/notes", workspace), get(workspace_notes).post(add_workspace_note))
+        .route(&format!("{}/notes/{{note}}", workspace), put(update_workspace_note).delete(delete_workspace_note))
+        .route(&format!("{}/machines", workspace), get(list_machines).post(add_machine))
+        .route(machine, get(get_machine).patch(update_machine).delete(delete_machine))
+        .route(&format!("{}/scan", machine), post(scan_machine))
+        .route(&format!("{}/notes", machine), get(machine_notes).post(add_machine_note))
+        .route(&format!("{}/notes/{{note}}", machine), put(update_machine_note).delete(delete_machine_note))
+        .route(&format!("{}/ports", machine), get(list_ports))
+        .route(port, get(get_port).put(save_port).delete(delete_port))
+        .route(&format!("{}/notes", port), post(add_port_note).put(replace_port_notes))
+        .route(&format!("{}/notes/{{note}}", port), axum::routing::patch(edit_port_note).delete(delete_port_note))
+        .route(&format!("{}/credentials", port), post(add_credentials))
+        .route("/api/search", get(search))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state)
+}
+
+// Serves until `shutdown` completes; used by the app's background server and the CLI's `serve`
+pub(crate) async fn serve(
+    listener: TcpListener,
+    store: Store,
+    backend: Backend,
+    token: String,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> AppResult<()> {
+    if token.is_empty() {
+        return Err(AppError::validation("The API token must not be empty"));
+    }
+    serve_router(listener, router(ApiState { store, backend, token: token.into() }), shutdown).await
+}
+
+// Also runs the team server. Requests still running this long after `shutdown`, such as a
+// scan, are dropped so the settings can restart the server without hanging the app.
+pub(crate) async fn serve_router(listener: TcpListener, app: Router, shutdown: impl Future<Output = ()> + Send + 'static) -> AppResult<()> {
+    listener.set_nonblocking(true).map_err(|e| AppError::Internal(format!("Failed to configure listener: {}", e)))?;
+    let listener = tokio::net::TcpListener::from_std(listener)
+        .map_err(|e| AppError::Internal(format!("Failed to configure listener: {}", e)))?;
+    let (stopping, stopped) = oneshot::channel::<()>();
+    let shutdown = async move {
+        shutdown.await;
+        let _ = stopping.send(());
+    };
+    let server = pin!(axum::serve(listener, app).with_graceful_shutdown(shutdown).into_future());
+    let deadline = pin!(async move {
+        match stopped.await {
+            Ok(()) => tokio::time::sleep(SHUTDOWN_GRACE).await,
+            Err(_) => std::future::pending().await,
+        }
+    });
+    match future::select(server, deadline).await {
+        Either::Left((result, _)) => result.map_err(|e| AppError::Internal(format!("Server failed: {}", e))),
+        Either::Right(_) => Ok(()),
+    }
+}
+
+// Only ever reachable from this machine
+pub(crate) fn bind(port: u16) -> AppResult<TcpListener> {
+    TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+        .map_err(|e| AppError::validation(format!("Cannot listen on port {}: {}", port, e)))
+}
+
+struct Running {
+    port: u16,
+    // A second handle on the server's socket, so a restart on the same port never lets go of it
+    listener: TcpListener,
+    shutdown: oneshot::Sender<()>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl Running {
+    // Returns once the server has let go of its port, so it can be bound again
+    fn stop(self) -> AppResult<()> {
+        let _ = self.shutdown.send(());
+        if self.thread.join().is_err() {
+            return Err(AppError::Internal("The API server stopped unexpectedly".to_string()));
+        }
+        Ok(())
+    }
+}
+
+// The app's API server, started and stopped from the settings. Clones share the same server.
+#[derive(Clone)]
+pub(crate) struct ApiServer {
+    store: Store,
+    backend: Backend,
+    running: Arc<Mutex<Option<Running>>>,
+}
+
+impl ApiServer {
+    pub(crate) fn new(store: Store, backend: Backend) -> ApiServer {
+        ApiServer { store, backend, running: Arc::new(Mutex::new(None)) }
+    }
+
+    pub(crate) fn status(&self) -> AppResult<ApiStatus> {
+        let settings = load_settings(self.store.dir())?;
+        let running = self.running.lock()?.as_ref().map(|running| running.port);
+        let port = running.unwrap_or(settings.port);
+        Ok(ApiStatus {
+            enabled: settings.enabled,
+            port: settings.port,
+            token: settings.token,
+            running: running.is_some(),
+            url: format!("http://127.0.0.1:{}/api", port),
+        })
+    }
+
+    // Starts, restarts or stops the server to match the saved settings
+    pub(crate) fn apply(&self) -> AppResult<()> {
+        self.start(&load_settings(self.store.dir())?)
+    }
+
+    // The new port is bound before the old server is stopped, so a port that can't be used
+    // leaves the running server alone
+    fn start(&self, settings: &ApiSettings) -> AppResult<()> {
+        let mut running = self.running.lock()?;
+        let listener = if !settings.enabled {
+            None
+        } else if let Some(current) = running.as_ref().filter(|current| current.port == settings.port) {
+            Some(current.listener.try_clone().map_err(|e| AppError::Internal(format!("Failed to reuse listener: {}", e)))?)
+        } else {
+            Some(bind(settings.port)?)
+        };
+        if let Some(old) = running.take() {
+            old.stop()?;
+        }
+        let Some(listener) = listener else {
+            return Ok(());
+        };
+        let server_listener = listener.try_clone()
+            .map_err(|e| AppError::Internal(format!("Failed to configure listener: {}", e)))?;
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        let (store, backend, token) = (self.store.clone(), self.backend.clone(), settings.token.clone());
+        let thread = thread::spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                Ok(runtime) => runtime,
+                Err(e) => return eprintln!("Failed to start API runtime: {}", e),
+            };
+            let shutdown = async {
+                let _ = stopped.await;
+            };
+            if let Err(e) = runtime.block_on(serve(server_listener, store, backend, token, shutdown)) {
+                eprintln!("{}", e);
+            }
+        });
+        *running = Some(Running { port: settings.port, listener, shutdown, thread });
+        Ok(())
+    }
+
+    // Blocks for up to SHUTDOWN_GRACE while requests finish
+    pub(crate) fn stop(&self) -> AppResult<()> {
+        let running = self.running.lock()?.take();
+        match running {
+            Some(running) => running.stop(),
+            None => Ok(()),
+        }
+    }
+
+    // The settings are only saved once the server runs with them
+    pub(crate) fn update(&self, enabled: bool, port: Option<u16>) -> AppResult<ApiStatus> {
+        let mut settings = load_settings(self.store.dir())?;
+        settings.enabled = enabled;
+        if let Some(port) = port {
+            if port == 0 {
+                return Err(AppError::validation("Port must be between 1 and 65535"));
+            }
+            settings.port = port;
+        }
+        self.start(&settings)?;
+        save_settings(self.store.dir(), &settings)?;
+        self.status()
+    }
+
+    // Invalidates the old token; a running server is restarted with the new one
+    pub(crate) fn regenerate_token(&self) -> AppResult<ApiStatus> {
+        let mut settings = load_settings(self.store.dir())?;
+        settings.token = new_token();
+        if self.running.lock()?.is_some() {
+            self.start(&settings)?;
+        }
+        save_settings(self.store.dir(), &settings)?;
+        self.status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::temp_store;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use futures::executor::block_on;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "secret";
+
+    fn app(store: &Store) -> Router {
+        router(ApiState { store: store.clone(), backend: Backend::default(), token: TOKEN.into() })
+    }
+
+    fn call(app: &Router, method: &str, uri: &str, body: Option<Value>, token: Option<&str>) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let body = match body {
+            Some(body) => {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        block_on(async {
+            let response = app.clone().oneshot(request.body(body).unwrap()).await.unwrap();
+            let status = response.status();
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+        })
+    }
+
+    #[test]
+    fn rejects_missing_or_wrong_token() {
+        let (_dir, store) = temp_store();
+        let app = app(&store);
+        assert_eq!(call(&app, "GET", "/api/workspaces", None, None).0, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(&app, "GET", "/api/workspaces", None, Some("guess")).0, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(&app, "GET", "/api/workspaces", None, Some(TOKEN)).0, StatusCode::OK);
+    }
+
+    #[test]
+    fn crud_round_trip() {
+        let (_dir, store) = temp_store();
+        let app = app(&store);
+        let token = Some(TOKEN);
+
+        let (status, body) = call(&app, "POST", "/api/workspaces", Some(json!({ "name": "Lab", "ip_range": "10.0.0.0/24" })), token);
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["id"], 1);
+        let (status, _) = call(&app, "POST", "/api/workspaces/1/machines", Some(json!({ "hostname": "web", "ip": "10.0.0.2" })), token);
+        assert_eq!(status, StatusCode::CREATED);
+
+        let port = json!({ "service": "http", "application": "nginx 1.25" });
+        let (status, body) = call(&app, "PUT", "/api/workspaces/1/machines/1/ports/80", Some(port), token);
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["protocol"], "tcp");
+
+        let (status, body) = call(&app, "POST", "/api/workspaces/1/machines/1/ports/80/notes", Some(json!({ "content": "Default page" })), token);
+        assert_eq!(status, StatusCode::CREATED);
+        let note = body["id"].as_str().unwrap().to_string();
+        let uri = format!("/api/workspaces/1/machines/1/ports/80/notes/{}", note);
+        assert_eq!(call(&app, "PATCH", &uri, Some(json!({ "content": "Admin panel" })), token).0, StatusCode::NO_CONTENT);
+
+        let (_, hits) = call(&app, "GET", "/api/search?q=admin", None, token);
+        assert_eq!(hits.as_array().unwrap().len(), 1);
+
+        assert_eq!(call(&app, "PATCH", "/api/workspaces/1/machines/1", Some(json!({ "hostname": "www" })), token).0, StatusCode::NO_CONTENT);
+        let (_, machines) = call(&app, "GET", "/api/workspaces/1/machines", None, token);
+        assert_eq!(machines[0]["hostname"], "www");
+
+        assert_eq!(call(&app, "DELETE", "/api/workspaces/1", None, token).0, StatusCode::NO_CONTENT);
+        let (_, workspaces) = call(&app, "GET", "/api/workspaces", None, token);
+        assert!(workspaces.as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn errors_map_to_status_codes() {
+        let (_dir, store) = temp_store();
+        let app = app(&store);
+
+        let (status, body) = call(&app, "GET", "/api/workspaces/9/machines/1", None, Some(TOKEN));
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["entity"], "machine");
+
+        call(&app, "POST", "/api/workspaces", Some(json!({ "name": "Lab", "ip_range": "10.0.0.0/24" })), Some(TOKEN));
+        let (status, body) = call(&app, "POST", "/api/workspaces/1/notes", Some(json!({ "content": " " })), Some(TOKEN));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "validation");
+    }
+
+    #[test]
+    fn settings_get_a_token() {
+        let (_dir, store) = temp_store();
+        let first = load_settings(store.dir()).unwrap();
+        assert!(!first.enabled);
+        assert_eq!(first.port, DEFAULT_PORT);
+        assert_eq!(load_settings(store.dir()).unwrap().token, first.token);
+    }
+
+    #[test]
+    fn tokens_are_compared_whole() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secreT", "secret"));
+        assert!(!tokens_match("secret2", "secret"));
+        assert!(!tokens_match("", "secret"));
+    }
+
+    #[test]
+    fn stopping_does_not_wait_for_slow_requests() {
+        use std::io::Write;
+        use std::net::TcpStream;
+        use std::time::Instant;
+
+        let listener = bind(0).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let app = Router::new().route("/slow", get(|| tokio::time::sleep(Duration::from_secs(60))));
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        let server = thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(serve_router(listener, app, async {
+                let _ = stopped.await;
+            }))
+        });
+
+        let mut client = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        client.write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        thread::sleep(Duration::from_millis(200));
+        let started = Instant::now();
+        shutdown.send(()).unwrap();
+        server.join().unwrap().unwrap();
+        assert!(started.elapsed() < SHUTDOWN_GRACE * 3);
+    }
+
+    #[test]
+    fn server_restarts_on_the_same_port() {
+        use std::io::{Read, Write};
+        use std::net::TcpStream;
+
+        let (_dir, store) = temp_store();
+        let port = bind(0).unwrap().local_addr().unwrap().port();
+        let server = ApiServer::new(store, Backend::default());
+        server.update(true, Some(port)).unwrap();
+
+        // An open keep-alive connection mustn't hold the port
+        let mut client = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        client.write_all(b"GET /api/workspaces HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = [0; 12];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(&response, b"HTTP/1.1 401");
+
+        let status = server.regenerate_token().unwrap();
+        assert!(status.running);
+        server.apply().unwrap();
+        assert!(server.status().unwrap().running);
+        server.stop().unwrap();
+        assert!(!server.status().unwrap().running);
+        bind(port).unwrap();
+    }
+
+    #[test]
+    fn port_in_use_leaves_the_server_and_settings_alone() {
+        let (_dir, store) = temp_store();
+        let port = bind(0).unwrap().local_addr().unwrap().port();
+        let server = ApiServer::new(store, Backend::default());
+        server.update(true, Some(port)).unwrap();
+
+        let taken = bind(0).unwrap();
+        let busy = taken.local_addr().unwrap().port();
+        assert!(matches!(server.update(true, Some(busy)), Err(AppError::Validation(_))));
+        let status = server.status().unwrap();
+        assert!(status.running);
+        assert_eq!(status.port, port);
+        assert_eq!(status.url, format!("http://127.0.0.1:{}/api", port));
+        server.stop().unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+use serde::{Serialize, Deserialize};
+use specta::Type;
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::error::{AppError, AppResult};
+use crate::{current_operator, now};
+
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct AuditEntry {
+    pub timestamp: String,
+    pub operator: String,
+    pub action: String,
+    // e.g. "machine:3", "port:3/22", "note:<id>", "finding:2"
+    pub object: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+fn log_path(dir: &Path, workspace_id: u32) -> AppResult<PathBuf> {
+    let dir = dir.join("audit");
+    fs::create_dir_all(&dir).map_err(|e| AppError::storage("Failed to create audit directory", e))?;
+    Ok(dir.join(format!("workspace_{}.jsonl", workspace_id)))
+}
+
+// Entries are never rewritten, so passwords and hashes of credentials anywhere in the value are
+// masked; otherwise they would stay on disk after the credentials are deleted
+pub(crate) fn to_value<T: Serialize>(value: &T) -> Option<Value> {
+    let mut value = serde_json::to_value(value).ok()?;
+    mask_secrets(&mut value);
+    Some(value)
+}
+
+fn mask_secrets(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            if let Some(Value::Object(credentials)) = map.get_mut("Credentials") {
+                for field in ["password", "hash"] {
+                    if let Some(secret) = credentials.get_mut(field).filter(|secret| !secret.is_null()) {
+                        *secret = Value::String("[redacted]".to_string());
+                    }
+                }
+            }
+            map.values_mut().for_each(mask_secrets);
+        }
+        Value::Array(items) => items.iter_mut().for_each(mask_secrets),
+        _ => {}
+    }
+}
+
+impl AuditEntry {
+    // Made by the current operator, now
+    pub(crate) fn new(action: &str, object: String, before: Option<Value>, after: Option<Value>) -> Self {
+        AuditEntry {
+            timestamp: now(),
+            operator: current_operator(),
+            action: action.to_string(),
+            object,
+            before,
+            after,
+        }
+    }
+}
+
+// Appends one entry to the workspace's log; entries are never rewritten
+pub(crate) fn record(dir: &Path, workspace_id: u32, entry: &AuditEntry) -> AppResult<()> {
+    let line = serde_json::to_string(entry)?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path(dir, workspace_id)?)
+        .map_err(|e| AppError::storage("Failed to open audit log", e))?;
+    writeln!(file, "{}", line).map_err(|e| AppError::storage("Failed to write audit log", e))
+}
+
+// "machine:1" matches machine 1 but not machine 10, "port:1" matches every port of machine 1,
+// and a filter ending in a separator, such as "note:", matches everything under it
+fn object_matches(object: &str, filter: &str) -> bool {
+    object.strip_prefix(filter)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || filter.ends_with(['/', ':']))
+}
+
+// Newest entries first, optionally filtered by object and action
+pub(crate) fn entries(dir: &Path, workspace_id: u32, object: Option<&str>, action: Option<&str>, limit: Option<usize>) -> AppResult<Vec<AuditEntry>> {
+    let path = log_path(dir, workspace_id)?;
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let data = fs::read_to_string(path).map_err(|e| AppError::storage("Failed to read audit log", e))?;
+
+    let mut entries: Vec<AuditEntry> = data.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .filter(|entry: &AuditEntry| object.is_none_or(|o| object_matches(&entry.object, o)))
+        .filter(|entry: &AuditEntry| action.is_none_or(|a| entry.action == a))
+        .collect();
+    entries.reverse();
+    if let Some(limit) = limit {
+        entries.truncate(limit);
+    }
+    Ok(entries)
+}
@@ -0,0 +1,379 @@
+use reqwest::header::CONTENT_TYPE;
+use serde::{Serialize, Deserialize};
+use specta::Type;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+use crate::error::{AppError, AppResult};
+use crate::llm::{self, AiSettings, Prompt};
+
+// Local scanning/AI service the app drives
+const DEFAULT_URL: &str = "http://127.0.0.1:8084";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct ApiPort {
+    pub service: String,
+    pub application: String,
+    pub protocol: String,
+    pub number: u16,
+    pub state: String,
+    pub data: Option<Vec<String>>, // Make data field optional
+    pub details: Option<Vec<String>>,  // Add new field
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct NetworkScan {
+    pub cidr: String,
+    pub active_hosts: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Type)]
+pub(crate) struct ToolState {
+    pub installed: bool,
+    #[serde(default)]
+    pub running: bool,
+}
+
+#[derive(Serialize, Deserialize, Type)]
+pub(crate) struct ToolStatus {
+    pub rustscan: ToolState,
+    pub nmap: ToolState,
+    pub ollama: ToolState,
+}
+
+#[derive(Serialize, Deserialize, Type)]
+pub(crate) struct AiAnswer {
+    pub text: String,
+    pub model: Option<String>,
+}
+
+
+#[derive(Clone)]
+pub(crate) struct Backend {
+    url: String,
+    client: reqwest::Client,
+    // Which model answers AI questions; shared by clones so a change applies everywhere
+    ai: Arc<Mutex<AiSettings>>,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Backend::new(DEFAULT_URL)
+    }
+}
+
+impl Backend {
+    pub(crate) fn new(url: &str) -> Self {
+        Backend {
+            url: url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+            ai: Arc::new(Mutex::new(AiSettings::default())),
+        }
+    }
+
+    pub(crate) fn ai_settings(&self) -> AppResult<AiSettings> {
+        Ok(self.ai.lock()?.clone())
+    }
+
+    pub(crate) fn set_ai_settings(&self, settings: AiSettings) -> AppResult<()> {
+        *self.ai.lock()? = settings;
+        Ok(())
+    }
+
+    // Whether the configured model runs on this machine
+    pub(crate) fn local_model(&self) -> AppResult<bool> {
+        Ok(self.ai_settings()?.is_local(&self.url))
+    }
+
+    // The address and body `ask_stream` would send, without sending them
+    pub(crate) fn request_body(&self, prompt: &Prompt) -> AppResult<(String, Value)> {
+        Ok(llm::request_body(&self.ai_settings()?, &self.url, prompt))
+    }
+
+    // Raw scanner output: a JSON port list, or "N/A" when nothing is open
+    pub(crate) async fn scan(&self, ip: &str) -> AppResult<String> {
+        let response = self.client.get(format!("{}/scan/{}", self.url, ip)).send().await?;
+        Ok(response.text().await?)
+    }
+
+    pub(crate) async fn scan_ports(&self, ip: &str) -> AppResult<Vec<ApiPort>> {
+        parse_scan(&self.scan(ip).await?)
+    }
+
+    pub(crate) async fn discover(&self, ip_range: &str) -> AppResult<NetworkScan> {
+        let response = self.client.get(format!("{}/discover/{}", self.url, ip_range.replace("/", "-"))).send().await?;
+        response.json().await.map_err(|e| AppError::backend("Failed to parse response", e))
+    }
+
+    pub(crate) async fn ask(&self, prompt: &Prompt) -> AppResult<AiAnswer> {
+        self.ask_stream(prompt, |_| {}).await
+    }
+
+    // Asks the configured provider for a streamed answer, handing each piece of text to
+    // `on_text` as it arrives. Answers that aren't streamed are passed on whole at the end.
+    pub(crate) async fn ask_stream(&self, prompt: &Prompt, mut on_text: impl FnMut(&str)) -> AppResult<AiAnswer> {
+        let settings = self.ai_settings()?;
+        let mut response = llm::request(&self.client, &settings, &self.url, prompt).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(AppError::backend(&format!("AI provider answered {}", status), body.trim()));
+        }
+        let content_type = response.headers().get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let mut stream = AnswerStream::new(content_type);
+        while let Some(chunk) = response.chunk().await? {
+            let text = stream.push(&chunk);
+            if !text.is_empty() {
+                on_text(&text);
+            }
+        }
+        let text = stream.finish();
+        if !text.is_empty() {
+            on_text(&text);
+        }
+        let mut answer = stream.answer;
+        if answer.model.is_none() && !settings.model.is_empty() {
+            answer.model = Some(settings.model);
+        }
+        Ok(answer)
+    }
+
+    pub(crate) async fn tools(&self) -> AppResult<ToolStatus> {
+        let response = self.client.get(format!("{}/tools", self.url)).send().await?;
+        response.json().await.map_err(|e| AppError::backend("Failed to parse tool status", e))
+    }
+}
+
+pub(crate) fn parse_scan(response_text: &str) -> AppResult<Vec<ApiPort>> {
+    if response_text.contains("N/A") {
+        return Ok(vec![]);
+    }
+    serde_json::from_str(response_text).map_err(|e| AppError::backend("Failed to parse response", e))
+}
+
+// The AI endpoint answers with {"text", "model"} (or {"response"}), older versions with plain
+// text. Ollama and OpenAI-style answers come as {"message": {"content"}} or {"choices"}.
+pub(crate) fn parse_answer(raw: String) -> AiAnswer {
+    match serde_json::from_str::<serde_json::Value>(&raw) {
+        Ok(parsed) => {
+            let model = parsed.get("model").and_then(|m| m.as_str()).map(|m| m.to_string());
+            let text = parsed.as_object().and_then(piece_text);
+            AiAnswer { text: text.unwrap_or(raw), model }
+        }
+        Err(_) => AiAnswer { text: raw, model: None },
+    }
+}
+
+enum StreamFormat {
+    // One JSON object per line
+    Ndjson,
+    // Server-sent events with a JSON object or plain text in each `data:` line
+    Sse,
+    // Anything else is read whole, as from `ask`
+    Whole,
+}
+
+// Decodes a streamed /ask response into the text it adds, keeping the full answer
+pub(crate) struct AnswerStream {
+    format: StreamFormat,
+    buffer: Vec<u8>,
+    pub answer: AiAnswer,
+}
+
+impl AnswerStream {
+    pub(crate) fn new(content_type: &str) -> Self {
+        let format = match content_type.split(';').next().unwrap_or_default().trim() {
+            "text/event-stream" => StreamFormat::Sse,
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" => StreamFormat::Ndjson,
+            _ => StreamFormat::Whole,
+        };
+        AnswerStream { format, buffer: vec![], answer: AiAnswer { text: String::new(), model: None } }
+    }
+
+    // Feeds the next bytes of the body, returns the text they completed
+    pub(crate) fn push(&mut self, bytes: &[u8]) -> String {
+        self.buffer.extend_from_slice(bytes);
+        if let StreamFormat::Whole = self.format {
+            return String::new();
+        }
+        let mut text = String::new();
+        while let Some(end) = self.buffer.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            text.push_str(&self.line(&String::from_utf8_lossy(&line)));
+        }
+        text
+    }
+
+    // Handles whatever is left once the body ended
+    pub(crate) fn finish(&mut self) -> String {
+        let rest = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned();
+        match self.format {
+            StreamFormat::Whole => {
+                self.answer = parse_answer(rest);
+                self.answer.text.clone()
+            }
+            _ => self.line(&rest),
+        }
+    }
+
+    fn line(&mut self, line: &str) -> String {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let data = match self.format {
+            StreamFormat::Sse => match line.strip_prefix("data:") {
+                Some(data) => data.strip_prefix(' ').unwrap_or(data),
+                // Event names, IDs and comments
+                None => return String::new(),
+            },
+            _ => line,
+        };
+        if data.is_empty() || data == "[DONE]" {
+            return String::new();
+        }
+        let text = match serde_json::from_str::<Value>(data) {
+            Ok(Value::Object(piece)) => {
+                if let Some(model) = piece.get("model").and_then(Value::as_str) {
+                    self.answer.model = Some(model.to_string());
+                }
+                piece_text(&piece).unwrap_or_default()
+            }
+            // SSE lines can carry plain text tokens
+            _ => data.to_string(),
+        };
+        self.answer.text.push_str(&text);
+        text
+    }
+}
+
+// The text of one streamed piece, in the sidecar's fields or Ollama's and OpenAI's
+fn piece_text(piece: &serde_json::Map<String, Value>) -> Option<String> {
+    ["text", "response", "token", "content"].iter()
+        .find_map(|field| piece.get(*field))
+        .or_else(|| piece.get("message").and_then(|message| message.get("content")))
+        .or_else(|| {
+            let choice = piece.get("choices").and_then(|choices| choices.get(0))?;
+            choice.get("delta").or_else(|| choice.get("message")).and_then(|message| message.get("content"))
+        })
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api;
+    use crate::llm::Provider;
+    use axum::http::{header, HeaderMap, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use std::net::TcpListener;
+
+    fn decode(content_type: &str, chunks: &[&str]) -> (Vec<String>, AiAnswer) {
+        let mut stream = AnswerStream::new(content_type);
+        let mut pieces: Vec<String> = chunks.iter().map(|chunk| stream.push(chunk.as_bytes())).collect();
+        pieces.push(stream.finish());
+        pieces.retain(|piece| !piece.is_empty());
+        (pieces, stream.answer)
+    }
+
+    #[test]
+    fn decodes_ndjson_split_across_chunks() {
+        let (pieces, answer) = decode("application/x-ndjson", &[
+            "{\"model\":\"llama3\",\"response\":\"Try \"}\n{\"respo",
+            "nse\":\"null sessions\"}\n{\"done\":true}",
+        ]);
+        assert_eq!(pieces, ["Try ", "null sessions"]);
+        assert_eq!(answer.text, "Try null sessions");
+        assert_eq!(answer.model.as_deref(), Some("llama3"));
+    }
+
+    #[test]
+    fn decodes_sse_events() {
+        let (pieces, answer) = decode("text/event-stream; charset=utf-8", &[
+            ": keep-alive\n\nevent: token\ndata: {\"choices\":[{\"delta\":{\"content\":\"SMB\"}}]}\n\n",
+            "data: signing\r\n\r\ndata: [DONE]\n\n",
+        ]);
+        assert_eq!(pieces, ["SMB", "signing"]);
+        assert_eq!(answer.text, "SMBsigning");
+    }
+
+    #[test]
+    fn unstreamed_answers_arrive_whole() {
+        let (pieces, answer) = decode("application/json", &["{\"text\":\"Patch it\",", "\"model\":\"qwen\"}"]);
+        assert_eq!(pieces, ["Patch it"]);
+        assert_eq!(answer.model.as_deref(), Some("qwen"));
+        assert_eq!(decode("text/plain", &["plain answer"]).1.text, "plain answer");
+    }
+
+    type Requests = Arc<Mutex<Vec<(Option<String>, Value)>>>;
+
+    // Answers like Ollama on /api/chat and like OpenAI on /v1/chat/completions, keeping each
+    // request's Authorization header and body
+    fn mock_providers(seen: Requests) -> String {
+        let ollama = seen.clone();
+        let app = Router::new()
+            .route("/api/chat", post(move |headers: HeaderMap, Json(body): Json<Value>| async move {
+                ollama.lock().unwrap().push((headers.get(header::AUTHORIZATION).map(|value| value.to_str().unwrap().to_string()), body));
+                let lines = "{\"model\":\"llama3.1\",\"message\":{\"role\":\"assistant\",\"content\":\"Try \"},\"done\":false}\n\
+                    {\"model\":\"llama3.1\",\"message\":{\"role\":\"assistant\",\"content\":\"kerberoasting\"},\"done\":true}\n";
+                ([(header::CONTENT_TYPE, "application/x-ndjson")], lines).into_response()
+            }))
+            .route("/v1/chat/completions", post(move |headers: HeaderMap, Json(body): Json<Value>| async move {
+                seen.lock().unwrap().push((headers.get(header::AUTHORIZATION).map(|value| value.to_str().unwrap().to_string()), body));
+                if headers.get(header::AUTHORIZATION).is_none() {
+                    return (StatusCode::UNAUTHORIZED, "missing key").into_response();
+                }
+                let events = "data: {\"model\":\"gpt-4o-mini\",\"choices\":[{\"delta\":{\"content\":\"Use \"}}]}\n\n\
+                    data: {\"choices\":[{\"delta\":{\"content\":\"BloodHound\"}}]}\n\ndata: [DONE]\n\n";
+                ([(header::CONTENT_TYPE, "text/event-stream")], events).into_response()
+            }));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(api::serve_router(listener, app, std::future::pending()));
+        url
+    }
+
+    #[test]
+    fn asks_ollama_and_openai_compatible_providers() {
+        let seen = Requests::default();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let prompt = Prompt::new("Next step on the DC?".to_string());
+        runtime.block_on(async {
+            let url = mock_providers(seen.clone());
+            let backend = Backend::default();
+
+            let ollama = AiSettings { provider: Provider::Ollama, url: url.clone(), model: "llama3.1".to_string(), context_window: 4096, ..AiSettings::default() };
+            backend.set_ai_settings(ollama).unwrap();
+            let mut streamed = String::new();
+            let answer = backend.ask_stream(&prompt, |text| streamed.push_str(text)).await.unwrap();
+            assert_eq!(streamed, "Try kerberoasting");
+            assert_eq!(answer.model.as_deref(), Some("llama3.1"));
+
+            let mut openai = AiSettings {
+                provider: Provider::OpenAi,
+                url: format!("{}/v1", url),
+                model: "gpt-4o-mini".to_string(),
+                temperature: 0.7,
+                ..AiSettings::default()
+            };
+            backend.set_ai_settings(openai.clone()).unwrap();
+            assert!(matches!(backend.ask(&prompt).await, Err(AppError::BackendUnavailable(message)) if message.contains("401")));
+            openai.api_key = Some("sk-test".to_string());
+            backend.set_ai_settings(openai).unwrap();
+            assert_eq!(backend.ask(&prompt).await.unwrap().text, "Use BloodHound");
+        });
+
+        let seen = seen.lock().unwrap();
+        let (auth, body) = &seen[0];
+        assert!(auth.is_none());
+        assert_eq!(body["model"], "llama3.1");
+        assert_eq!(body["options"]["num_ctx"], 4096);
+        assert_eq!(body["messages"][1]["content"], "Next step on the DC?");
+        let (auth, body) = &seen[2];
+        assert_eq!(auth.as_deref(), Some("Bearer sk-test"));
+        assert_eq!(body["temperature"].as_f64().unwrap() as f32, 0.7);
+        assert_eq!(body["stream"], true);
+    }
+}
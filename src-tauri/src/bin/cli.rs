@@ -0,0 +1,4 @@
+// Headless client for the same data directory, see `automasploit-cli --help`
+fn main() -> std::process::ExitCode {
+    automasploit_app_lib::run_cli()
+}
@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{AppError, AppResult, Entity};
+use crate::{now, ChatContext};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Role {
+    User,
+    Assistant,
+}
+
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+    pub timestamp: String,
+    // Which model answered, for assistant messages
+    pub model: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct Conversation {
+    pub id: String,
+    pub title: String,
+    pub workspace_id: u32,
+    pub machine_id: Option<u32>,
+    pub port_number: Option<u16>,
+    pub created: String,
+    pub updated: String,
+    pub messages: Vec<ChatMessage>,
+}
+
+// A conversation without its messages, for listings
+#[derive(Serialize, Type)]
+pub(crate) struct ConversationSummary {
+    pub id: String,
+    pub title: String,
+    pub machine_id: Option<u32>,
+    pub port_number: Option<u16>,
+    pub updated: String,
+    pub message_count: u32,
+}
+
+impl Conversation {
+    // Titled after the first question
+    pub(crate) fn new(context: &ChatContext, question: &str) -> Self {
+        let mut title: String = question.lines().next().unwrap_or_default().chars().take(60).collect();
+        if title.trim().is_empty() {
+            title = "New conversation".to_string();
+        }
+        let created = now();
+        Conversation {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: title.trim().to_string(),
+            workspace_id: context.workspace_id,
+            machine_id: context.machine_id,
+            port_number: context.machine_id.and(context.port_number),
+            created: created.clone(),
+            updated: created,
+            messages: vec![],
+        }
+    }
+
+    // The workspace, machine or port the conversation is about
+    pub(crate) fn context(&self) -> ChatContext {
+        let type_ = match (self.machine_id, self.port_number) {
+            (Some(_), Some(_)) => "port",
+            (Some(_), None) => "machine",
+            _ => "workspace",
+        };
+        ChatContext {
+            type_: type_.to_string(),
+            workspace_id: self.workspace_id,
+            machine_id: self.machine_id,
+            port_number: self.port_number,
+        }
+    }
+
+    pub(crate) fn push(&mut self, role: Role, content: String, model: Option<String>) {
+        let timestamp = now();
+        self.updated = timestamp.clone();
+        self.messages.push(ChatMessage { role, content, timestamp, model });
+    }
+
+    pub(crate) fn summary(&self) -> ConversationSummary {
+        ConversationSummary {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            machine_id: self.machine_id,
+            port_number: self.port_number,
+            updated: self.updated.clone(),
+            message_count: self.messages.len() as u32,
+        }
+    }
+}
+
+pub(crate) fn get<'a>(conversations: &'a [Conversation], id: &str) -> AppResult<&'a Conversation> {
+    conversations.iter()
+        .find(|conversation| conversation.id == id)
+        .ok_or_else(|| AppError::not_found(Entity::Conversation, id))
+}
+
+pub(crate) fn find<'a>(conversations: &'a mut [Conversation], id: &str) -> AppResult<&'a mut Conversation> {
+    conversations.iter_mut()
+        .find(|conversation| conversation.id == id)
+        .ok_or_else(|| AppError::not_found(Entity::Conversation, id))
+}
+
+// Rough count for budgeting; about four characters per token for English text
+pub(crate) fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+// The newest messages that fit in `budget` tokens. Whole messages only, and never an answer
+// without the question before it.
+pub(crate) fn recent_history(messages: &[ChatMessage], budget: usize) -> &[ChatMessage] {
+    let mut used = 0;
+    let mut start = messages.len();
+    for (index, message) in messages.iter().enumerate().rev() {
+        used += estimate_tokens(&message.content);
+        if used > budget {
+            break;
+        }
+        start = index;
+    }
+    while messages.get(start).is_some_and(|message| message.role == Role::Assistant) {
+        start += 1;
+    }
+    &messages[start..]
+}
+
+pub(crate) fn to_markdown(conversation: &Conversation) -> String {
+    let mut markdown = format!("# {}\n\nStarted {}\n", conversation.title, conversation.created);
+    for message in &conversation.messages {
+        let speaker = match (message.role, &message.model) {
+            (Role::User, _) => "You".to_string(),
+            (Role::Assistant, Some(model)) => format!("Assistant ({})", model),
+            (Role::Assistant, None) => "Assistant".to_string(),
+        };
+        markdown.push_str(&format!("\n## {} - {}\n\n{}\n", speaker, message.timestamp, message.content.trim()));
+    }
+    markdown
+}
+
+fn conversations_path(dir: &Path, workspace_id: u32) -> PathBuf {
+    dir.join("chats").join(format!("workspace_{}.json", workspace_id))
+}
+
+pub(crate) fn load(dir: &Path, workspace_id: u32) -> AppResult<Vec<Conversation>> {
+    let path = conversations_path(dir, workspace_id);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let data = fs::read_to_string(path).map_err(|e| AppError::storage("Failed to read conversations", e))?;
+    serde_json::from_str(&data).map_err(|e| AppError::storage("Failed to parse conversations", e))
+}
+
+pub(crate) fn save(dir: &Path, workspace_id: u32, conversations: &[Conversation]) -> AppResult<()> {
+    let path = conversations_path(dir, workspace_id);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| AppError::storage("Failed to create chats directory", e))?;
+    }
+    fs::write(path, serde_json::to_string_pretty(conversations)?)
+        .map_err(|e| AppError::storage("Failed to write conversations", e))
+}
+
+// Conversations go with their workspace
+pub(crate) fn remove(dir: &Path, workspace_id: u32) -> AppResult<()> {
+    let path = conversations_path(dir, workspace_id);
+    if path.exists() {
+        fs::remove_file(path).map_err(|e| AppError::storage("Failed to delete conversations", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: Role, content: &str) -> ChatMessage {
+        ChatMessage { role, content: content.to_string(), timestamp: String::new(), model: None }
+    }
+
+    #[test]
+    fn history_keeps_the_newest_messages_within_budget() {
+        let messages = vec![
+            message(Role::User, &"a".repeat(400)),
+            message(Role::Assistant, &"b".repeat(40)),
+            message(Role::User, &"c".repeat(40)),
+            message(Role::Assistant, &"d".repeat(40)),
+        ];
+        assert_eq!(recent_history(&messages, 1000).len(), 4);
+        // The first question doesn't fit, so its answer is left out too
+        let history = recent_history(&messages, 40);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].role, Role::User);
+        assert!(recent_history(&messages, 5).is_empty());
+    }
+
+    #[test]
+    fn context_follows_the_conversation() {
+        let chat = ChatContext { type_: "port".to_string(), workspace_id: 1, machine_id: Some(2), port_number: Some(445) };
+        let mut conversation = Conversation::new(&chat, "How do I enumerate shares?\nAnonymous login works");
+        assert_eq!(conversation.title, "How do I enumerate shares?");
+        assert_eq!(conversation.context().type_, "port");
+
+        conversation.push(Role::User, "How do I enumerate shares?".to_string(), None);
+        conversation.push(Role::Assistant, "Use smbclient -L".to_string(), Some("llama3".to_string()));
+        let markdown = to_markdown(&conversation);
+        assert!(markdown.starts_with("# How do I enumerate shares?"));
+        assert!(markdown.contains("## Assistant (llama3)"));
+        assert_eq!(conversation.summary().message_count, 2);
+    }
+
+    #[test]
+    fn corrupt_conversations_are_a_storage_error() {
+        let dir = tempfile::tempdir().unwrap();
+        save(dir.path(), 1, &[]).unwrap();
+        fs::write(conversations_path(dir.path(), 1), "[{ not json").unwrap();
+        assert!(matches!(load(dir.path(), 1), Err(AppError::Storage(message)) if message.starts_with("Failed to parse conversations")));
+    }
+}
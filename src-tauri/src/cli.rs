@@ -0,0 +1,412 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use serde_json::json;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use crate::api;
+use crate::backend::Backend;
+use crate::error::{AppError, AppResult};
+use crate::report::{ReportFormat, ReportOptions};
+use crate::services::{machines, notes, ports, reports, scans, workspaces};
+use crate::store::{self, Store};
+use crate::tags;
+use crate::team;
+use crate::PortNotes;
+
+// Same store and services as the desktop app, for boxes without a display
+#[derive(Parser)]
+#[command(name = "automasploit-cli", version, about = "Work with NetVision workspaces from a terminal")]
+struct Cli {
+    /// Directory holding database.json [default: $NETVISION_DATA_DIR, then the desktop app's]
+    #[arg(long, global = true)]
+    data_dir: Option<PathBuf>,
+    /// Scanning service URL
+    #[arg(long, global = true, default_value = "http://127.0.0.1:8084")]
+    backend: String,
+    /// Print JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List workspaces
+    Workspaces {
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Create a workspace
+    AddWorkspace { name: String, ip_range: String },
+    /// List the machines of a workspace
+    Machines {
+        workspace: u32,
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Add a machine to a workspace
+    AddMachine { workspace: u32, hostname: String, ip: String },
+    /// List the ports of a machine
+    Ports {
+        workspace: u32,
+        machine: u32,
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Discover live hosts in the workspace's IP range and add them
+    Discover { workspace: u32 },
+    /// Port scan one machine, or every machine of the workspace
+    Scan { workspace: u32, machine: Option<u32> },
+    /// List workspace, machine or port notes
+    Notes {
+        workspace: u32,
+        #[arg(long)]
+        machine: Option<u32>,
+        #[arg(long, requires = "machine")]
+        port: Option<u16>,
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Add a note to a workspace, machine or port
+    AddNote {
+        workspace: u32,
+        #[arg(long)]
+        machine: Option<u32>,
+        #[arg(long, requires = "machine")]
+        port: Option<u16>,
+        #[arg(long, default_value = "Information Gathering")]
+        stage: String,
+        content: String,
+    },
+    /// Record credentials found on a service
+    AddCredentials {
+        workspace: u32,
+        machine: u32,
+        port: u16,
+        #[arg(long)]
+        username: Option<String>,
+        #[arg(long)]
+        password: Option<String>,
+        #[arg(long)]
+        hash: Option<String>,
+    },
+    /// Render the workspace report to a file, or to stdout without --output
+    Report {
+        workspace: u32,
+        #[arg(long, value_enum, default_value_t = Format::Markdown)]
+        format: Format,
+        #[arg(long)]
+        output: Option<String>,
+        #[arg(long)]
+        include_credentials: bool,
+    },
+    /// Merge an exported database or workspace file, matching workspaces by name
+    Merge {
+        path: String,
+        /// Merge a single exported workspace into this one instead
+        #[arg(long)]
+        workspace: Option<u32>,
+    },
+    /// Run the local HTTP API in the foreground, with the port and token from the app's settings
+    Serve {
+        #[arg(long)]
+        port: Option<u16>,
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Run a team server the desktop apps can share workspaces through
+    TeamServer {
+        /// Address to listen on; use 0.0.0.0 to accept other machines, over plain HTTP
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: String,
+        #[arg(long, default_value_t = team::DEFAULT_PORT)]
+        port: u16,
+    },
+    /// Add a team server user and print their token
+    AddTeamUser { name: String },
+    /// List the team server's users
+    TeamUsers,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Markdown,
+    Html,
+}
+
+impl From<Format> for ReportFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Markdown => ReportFormat::Markdown,
+            Format::Html => ReportFormat::Html,
+        }
+    }
+}
+
+// Enum values as they appear in the JSON, e.g. "Enumerated"
+fn name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+fn first_line(text: &str) -> String {
+    text.lines().next().unwrap_or_default().to_string()
+}
+
+fn table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<String>| {
+        let padded: Vec<String> = cells.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+        padded.join("  ").trim_end().to_string()
+    };
+    let mut lines = vec![line(headers.iter().map(|header| header.to_string()).collect())];
+    lines.extend(rows.into_iter().map(line));
+    lines.join("\n")
+}
+
+// JSON when asked for, the table otherwise
+fn output<T: Serialize>(as_json: bool, value: &T, table: impl FnOnce(&T) -> String) -> AppResult<String> {
+    if as_json {
+        Ok(serde_json::to_string_pretty(value)?)
+    } else {
+        Ok(table(value))
+    }
+}
+
+fn message(as_json: bool, text: String, value: serde_json::Value) -> AppResult<String> {
+    if as_json {
+        Ok(serde_json::to_string_pretty(&value)?)
+    } else {
+        Ok(text)
+    }
+}
+
+async fn execute(store: &Store, backend: &Backend, command: Command, as_json: bool) -> AppResult<String> {
+    match command {
+        Command::Workspaces { tag } => {
+            let list = workspaces::list(store, tag.as_deref())?;
+            output(as_json, &list, |list| table(&["ID", "NAME", "RANGE", "TAGS"], list.iter().map(|workspace| vec![
+                workspace.id.to_string(), workspace.name.clone(), workspace.ip_range.clone(), workspace.tags.join(","),
+            ]).collect()))
+        }
+        Command::AddWorkspace { name, ip_range } => {
+            let id = workspaces::add(store, name, ip_range)?;
+            message(as_json, format!("Workspace {} added", id), json!({ "id": id }))
+        }
+        Command::Machines { workspace, tag } => {
+            workspaces::get(store, workspace)?;
+            let list = machines::list(store, workspace, tag.as_deref())?;
+            output(as_json, &list, |list| table(&["ID", "HOSTNAME", "IP", "STATUS", "OS", "TAGS"], list.iter().map(|machine| vec![
+                machine.id.to_string(),
+                machine.hostname.clone(),
+                machine.ip.clone(),
+                name(&machine.status),
+                machine.fingerprint.as_ref()
+                    .map(|fingerprint| fingerprint.os_name.clone().unwrap_or_else(|| name(&fingerprint.os)))
+                    .unwrap_or_default(),
+                machine.tags.join(","),
+            ]).collect()))
+        }
+        Command::AddMachine { workspace, hostname, ip } => {
+            let id = machines::add(store, workspace, hostname, ip)?;
+            message(as_json, format!("Machine {} added", id), json!({ "id": id }))
+        }
+        Command::Ports { workspace, machine, tag } => {
+            machines::get(store, workspace, machine)?;
+            let list = ports::list(store, workspace, machine, tag.as_deref())?;
+            output(as_json, &list, |list| table(&["PORT", "PROTOCOL", "STATE", "SERVICE", "APPLICATION", "CVES"], list.iter().map(|port| vec![
+                port.number.to_string(),
+                port.protocol.clone(),
+                port.state.clone(),
+                port.service.clone(),
+                port.application.clone(),
+                port.cves.len().to_string(),
+            ]).collect()))
+        }
+        Command::Discover { workspace } => {
+            let count = scans::discover_hosts(store, backend, workspace).await?;
+            message(as_json, format!("Network scan completed. Found and added {} hosts", count), json!({ "hosts": count }))
+        }
+        Command::Scan { workspace, machine } => {
+            let targets = match machine {
+                Some(machine) => vec![machine],
+                None => machines::list(store, workspace, None)?.into_iter().map(|machine| machine.id).collect(),
+            };
+            let mut results = vec![];
+            for machine in targets {
+                let count = scans::scan_machine(store, backend, workspace, machine).await?;
+                results.push(json!({ "machine": machine, "ports": count }));
+            }
+            let text = results.iter()
+                .map(|result| format!("Machine {}: {} open ports", result["machine"], result["ports"]))
+                .collect::<Vec<_>>()
+                .join("\n");
+            message(as_json, text, json!(results))
+        }
+        Command::Merge { path, workspace } => {
+            let report = workspaces::merge(store, &path, workspace)?;
+            output(as_json, &report, |report| {
+                let summary = format!(
+                    "Added {} workspaces, {} machines, {} ports and {} notes with {} conflicts",
+                    report.workspaces_added, report.machines_added, report.ports_added, report.notes_added, report.conflicts.len(),
+                );
+                if report.conflicts.is_empty() {
+                    return summary;
+                }
+                let conflicts = table(&["WORKSPACE", "FIELD", "KEPT", "THEIRS"], report.conflicts.iter().map(|conflict| vec![
+                    conflict.workspace.clone(), conflict.field.clone(), first_line(&conflict.ours.to_string()), first_line(&conflict.theirs.to_string()),
+                ]).collect());
+                format!("{}\n\n{}", summary, conflicts)
+            })
+        }
+        Command::Notes { workspace, machine, port: Some(port), tag } => {
+            let port = ports::get(store, workspace, machine.unwrap_or_default(), port)?;
+            // Only pentest notes carry tags
+            let data: Vec<&PortNotes> = port.data.iter().filter(|note| match note {
+                PortNotes::PentestNote { tags, .. } => tags::has_tag(tags, tag.as_deref()),
+                _ => tag.is_none(),
+            }).collect();
+            let rows: Vec<Vec<String>> = data.iter().filter_map(|note| match note {
+                PortNotes::PentestNote { id, stage, author, content, .. } => Some(vec![id.clone(), stage.clone(), author.clone(), first_line(content)]),
+                PortNotes::Credentials { name, hash, password, .. } => Some(vec![
+                    String::new(),
+                    "Credentials".to_string(),
+                    String::new(),
+                    [name, password, hash].iter().map(|value| value.as_deref().unwrap_or_default()).collect::<Vec<_>>().join(" / "),
+                ]),
+                _ => None,
+            }).collect();
+            output(as_json, &data, |_| table(&["ID", "STAGE", "AUTHOR", "CONTENT"], rows))
+        }
+        Command::Notes { workspace, machine, port: None, tag } => {
+            let list = notes::list(store, workspace, machine, tag.as_deref())?;
+            output(as_json, &list, |list| table(&["ID", "STAGE", "AUTHOR", "CONTENT"], list.iter().map(|note| vec![
+                note.id.clone(), note.stage.clone(), note.author.clone(), first_line(&note.content),
+            ]).collect()))
+        }
+        Command::AddNote { workspace, machine, port, stage, content } => {
+            let id = match (machine, port) {
+                (Some(machine), Some(port)) => ports::add_note(store, workspace, machine, port, stage, content)?,
+                _ => notes::add(store, workspace, machine, stage, content)?,
+            };
+            message(as_json, id.clone(), json!({ "id": id }))
+        }
+        Command::AddCredentials { workspace, machine, port, username, password, hash } => {
+            ports::add_credentials(store, workspace, machine, port, username, hash, password)?;
+            message(as_json, "Credentials saved".to_string(), json!({ "saved": true }))
+        }
+        Command::Report { workspace, format, output, include_credentials } => {
+            let options = ReportOptions { include_credentials };
+            match output {
+                Some(path) => {
+                    reports::export(store, workspace, format.into(), options, &path)?;
+                    message(as_json, format!("Report exported to {}", path), json!({ "path": path }))
+                }
+                None => reports::generate(store, workspace, format.into(), options),
+            }
+        }
+        Command::Serve { port, token } => {
+            let settings = api::load_settings(store.dir())?;
+            let port = port.unwrap_or(settings.port);
+            let token = token.unwrap_or(settings.token);
+            let listener = api::bind(port)?;
+            eprintln!("Serving http://127.0.0.1:{}/api with token {}", port, token);
+            api::serve(listener, store.clone(), backend.clone(), token, std::future::pending()).await?;
+            Ok(String::new())
+        }
+        Command::TeamServer { bind, port } => {
+            let listener = std::net::TcpListener::bind((bind.as_str(), port))
+                .map_err(|e| AppError::validation(format!("Cannot listen on {}:{}: {}", bind, port, e)))?;
+            eprintln!("Team server listening on http://{}:{}", bind, port);
+            team::serve(listener, store.clone(), std::future::pending()).await?;
+            Ok(String::new())
+        }
+        Command::AddTeamUser { name } => {
+            let user = team::add_user(store.dir(), &name)?;
+            message(as_json, format!("Token for {}: {}", user.name, user.token), json!({ "name": user.name, "token": user.token }))
+        }
+        Command::TeamUsers => {
+            let users = team::load_users(store.dir())?;
+            let names: Vec<&str> = users.iter().map(|user| user.name.as_str()).collect();
+            output(as_json, &names, |names| names.join("\n"))
+        }
+    }
+}
+
+pub(crate) fn run() -> ExitCode {
+    let cli = Cli::parse();
+    let result = (|| -> AppResult<String> {
+        let dir = match cli.data_dir {
+            Some(dir) => dir,
+            None => store::default_data_dir()?,
+        };
+        let store = Store::open(dir)?;
+        let backend = Backend::new(&cli.backend);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| AppError::Internal(format!("Failed to start runtime: {}", e)))?;
+        runtime.block_on(execute(&store, &backend, cli.command, cli.json))
+    })();
+
+    match result {
+        Ok(text) => {
+            // A closed pipe (`| head`) is not an error worth panicking over
+            if !text.is_empty() {
+                let _ = writeln!(io::stdout(), "{}", text);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::temp_store;
+    use futures::executor::block_on;
+
+    fn run_args(store: &Store, args: &[&str]) -> AppResult<String> {
+        let cli = Cli::try_parse_from([&["automasploit-cli"], args].concat()).unwrap();
+        block_on(execute(store, &Backend::default(), cli.command, cli.json))
+    }
+
+    #[test]
+    fn builds_a_workspace_from_the_command_line() {
+        let (_dir, store) = temp_store();
+        assert_eq!(run_args(&store, &["add-workspace", "Acme", "10.0.0.0/24"]).unwrap(), "Workspace 1 added");
+        assert_eq!(run_args(&store, &["add-machine", "1", "dc01", "10.0.0.5"]).unwrap(), "Machine 1 added");
+        run_args(&store, &["add-note", "1", "--machine", "1", "--stage", "Enumeration", "SMB signing disabled"]).unwrap();
+
+        let machines = run_args(&store, &["machines", "1"]).unwrap();
+        assert!(machines.starts_with("ID  HOSTNAME  IP"));
+        assert!(machines.contains("dc01      10.0.0.5  Untouched"));
+        assert!(run_args(&store, &["notes", "1", "--machine", "1"]).unwrap().contains("SMB signing disabled"));
+        assert!(!run_args(&store, &["notes", "1", "--machine", "1", "--tag", "dc"]).unwrap().contains("SMB signing disabled"));
+
+        let json: serde_json::Value = serde_json::from_str(&run_args(&store, &["--json", "workspaces"]).unwrap()).unwrap();
+        assert_eq!(json[0]["name"], "Acme");
+        assert!(run_args(&store, &["report", "1"]).unwrap().contains("dc01"));
+    }
+
+    #[test]
+    fn reports_missing_objects() {
+        let (_dir, store) = temp_store();
+        assert_eq!(run_args(&store, &["machines", "4"]).unwrap_err().to_string(), "Workspace '4' not found");
+        assert!(Cli::try_parse_from(["automasploit-cli", "notes", "1", "--port", "22"]).is_err());
+    }
+}
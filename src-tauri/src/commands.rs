@@ -0,0 +1,720 @@
+// Tauri commands of the desktop app, thin wrappers around `services`
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_specta::{collect_commands, ErrorHandlingMode};
+#[cfg(debug_assertions)]
+use specta_typescript::{BigIntExportBehavior, Typescript};
+
+use crate::api::{ApiServer, ApiStatus};
+use crate::audit::AuditEntry;
+use crate::backend::{Backend, ToolStatus};
+use crate::compromise::{AccessLevel, CompromiseSummary, Evidence, StatusChange};
+use crate::cve::CveFeedStatus;
+use crate::cvss::CvssScore;
+use crate::error::{AppError, AppResult};
+use crate::notes::RevisionView;
+use crate::findings::{Finding, FindingInput, FindingStatus, FindingSummary, Severity};
+use crate::fingerprint::OsFingerprint;
+use crate::llm::AiSettings;
+use crate::merge::MergeReport;
+use crate::query::{PortPage, PortQuery};
+use crate::report::{ReportFormat, ReportOptions};
+use crate::search::SearchHit;
+use crate::services::{ai, cves, machines, ports, reports, scans, workspaces};
+use crate::services::ai::{AiChunk, AiRequests, ChatReply, PromptPreview};
+use crate::redact::RedactionPolicy;
+use crate::chat::{Conversation, ConversationSummary};
+use crate::store::Store;
+use crate::sync::{SyncClient, SyncLink, SyncStatus};
+use crate::tags::{Tag, TagTarget};
+use crate::team::RemoteWorkspace;
+use crate::{cvss, llm, services, ChatContext, JsMachine, JsPort, JsWorkspace, Note};
+
+#[tauri::command]
+#[specta::specta]
+async fn scan_ip(backend: State<'_, Backend>, ip: String) -> AppResult<String> {
+    backend.scan(&ip).await
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn scan_machine(store: State<'_, Store>, backend: State<'_, Backend>, workspace_id: u32, machine_id: u32) -> AppResult<String> {
+    match scans::scan_machine(&store, &backend, workspace_id, machine_id).await? {
+        0 => Ok("Scan completed. No open ports found.".to_string()),
+        count => Ok(format!("Scan completed successfully. Found {} ports", count)),
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+fn workspaces(store: State<Store>, tag: Option<String>) -> AppResult<Vec<JsWorkspace>> {
+    workspaces::list(&store, tag.as_deref())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn machines(store: State<Store>, workspace_id: u32, tag: Option<String>) -> AppResult<Vec<JsMachine>> {
+    machines::list(&store, workspace_id, tag.as_deref())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn ports(store: State<Store>, workspace_id: u32, machine_id: u32, tag: Option<String>) -> AppResult<Vec<JsPort>> {
+    ports::list(&store, workspace_id, machine_id, tag.as_deref())
+}
+
+// Workspace-wide port listing, e.g. every host with 445 open or every Apache 2.4 service
+#[tauri::command]
+#[specta::specta]
+fn query_ports(store: State<Store>, workspace_id: u32, query: Option<PortQuery>) -> AppResult<PortPage> {
+    workspaces::query_ports(&store, workspace_id, &query.unwrap_or_default())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn get_machine(store: State<Store>, workspace_id: u32, machine_id: u32) -> AppResult<JsMachine> {
+    machines::get(&store, workspace_id, machine_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn get_workspace(store: State<Store>, workspace_id: u32) -> AppResult<JsWorkspace> {
+    workspaces::get(&store, workspace_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn get_port(store: State<Store>, workspace_id: u32, machine_id: u32, port_number: u16) -> AppResult<JsPort> {
+    ports::get(&store, workspace_id, machine_id, port_number)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn add_workspace(store: State<Store>, name: String, ip_range: String) -> AppResult<String> {
+    workspaces::add(&store, name, ip_range)?;
+    Ok("Workspace added successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn add_machine(store: State<Store>, workspace_id: u32, name: String, ip: String) -> AppResult<String> {
+    machines::add(&store, workspace_id, name, ip)?;
+    Ok("Machine added successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn discover_hosts(store: State<'_, Store>, backend: State<'_, Backend>, workspace_id: u32) -> AppResult<String> {
+    let count = scans::discover_hosts(&store, &backend, workspace_id).await?;
+    Ok(format!("Network scan completed. Found and added {} hosts", count))
+}
+
+#[tauri::command]
+#[specta::specta]
+fn add_credentials(
+    store: State<Store>,
+    workspace_id: u32,
+    machine_id: u32,
+    port_number: u16,
+    name: Option<String>,
+    hash: Option<String>,
+    password: Option<String>,
+) -> AppResult<String> {
+    ports::add_credentials(&store, workspace_id, machine_id, port_number, name, hash, password)?;
+    Ok("Credentials added successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn add_port_note(
+    store: State<Store>,
+    workspace_id: u32,
+    machine_id: u32,
+    port_number: u16,
+    stage: String,
+    content: String,
+) -> AppResult<String> {
+    ports::add_note(&store, workspace_id, machine_id, port_number, stage, content)
+}
+
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+fn edit_credentials(
+    store: State<Store>,
+    workspace_id: u32,
+    machine_id: u32,
+    port_number: u16,
+    note_id: String,
+    name: Option<String>,
+    hash: Option<String>,
+    password: Option<String>,
+) -> AppResult<()> {
+    ports::edit_credentials(&store, workspace_id, machine_id, port_number, &note_id, name, hash, password)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn edit_port_note(
+    store: State<Store>,
+    workspace_id: u32,
+    machine_id: u32,
+    port_number: u16,
+    note_id: String,
+    stage: Option<String>,
+    content: Option<String>,
+) -> AppResult<String> {
+    ports::edit_note(&store, workspace_id, machine_id, port_number, &note_id, stage, content)?;
+    Ok("Note updated successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn delete_port_note(store: State<Store>, workspace_id: u32, machine_id: u32, port_number: u16, note_id: String) -> AppResult<String> {
+    ports::delete_note(&store, workspace_id, machine_id, port_number, &note_id)?;
+    Ok("Note deleted successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn reorder_port_notes(store: State<Store>, workspace_id: u32, machine_id: u32, port_number: u16, note_ids: Vec<String>) -> AppResult<String> {
+    ports::reorder_notes(&store, workspace_id, machine_id, port_number, &note_ids)?;
+    Ok("Notes reordered successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn move_port_note(
+    store: State<Store>,
+    workspace_id: u32,
+    machine_id: u32,
+    port_number: u16,
+    note_id: String,
+    target_machine_id: u32,
+    target_port_number: u16,
+) -> AppResult<String> {
+    ports::move_note(&store, workspace_id, machine_id, port_number, &note_id, target_machine_id, target_port_number)?;
+    Ok("Note moved successfully".to_string())
+}
+
+// The answer arrives as "ai-chunk" events carrying `request_id`, and whole once it's done.
+// Leave out `conversation_id` to start a new conversation.
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+async fn ask_question(
+    app: AppHandle,
+    store: State<'_, Store>,
+    backend: State<'_, Backend>,
+    requests: State<'_, AiRequests>,
+    request_id: String,
+    conversation_id: Option<String>,
+    question: String,
+    context: ChatContext,
+) -> AppResult<ChatReply> {
+    ai::ask_question(&store, &backend, &requests, &request_id, conversation_id.as_deref(), &question, &context, |text| {
+        let _ = app.emit("ai-chunk", AiChunk { request_id: request_id.clone(), text: text.to_string() });
+    }).await
+}
+
+// What asking the question would send, after redaction
+#[tauri::command]
+#[specta::specta]
+fn preview_prompt(
+    store: State<Store>,
+    backend: State<Backend>,
+    conversation_id: Option<String>,
+    question: String,
+    context: ChatContext,
+) -> AppResult<PromptPreview> {
+    ai::preview_prompt(&store, &backend, conversation_id.as_deref(), &question, &context)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn set_redaction_policy(store: State<Store>, workspace_id: u32, policy: RedactionPolicy) -> AppResult<()> {
+    workspaces::set_redaction(&store, workspace_id, policy)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn cancel_question(requests: State<AiRequests>, request_id: String) -> AppResult<bool> {
+    requests.cancel(&request_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn ai_settings(backend: State<Backend>) -> AppResult<AiSettings> {
+    backend.ai_settings()
+}
+
+#[tauri::command]
+#[specta::specta]
+fn update_ai_settings(store: State<Store>, backend: State<Backend>, settings: AiSettings) -> AppResult<AiSettings> {
+    ai::update_settings(&store, &backend, settings)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn conversations(store: State<Store>, workspace_id: u32, machine_id: Option<u32>, port_number: Option<u16>) -> AppResult<Vec<ConversationSummary>> {
+    services::chat::list(&store, workspace_id, machine_id, port_number)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn get_conversation(store: State<Store>, workspace_id: u32, conversation_id: String) -> AppResult<Conversation> {
+    services::chat::get(&store, workspace_id, &conversation_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn rename_conversation(store: State<Store>, workspace_id: u32, conversation_id: String, title: String) -> AppResult<String> {
+    services::chat::rename(&store, workspace_id, &conversation_id, title)?;
+    Ok("Conversation renamed successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn delete_conversation(store: State<Store>, workspace_id: u32, conversation_id: String) -> AppResult<String> {
+    services::chat::delete(&store, workspace_id, &conversation_id)?;
+    Ok("Conversation deleted successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn export_conversation(store: State<Store>, workspace_id: u32, conversation_id: String, path: String) -> AppResult<String> {
+    services::chat::export(&store, workspace_id, &conversation_id, &path)?;
+    Ok(format!("Conversation exported to {}", path))
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn check_tools(backend: State<'_, Backend>) -> AppResult<ToolStatus> {
+    backend.tools().await
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn analyze_port(store: State<'_, Store>, backend: State<'_, Backend>, workspace_id: u32, machine_id: u32, port_number: u16) -> AppResult<String> {
+    ai::analyze_port(&store, &backend, workspace_id, machine_id, port_number).await?;
+    Ok("Analysis completed and saved".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn machine_notes(store: State<Store>, workspace_id: u32, machine_id: u32, tag: Option<String>) -> AppResult<Vec<Note>> {
+    services::notes::list(&store, workspace_id, Some(machine_id), tag.as_deref())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn add_machine_note(store: State<Store>, workspace_id: u32, machine_id: u32, stage: String, content: String) -> AppResult<String> {
+    services::notes::add(&store, workspace_id, Some(machine_id), stage, content)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn update_machine_note(store: State<Store>, workspace_id: u32, machine_id: u32, note_id: String, stage: String, content: String) -> AppResult<String> {
+    services::notes::update(&store, workspace_id, Some(machine_id), &note_id, stage, content)?;
+    Ok("Note updated successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn delete_machine_note(store: State<Store>, workspace_id: u32, machine_id: u32, note_id: String) -> AppResult<String> {
+    services::notes::delete(&store, workspace_id, Some(machine_id), &note_id)?;
+    Ok("Note deleted successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn workspace_notes(store: State<Store>, workspace_id: u32, tag: Option<String>) -> AppResult<Vec<Note>> {
+    services::notes::list(&store, workspace_id, None, tag.as_deref())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn add_workspace_note(store: State<Store>, workspace_id: u32, stage: String, content: String) -> AppResult<String> {
+    services::notes::add(&store, workspace_id, None, stage, content)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn update_workspace_note(store: State<Store>, workspace_id: u32, note_id: String, stage: String, content: String) -> AppResult<String> {
+    services::notes::update(&store, workspace_id, None, &note_id, stage, content)?;
+    Ok("Note updated successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn delete_workspace_note(store: State<Store>, workspace_id: u32, note_id: String) -> AppResult<String> {
+    services::notes::delete(&store, workspace_id, None, &note_id)?;
+    Ok("Note deleted successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn generate_report(store: State<Store>, workspace_id: u32, format: ReportFormat, include_credentials: Option<bool>) -> AppResult<String> {
+    let options = ReportOptions { include_credentials: include_credentials.unwrap_or(false) };
+    reports::generate(&store, workspace_id, format, options)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn export_report(store: State<Store>, workspace_id: u32, format: ReportFormat, path: String, include_credentials: Option<bool>) -> AppResult<String> {
+    let options = ReportOptions { include_credentials: include_credentials.unwrap_or(false) };
+    reports::export(&store, workspace_id, format, options, &path)?;
+    Ok(format!("Report exported to {}", path))
+}
+
+#[tauri::command]
+#[specta::specta]
+fn report_template(store: State<Store>, format: ReportFormat) -> AppResult<String> {
+    reports::template(&store, format)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn save_report_template(store: State<Store>, format: ReportFormat, template: String) -> AppResult<String> {
+    reports::save_template(&store, format, &template)?;
+    Ok("Report template saved successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn reset_report_template(store: State<Store>, format: ReportFormat) -> AppResult<String> {
+    reports::reset_template(&store, format)?;
+    Ok("Report template reset to default".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn findings(store: State<Store>, workspace_id: u32, severity: Option<Severity>, status: Option<FindingStatus>) -> AppResult<Vec<Finding>> {
+    services::findings::list(&store, workspace_id, severity, status)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn get_finding(store: State<Store>, workspace_id: u32, finding_id: u32) -> AppResult<Finding> {
+    services::findings::get(&store, workspace_id, finding_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn add_finding(store: State<Store>, workspace_id: u32, finding: FindingInput) -> AppResult<u32> {
+    services::findings::add(&store, workspace_id, finding)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn update_finding(store: State<Store>, workspace_id: u32, finding_id: u32, finding: FindingInput) -> AppResult<String> {
+    services::findings::update(&store, workspace_id, finding_id, finding)?;
+    Ok("Finding updated successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn delete_finding(store: State<Store>, workspace_id: u32, finding_id: u32) -> AppResult<String> {
+    services::findings::delete(&store, workspace_id, finding_id)?;
+    Ok("Finding deleted successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn finding_summary(store: State<Store>, workspace_id: u32) -> AppResult<FindingSummary> {
+    services::findings::summary(&store, workspace_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn cvss_score(vector: String) -> AppResult<CvssScore> {
+    cvss::score(&vector).map_err(AppError::Validation)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn import_cve_feed(store: State<Store>, path: String) -> AppResult<String> {
+    let count = cves::import_feed(&store, &path)?;
+    Ok(format!("Imported {} CVEs", count))
+}
+
+#[tauri::command]
+#[specta::specta]
+fn import_cpe_dictionary(store: State<Store>, path: String) -> AppResult<String> {
+    let count = cves::import_dictionary(&store, &path)?;
+    Ok(format!("Imported {} CPE dictionary entries", count))
+}
+
+// Merges another exported database.json or workspace; conflicting fields keep our value
+#[tauri::command]
+#[specta::specta]
+fn merge_database(store: State<Store>, path: String, workspace_id: Option<u32>) -> AppResult<MergeReport> {
+    workspaces::merge(&store, &path, workspace_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn cve_feed_status(store: State<Store>) -> AppResult<CveFeedStatus> {
+    cves::status(&store)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn match_cves(store: State<Store>, workspace_id: u32, machine_id: Option<u32>) -> AppResult<String> {
+    let matched = cves::match_cves(&store, workspace_id, machine_id)?;
+    Ok(format!("Matched {} candidate CVEs", matched))
+}
+
+#[tauri::command]
+#[specta::specta]
+fn note_revisions(store: State<Store>, workspace_id: u32, note_id: String) -> AppResult<Vec<RevisionView>> {
+    services::notes::revisions(&store, workspace_id, &note_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn restore_note_revision(store: State<Store>, workspace_id: u32, note_id: String, revision: usize) -> AppResult<String> {
+    services::notes::restore(&store, workspace_id, &note_id, revision)?;
+    Ok("Note restored successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn fingerprint_machine(store: State<Store>, workspace_id: u32, machine_id: u32) -> AppResult<Option<OsFingerprint>> {
+    machines::fingerprint(&store, workspace_id, machine_id)
+}
+
+// Pass no icon to go back to the detected one
+#[tauri::command]
+#[specta::specta]
+fn set_machine_icon(store: State<Store>, workspace_id: u32, machine_id: u32, icon: Option<String>) -> AppResult<String> {
+    machines::set_icon(&store, workspace_id, machine_id, icon)?;
+    Ok("Machine icon updated successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn set_machine_status(
+    store: State<Store>,
+    workspace_id: u32,
+    machine_id: u32,
+    status: AccessLevel,
+    evidence: Option<Evidence>,
+    comment: Option<String>,
+) -> AppResult<String> {
+    machines::set_status(&store, workspace_id, machine_id, status, evidence, comment)?;
+    Ok("Machine status updated successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn machine_status_history(store: State<Store>, workspace_id: u32, machine_id: u32) -> AppResult<Vec<StatusChange>> {
+    machines::status_history(&store, workspace_id, machine_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn compromise_summary(store: State<Store>, workspace_id: u32) -> AppResult<CompromiseSummary> {
+    machines::compromise_summary(&store, workspace_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn tags(store: State<Store>) -> AppResult<Vec<Tag>> {
+    services::tags::list(&store)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn save_tag(store: State<Store>, name: String, color: Option<String>) -> AppResult<Tag> {
+    services::tags::save(&store, &name, color)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn delete_tag(store: State<Store>, name: String) -> AppResult<String> {
+    services::tags::delete(&store, &name)?;
+    Ok("Tag deleted successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn tag_object(store: State<Store>, workspace_id: u32, target: TagTarget, tag: String) -> AppResult<String> {
+    services::tags::tag(&store, workspace_id, &target, &tag)?;
+    Ok("Tag added successfully".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+fn untag_object(store: State<Store>, workspace_id: u32, target: TagTarget, tag: String) -> AppResult<String> {
+    services::tags::untag(&store, workspace_id, &target, &tag)?;
+    Ok("Tag removed successfully".to_string())
+}
+
+// Supports filters such as service:ssh, stage:Exploitation, port:445, host:dc01, workspace:2, tag:dc and kind:credentials
+#[tauri::command]
+#[specta::specta]
+fn search(store: State<Store>, query: String, limit: Option<usize>) -> AppResult<Vec<SearchHit>> {
+    workspaces::search(&store, &query, limit)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn audit_log(store: State<Store>, workspace_id: u32, object: Option<String>, action: Option<String>, limit: Option<usize>) -> AppResult<Vec<AuditEntry>> {
+    workspaces::audit_log(&store, workspace_id, object.as_deref(), action.as_deref(), limit)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn api_settings(server: State<ApiServer>) -> AppResult<ApiStatus> {
+    server.status()
+}
+
+// Restarting the API server waits for its requests to finish, which mustn't hold up the main thread
+async fn restart_api(server: &ApiServer, f: impl FnOnce(&ApiServer) -> AppResult<ApiStatus> + Send + 'static) -> AppResult<ApiStatus> {
+    let server = server.clone();
+    tauri::async_runtime::spawn_blocking(move || f(&server)).await
+        .map_err(|e| AppError::Internal(format!("Failed to restart the API server: {}", e)))?
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn update_api_settings(server: State<'_, ApiServer>, enabled: bool, port: Option<u16>) -> AppResult<ApiStatus> {
+    restart_api(&server, move |server| server.update(enabled, port)).await
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn regenerate_api_token(server: State<'_, ApiServer>) -> AppResult<ApiStatus> {
+    restart_api(&server, |server| server.regenerate_token()).await
+}
+
+#[tauri::command]
+#[specta::specta]
+fn sync_status(sync: State<SyncClient>) -> AppResult<SyncStatus> {
+    sync.status()
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn connect_team_server(sync: State<'_, SyncClient>, url: String, token: String) -> AppResult<SyncStatus> {
+    sync.connect(&url, &token).await
+}
+
+#[tauri::command]
+#[specta::specta]
+fn disconnect_team_server(sync: State<SyncClient>) -> AppResult<SyncStatus> {
+    sync.disconnect()
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn team_workspaces(sync: State<'_, SyncClient>) -> AppResult<Vec<RemoteWorkspace>> {
+    sync.remote_workspaces().await
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn share_workspace(sync: State<'_, SyncClient>, workspace_id: u32) -> AppResult<SyncLink> {
+    sync.share(workspace_id).await
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn join_team_workspace(sync: State<'_, SyncClient>, remote_id: u32) -> AppResult<u32> {
+    sync.join(remote_id).await
+}
+
+#[tauri::command]
+#[specta::specta]
+fn unlink_workspace(sync: State<SyncClient>, workspace_id: u32) -> AppResult<()> {
+    sync.unlink(workspace_id)
+}
+
+fn builder() -> tauri_specta::Builder<tauri::Wry> {
+    tauri_specta::Builder::<tauri::Wry>::new()
+        .error_handling(ErrorHandlingMode::Throw)
+        .commands(collect_commands![
+            workspaces, machines, ports, get_machine, get_workspace, get_port,
+            scan_ip, scan_machine, add_workspace, add_machine, discover_hosts,
+            add_credentials, add_port_note, edit_port_note, delete_port_note, edit_credentials,
+            reorder_port_notes, move_port_note, ask_question, cancel_question, check_tools,
+            conversations, get_conversation, rename_conversation, delete_conversation, export_conversation,
+            ai_settings, update_ai_settings, preview_prompt, set_redaction_policy,
+            analyze_port, generate_report, export_report, report_template,
+            save_report_template, reset_report_template, findings, get_finding,
+            add_finding, update_finding, delete_finding, finding_summary, cvss_score,
+            import_cve_feed, import_cpe_dictionary, cve_feed_status, match_cves,
+            machine_notes, add_machine_note, update_machine_note, delete_machine_note,
+            workspace_notes, add_workspace_note, update_workspace_note, delete_workspace_note,
+            note_revisions, restore_note_revision,
+            search, query_ports,
+            tags, save_tag, delete_tag, tag_object, untag_object,
+            set_machine_status, machine_status_history, compromise_summary,
+            fingerprint_machine, set_machine_icon,
+            audit_log, merge_database,
+            api_settings, update_api_settings, regenerate_api_token,
+            sync_status, connect_team_server, disconnect_team_server, team_workspaces,
+            share_workspace, join_team_workspace, unlink_workspace
+        ])
+        .typ::<AiChunk>()
+}
+
+#[cfg(any(debug_assertions, test))]
+fn typescript() -> Typescript {
+    Typescript::default().bigint(BigIntExportBehavior::Number)
+}
+
+pub fn run() {
+    let store = Store::open_default().expect("Failed to open workspaces directory");
+    let backend = Backend::default();
+    backend.set_ai_settings(llm::load_settings(store.dir())).expect("Failed to apply AI settings");
+    let api_server = ApiServer::new(store.clone(), backend.clone());
+    // A port taken by something else shouldn't keep the app from starting
+    if let Err(e) = api_server.apply() {
+        eprintln!("Failed to start local API: {}", e);
+    }
+
+    let builder = builder();
+
+    // The frontend imports these from $lib/bindings; regenerated by every debug build
+    #[cfg(debug_assertions)]
+    builder
+        .export(typescript(), "../src/lib/bindings.ts")
+        .expect("Failed to export TypeScript bindings");
+
+    let sync_store = store.clone();
+    tauri::Builder::default()
+        .setup(move |app| {
+            let handle = app.app_handle().clone();
+            // Lets open pages reload a workspace after teammates' changes were merged in
+            let sync = SyncClient::new(sync_store, move |workspace_id| {
+                let _ = handle.emit("workspace-synced", workspace_id);
+            })?;
+            sync.start()?;
+            app.manage(sync);
+            Ok(())
+        })
+        .manage(store)
+        .manage(backend)
+        .manage(AiRequests::default())
+        .manage(api_server)
+        .invoke_handler(builder.invoke_handler())
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // The bindings are checked in, so a command changed without a debug build fails here
+    #[test]
+    fn bindings_are_up_to_date() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bindings.ts");
+        builder().export(typescript(), &path).unwrap();
+        let checked_in = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/../src/lib/bindings.ts")).unwrap();
+        assert!(fs::read_to_string(&path).unwrap() == checked_in, "src/lib/bindings.ts is out of date, regenerate it with a debug build");
+    }
+}
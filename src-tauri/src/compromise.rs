@@ -0,0 +1,164 @@
+use serde::{Serialize, Deserialize};
+use specta::Type;
+use std::collections::BTreeMap;
+
+use crate::error::{AppError, AppResult, Entity};
+use crate::{current_operator, notes, now, Machine, PortNotes, Workspace};
+
+// How far the engagement got on a machine, in increasing order of access
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Type)]
+pub(crate) enum AccessLevel {
+    #[default]
+    Untouched,
+    Enumerated,
+    Foothold,
+    User,
+    // root, SYSTEM or equivalent
+    Root,
+    // Used to reach other networks
+    Pivot,
+}
+
+impl AccessLevel {
+    pub(crate) fn is_compromised(self) -> bool {
+        self >= AccessLevel::Foothold
+    }
+}
+
+// What justified a status change
+#[derive(Clone, Serialize, Deserialize, Type)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub(crate) enum Evidence {
+    Note { note_id: String },
+    // Evidence recorded before credentials had IDs has none and no longer points at anything
+    Credential {
+        machine_id: u32,
+        port_number: u16,
+        #[serde(default)]
+        credential_id: String,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct StatusChange {
+    pub from: AccessLevel,
+    pub to: AccessLevel,
+    pub timestamp: String,
+    pub operator: String,
+    pub evidence: Option<Evidence>,
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize, Type)]
+pub(crate) struct MachineProgress {
+    pub machine_id: u32,
+    pub hostname: String,
+    pub ip: String,
+    pub status: AccessLevel,
+    // When the machine reached its current status
+    pub since: Option<String>,
+}
+
+#[derive(Serialize, Type)]
+pub(crate) struct CompromiseSummary {
+    pub total: usize,
+    pub compromised: usize,
+    pub percent_compromised: f32,
+    pub by_status: BTreeMap<String, usize>,
+    pub machines: Vec<MachineProgress>,
+}
+
+fn status_name(status: AccessLevel) -> String {
+    serde_json::to_value(status).ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn validate_evidence(workspace: &Workspace, evidence: &Evidence) -> AppResult<()> {
+    match evidence {
+        Evidence::Note { note_id } => notes::view_in_workspace(workspace, note_id)
+            .map(|_| ())
+            .ok_or_else(|| AppError::not_found(Entity::Note, note_id)),
+        Evidence::Credential { machine_id, port_number, credential_id } => {
+            let port = workspace.data.iter()
+                .find(|machine| machine.id == *machine_id)
+                .and_then(|machine| machine.ports.iter().find(|port| port.number == *port_number))
+                .ok_or_else(|| AppError::not_found(Entity::Port, port_number))?;
+            let found = port.data.iter().any(|note| matches!(note,
+                PortNotes::Credentials { id, .. } if !id.is_empty() && id == credential_id));
+            if found {
+                Ok(())
+            } else {
+                Err(AppError::not_found(Entity::Credential, credential_id))
+            }
+        }
+    }
+}
+
+// Scanning a machine nobody has touched yet counts as enumeration
+pub(crate) fn mark_enumerated(machine: &mut Machine) {
+    if machine.status == AccessLevel::Untouched {
+        machine.status = AccessLevel::Enumerated;
+        machine.status_history.push(StatusChange {
+            from: AccessLevel::Untouched,
+            to: AccessLevel::Enumerated,
+            timestamp: now(),
+            operator: current_operator(),
+            evidence: None,
+            comment: Some("Port scan".to_string()),
+        });
+    }
+}
+
+pub(crate) fn set_status(workspace: &mut Workspace, machine_id: u32, status: AccessLevel, evidence: Option<Evidence>, comment: Option<String>) -> AppResult<StatusChange> {
+    if let Some(evidence) = &evidence {
+        validate_evidence(workspace, evidence)?;
+    }
+    let machine = workspace.data.iter_mut().find(|machine| machine.id == machine_id)
+        .ok_or_else(|| AppError::not_found(Entity::Machine, machine_id))?;
+    if machine.status == status {
+        return Err(AppError::validation(format!("Machine '{}' is already marked as {}", machine_id, status_name(status))));
+    }
+
+    let change = StatusChange {
+        from: machine.status,
+        to: status,
+        timestamp: now(),
+        operator: current_operator(),
+        evidence,
+        comment: comment.filter(|comment| !comment.trim().is_empty()),
+    };
+    machine.status = status;
+    machine.status_history.push(change.clone());
+    Ok(change)
+}
+
+fn progress(machine: &Machine) -> MachineProgress {
+    MachineProgress {
+        machine_id: machine.id,
+        hostname: machine.hostname.clone(),
+        ip: machine.ip.clone(),
+        status: machine.status,
+        since: machine.status_history.last().map(|change| change.timestamp.clone()),
+    }
+}
+
+pub(crate) fn summarize(workspace: &Workspace) -> CompromiseSummary {
+    let mut by_status = BTreeMap::new();
+    for machine in &workspace.data {
+        *by_status.entry(status_name(machine.status)).or_default() += 1;
+    }
+    let total = workspace.data.len();
+    let compromised = workspace.data.iter().filter(|machine| machine.status.is_compromised()).count();
+
+    let mut machines: Vec<MachineProgress> = workspace.data.iter().map(progress).collect();
+    machines.sort_by(|a, b| b.status.cmp(&a.status).then_with(|| a.machine_id.cmp(&b.machine_id)));
+
+    CompromiseSummary {
+        total,
+        compromised,
+        percent_compromised: if total == 0 { 0.0 } else { compromised as f32 * 100.0 / total as f32 },
+        by_status,
+        machines,
+    }
+}
@@ -0,0 +1,475 @@
+use serde::{Serialize, Deserialize};
+use specta::Type;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cvss::Rating;
+use crate::error::{AppError, AppResult};
+use crate::Port;
+
+// nmap product names that don't map 1:1 onto CPE vendor/product pairs; NVD files some products under several vendors
+const PRODUCT_ALIASES: [(&str, &[&str]); 24] = [
+    ("apache httpd", &["apache:http_server"]),
+    ("apache tomcat", &["apache:tomcat"]),
+    ("apache jserv", &["apache:tomcat"]),
+    ("openssh", &["openbsd:openssh"]),
+    ("nginx", &["f5:nginx", "nginx:nginx"]),
+    ("microsoft iis httpd", &["microsoft:internet_information_services"]),
+    ("microsoft sql server", &["microsoft:sql_server"]),
+    ("microsoft terminal services", &["microsoft:remote_desktop_services"]),
+    ("vsftpd", &["beasts:vsftpd"]),
+    ("proftpd", &["proftpd:proftpd"]),
+    ("pure-ftpd", &["pureftpd:pure-ftpd"]),
+    ("mysql", &["oracle:mysql"]),
+    ("mariadb", &["mariadb:mariadb"]),
+    ("postgresql", &["postgresql:postgresql"]),
+    ("samba smbd", &["samba:samba"]),
+    ("postfix smtpd", &["postfix:postfix"]),
+    ("exim smtpd", &["exim:exim"]),
+    ("isc bind", &["isc:bind"]),
+    ("dnsmasq", &["thekelleys:dnsmasq"]),
+    ("lighttpd", &["lighttpd:lighttpd"]),
+    ("jetty", &["eclipse:jetty"]),
+    ("redis key-value store", &["redis:redis"]),
+    ("mongodb", &["mongodb:mongodb"]),
+    ("dovecot imapd", &["dovecot:dovecot"]),
+];
+
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct CveMatch {
+    pub id: String,
+    pub score: Option<f64>,
+    pub rating: Option<Rating>,
+    pub vector: Option<String>,
+    pub description: String,
+    pub cpe: String,
+}
+
+// One vulnerable CPE range of a CVE, flattened out of the NVD configuration tree
+#[derive(Clone, Serialize, Deserialize)]
+struct CpeRange {
+    version: String,
+    update: String,
+    start_including: Option<String>,
+    start_excluding: Option<String>,
+    end_including: Option<String>,
+    end_excluding: Option<String>,
+    criteria: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CveEntry {
+    id: String,
+    score: Option<f64>,
+    vector: Option<String>,
+    description: String,
+    ranges: Vec<CpeRange>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct CveIndex {
+    imported_at: Option<String>,
+    // "vendor:product" -> CVEs affecting it
+    products: HashMap<String, Vec<CveEntry>>,
+    // Lowercased CPE dictionary titles -> "vendor:product"
+    #[serde(default)]
+    dictionary: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Type)]
+pub(crate) struct CveFeedStatus {
+    pub imported_at: Option<String>,
+    pub cves: usize,
+    pub products: usize,
+    pub dictionary_entries: usize,
+}
+
+fn index_path(dir: &Path) -> PathBuf {
+    dir.join("cve_index.json")
+}
+
+pub(crate) fn load_index(dir: &Path) -> CveIndex {
+    fs::read_to_string(index_path(dir))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+pub(crate) fn save_index(dir: &Path, index: &CveIndex) -> AppResult<()> {
+    let json = serde_json::to_string(index)?;
+    fs::write(index_path(dir), json).map_err(|e| AppError::storage("Failed to write CVE index", e))
+}
+
+impl CveIndex {
+    pub fn is_empty(&self) -> bool {
+        self.products.is_empty()
+    }
+
+    pub fn status(&self) -> CveFeedStatus {
+        let mut ids: Vec<&String> = self.products.values().flatten().map(|entry| &entry.id).collect();
+        ids.sort();
+        ids.dedup();
+        CveFeedStatus {
+            imported_at: self.imported_at.clone(),
+            cves: ids.len(),
+            products: self.products.len(),
+            dictionary_entries: self.dictionary.len(),
+        }
+    }
+
+    // Imports an NVD JSON feed (1.1 data feed or 2.0 API format); newer entries replace older ones
+    pub fn import_feed(&mut self, path: &str) -> AppResult<usize> {
+        let data = fs::read_to_string(path).map_err(|e| AppError::storage("Failed to read CVE feed", e))?;
+        let feed: Value = serde_json::from_str(&data)
+            .map_err(|e| AppError::validation(format!("Failed to parse CVE feed: {}", e)))?;
+
+        let entries: Vec<(CveEntry, Vec<String>)> = if let Some(items) = feed.get("CVE_Items").and_then(Value::as_array) {
+            items.iter().filter_map(parse_v1_item).collect()
+        } else if let Some(items) = feed.get("vulnerabilities").and_then(Value::as_array) {
+            items.iter().filter_map(|item| item.get("cve")).filter_map(parse_v2_item).collect()
+        } else {
+            return Err(AppError::validation("Unrecognized CVE feed: expected NVD 'CVE_Items' or 'vulnerabilities'"));
+        };
+
+        let count = entries.len();
+        for (entry, products) in entries {
+            for product in products {
+                let list = self.products.entry(product).or_default();
+                list.retain(|existing| existing.id != entry.id);
+                list.push(entry.clone());
+            }
+        }
+        self.imported_at = Some(chrono::Local::now().to_rfc3339());
+        Ok(count)
+    }
+
+    // Imports an NVD CPE dictionary in the 2.0 API JSON format
+    pub fn import_dictionary(&mut self, path: &str) -> AppResult<usize> {
+        let data = fs::read_to_string(path).map_err(|e| AppError::storage("Failed to read CPE dictionary", e))?;
+        let dictionary: Value = serde_json::from_str(&data)
+            .map_err(|e| AppError::validation(format!("Failed to parse CPE dictionary: {}", e)))?;
+        let products = dictionary.get("products").and_then(Value::as_array)
+            .ok_or_else(|| AppError::validation("Unrecognized CPE dictionary: expected 'products'"))?;
+
+        let mut count = 0;
+        for product in products {
+            let cpe = match product.pointer("/cpe/cpeName").and_then(Value::as_str) {
+                Some(cpe) => cpe,
+                None => continue,
+            };
+            let parts: Vec<&str> = cpe.split(':').collect();
+            if parts.len() < 5 || parts[2] != "a" {
+                continue;
+            }
+            let key = format!("{}:{}", parts[3], parts[4]);
+            for title in product.pointer("/cpe/titles").and_then(Value::as_array).into_iter().flatten() {
+                if let Some(title) = title.get("title").and_then(Value::as_str) {
+                    // Titles carry the version at the end ("Apache Tomcat 9.0.1"); index the product part
+                    let name = strip_version(&title.to_lowercase());
+                    if !name.is_empty() && self.dictionary.insert(name, key.clone()).is_none() {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    // Candidate CVEs for a port's application string, highest score first
+    pub fn match_port(&self, port: &Port) -> Vec<CveMatch> {
+        let (products, version) = match self.normalize(&port.application) {
+            Some(normalized) => normalized,
+            None => return vec![],
+        };
+        let (version, update) = split_update(&version);
+
+        let mut matches: Vec<CveMatch> = products.iter().filter_map(|product| self.products.get(product)).flatten()
+            .filter_map(|entry| {
+                entry.ranges.iter()
+                    .find(|range| range_matches(range, &version, &update))
+                    .map(|range| CveMatch {
+                        id: entry.id.clone(),
+                        score: entry.score,
+                        rating: entry.score.map(Rating::from_score),
+                        vector: entry.vector.clone(),
+                        description: entry.description.clone(),
+                        cpe: range.criteria.clone(),
+                    })
+            })
+            .collect();
+        // A CVE filed under several of the product's keys is listed once
+        let mut seen = HashSet::new();
+        matches.retain(|cve| seen.insert(cve.id.clone()));
+        matches.sort_by(|a, b| {
+            b.score.unwrap_or(0.0).partial_cmp(&a.score.unwrap_or(0.0)).unwrap_or(Ordering::Equal)
+                .then_with(|| b.id.cmp(&a.id))
+        });
+        matches
+    }
+
+    // Turns an nmap application string into its "vendor:product" keys and version
+    fn normalize(&self, application: &str) -> Option<(Vec<String>, String)> {
+        let lower = application.to_lowercase();
+        let tokens: Vec<&str> = lower.split_whitespace().collect();
+        let version_pos = tokens.iter().position(|token| looks_like_version(token))?;
+        let version = tokens[version_pos].to_string();
+        let name = tokens[..version_pos].join(" ");
+        if name.is_empty() {
+            return None;
+        }
+
+        if let Some((_, keys)) = PRODUCT_ALIASES.iter().find(|(alias, _)| name == *alias) {
+            return Some((keys.iter().map(|key| key.to_string()).collect(), version));
+        }
+        if let Some(key) = self.dictionary.get(&name) {
+            return Some((vec![key.clone()], version));
+        }
+        // Fall back to the "product:product" convention many CPEs follow
+        let product = name.replace(' ', "_");
+        Some((vec![format!("{}:{}", product, product)], version))
+    }
+}
+
+fn looks_like_version(token: &str) -> bool {
+    token.chars().next().is_some_and(|c| c.is_ascii_digit())
+        && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_')
+}
+
+fn strip_version(title: &str) -> String {
+    let tokens: Vec<&str> = title.split_whitespace().collect();
+    let end = tokens.iter().position(|token| looks_like_version(token)).unwrap_or(tokens.len());
+    tokens[..end].join(" ")
+}
+
+// OpenSSH style versions ("8.2p1") keep the patch level in the CPE update field
+fn split_update(version: &str) -> (String, String) {
+    if let Some(pos) = version.find('p') {
+        let (base, update) = version.split_at(pos);
+        if !base.is_empty() && update[1..].chars().all(|c| c.is_ascii_digit()) && update.len() > 1 {
+            return (base.to_string(), update.to_string());
+        }
+    }
+    (version.to_string(), "*".to_string())
+}
+
+// A run of digits or letters within one segment of a version: "2p1" is 2, "p", 1
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Run<'a> {
+    // Where one version has letters and the other digits, the letters sort first
+    Alpha(&'a str),
+    Number(u64),
+}
+
+fn runs(segment: &str) -> Vec<Run<'_>> {
+    let mut runs = vec![];
+    let mut rest = segment;
+    while let Some(first) = rest.chars().next() {
+        let end = rest.find(|c: char| c.is_ascii_digit() != first.is_ascii_digit()).unwrap_or(rest.len());
+        let (run, tail) = rest.split_at(end);
+        runs.push(match run.parse() {
+            Ok(number) => Run::Number(number),
+            Err(_) => Run::Alpha(run),
+        });
+        rest = tail;
+    }
+    runs
+}
+
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let split = |v: &str| -> Vec<String> {
+        v.split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    let (a, b) = (split(a), split(b));
+    for i in 0..a.len().max(b.len()) {
+        let ordering = match (a.get(i), b.get(i)) {
+            (Some(x), Some(y)) => runs(x).cmp(&runs(y)),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => Ordering::Equal,
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+fn range_matches(range: &CpeRange, version: &str, update: &str) -> bool {
+    let bounded = range.start_including.is_some() || range.start_excluding.is_some()
+        || range.end_including.is_some() || range.end_excluding.is_some();
+
+    if range.version != "*" && range.version != "-" {
+        let update_matches = range.update == "*" || range.update == "-" || update == "*" || range.update == update;
+        return compare_versions(&range.version, version) == Ordering::Equal && update_matches;
+    }
+    if !bounded {
+        // "All versions" entries are too broad to be useful as candidates
+        return false;
+    }
+
+    let full = if update == "*" { version.to_string() } else { format!("{}{}", version, update) };
+    range.start_including.as_deref().is_none_or(|v| compare_versions(&full, v) != Ordering::Less)
+        && range.start_excluding.as_deref().is_none_or(|v| compare_versions(&full, v) == Ordering::Greater)
+        && range.end_including.as_deref().is_none_or(|v| compare_versions(&full, v) != Ordering::Greater)
+        && range.end_excluding.as_deref().is_none_or(|v| compare_versions(&full, v) == Ordering::Less)
+}
+
+fn parse_range(criteria: &str, node: &Value) -> Option<(String, CpeRange)> {
+    // cpe:2.3:part:vendor:product:version:update:...
+    let parts: Vec<&str> = criteria.split(':').collect();
+    if parts.len() < 7 || parts[2] != "a" {
+        return None;
+    }
+    let field = |name: &str| node.get(name).and_then(Value::as_str).map(str::to_string);
+    Some((
+        format!("{}:{}", parts[3], parts[4]),
+        CpeRange {
+            version: parts[5].to_string(),
+            update: parts[6].to_string(),
+            start_including: field("versionStartIncluding"),
+            start_excluding: field("versionStartExcluding"),
+            end_including: field("versionEndIncluding"),
+            end_excluding: field("versionEndExcluding"),
+            criteria: criteria.to_string(),
+        },
+    ))
+}
+
+fn collect_ranges(nodes: &[Value], match_key: &str, uri_key: &str, ranges: &mut Vec<(String, CpeRange)>) {
+    for node in nodes {
+        for cpe_match in node.get(match_key).and_then(Value::as_array).into_iter().flatten() {
+            if cpe_match.get("vulnerable").and_then(Value::as_bool) != Some(true) {
+                continue;
+            }
+            if let Some(range) = cpe_match.get(uri_key).and_then(Value::as_str).and_then(|uri| parse_range(uri, cpe_match)) {
+                ranges.push(range);
+            }
+        }
+        if let Some(children) = node.get("children").and_then(Value::as_array) {
+            collect_ranges(children, match_key, uri_key, ranges);
+        }
+    }
+}
+
+fn build_entry(id: String, score: Option<f64>, vector: Option<String>, description: String, ranges: Vec<(String, CpeRange)>) -> Option<(CveEntry, Vec<String>)> {
+    if ranges.is_empty() {
+        return None;
+    }
+    let mut products: Vec<String> = ranges.iter().map(|(product, _)| product.clone()).collect();
+    products.sort();
+    products.dedup();
+    Some((
+        CveEntry { id, score, vector, description, ranges: ranges.into_iter().map(|(_, range)| range).collect() },
+        products,
+    ))
+}
+
+fn parse_v1_item(item: &Value) -> Option<(CveEntry, Vec<String>)> {
+    let id = item.pointer("/cve/CVE_data_meta/ID")?.as_str()?.to_string();
+    let description = item.pointer("/cve/description/description_data/0/value")
+        .and_then(Value::as_str).unwrap_or_default().to_string();
+    let (score, vector) = match item.pointer("/impact/baseMetricV3/cvssV3") {
+        Some(cvss) => (cvss.get("baseScore").and_then(Value::as_f64), cvss.get("vectorString").and_then(Value::as_str)),
+        None => match item.pointer("/impact/baseMetricV2/cvssV2") {
+            Some(cvss) => (cvss.get("baseScore").and_then(Value::as_f64), cvss.get("vectorString").and_then(Value::as_str)),
+            None => (None, None),
+        },
+    };
+
+    let mut ranges = vec![];
+    if let Some(nodes) = item.pointer("/configurations/nodes").and_then(Value::as_array) {
+        collect_ranges(nodes, "cpe_match", "cpe23Uri", &mut ranges);
+    }
+    build_entry(id, score, vector.map(str::to_string), description, ranges)
+}
+
+fn parse_v2_item(cve: &Value) -> Option<(CveEntry, Vec<String>)> {
+    let id = cve.get("id")?.as_str()?.to_string();
+    let description = cve.get("descriptions").and_then(Value::as_array).into_iter().flatten()
+        .find(|d| d.get("lang").and_then(Value::as_str) == Some("en"))
+        .and_then(|d| d.get("value")).and_then(Value::as_str)
+        .unwrap_or_default().to_string();
+
+    let metric = ["cvssMetricV40", "cvssMetricV31", "cvssMetricV30", "cvssMetricV2"].iter()
+        .find_map(|key| cve.pointer(&format!("/metrics/{}/0/cvssData", key)));
+    let score = metric.and_then(|m| m.get("baseScore")).and_then(Value::as_f64);
+    let vector = metric.and_then(|m| m.get("vectorString")).and_then(Value::as_str).map(str::to_string);
+
+    let mut ranges = vec![];
+    for configuration in cve.get("configurations").and_then(Value::as_array).into_iter().flatten() {
+        if let Some(nodes) = configuration.get("nodes").and_then(Value::as_array) {
+            collect_ranges(nodes, "cpeMatch", "criteria", &mut ranges);
+        }
+    }
+    build_entry(id, score, vector, description, ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start_including: Option<&str>, end_excluding: Option<&str>, end_including: Option<&str>) -> CpeRange {
+        CpeRange {
+            version: "*".to_string(),
+            update: "*".to_string(),
+            start_including: start_including.map(str::to_string),
+            start_excluding: None,
+            end_including: end_including.map(str::to_string),
+            end_excluding: end_excluding.map(str::to_string),
+            criteria: String::new(),
+        }
+    }
+
+    #[test]
+    fn versions_compare_numerically_within_segments() {
+        assert_eq!(compare_versions("8.2p1", "8.10"), Ordering::Less);
+        assert_eq!(compare_versions("8.9p1", "8.10p1"), Ordering::Less);
+        assert_eq!(compare_versions("8.2p1", "8.2p2"), Ordering::Less);
+        assert_eq!(compare_versions("8.2", "8.2p1"), Ordering::Less);
+        assert_eq!(compare_versions("2.4.49", "2.4.5"), Ordering::Greater);
+        assert_eq!(compare_versions("2.4.49", "2.4.49"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Less);
+        assert_eq!(split_update("8.2p1"), ("8.2".to_string(), "p1".to_string()));
+    }
+
+    #[test]
+    fn range_bounds_are_inclusive_or_exclusive() {
+        let below_8_10 = range(Some("8.0"), Some("8.10"), None);
+        assert!(range_matches(&below_8_10, "8.2", "p1"));
+        assert!(range_matches(&below_8_10, "8.0", "*"));
+        assert!(!range_matches(&below_8_10, "8.10", "*"));
+        assert!(!range_matches(&below_8_10, "7.9", "p1"));
+
+        let up_to_2_4_49 = range(None, None, Some("2.4.49"));
+        assert!(range_matches(&up_to_2_4_49, "2.4.49", "*"));
+        assert!(range_matches(&up_to_2_4_49, "2.4.5", "*"));
+        assert!(!range_matches(&up_to_2_4_49, "2.4.50", "*"));
+
+        // Unbounded "all versions" entries never match
+        assert!(!range_matches(&range(None, None, None), "1.0", "*"));
+    }
+
+    #[test]
+    fn aliases_match_every_vendor_of_a_product() {
+        let entry = |id: &str| CveEntry {
+            id: id.to_string(),
+            score: Some(7.5),
+            vector: None,
+            description: String::new(),
+            ranges: vec![range(Some("1.0"), Some("1.21"), None)],
+        };
+        let mut index = CveIndex::default();
+        index.products.insert("f5:nginx".to_string(), vec![entry("CVE-2021-23017"), entry("CVE-2019-20372")]);
+        index.products.insert("nginx:nginx".to_string(), vec![entry("CVE-2019-20372"), entry("CVE-2013-2028")]);
+
+        let port = crate::store::tests::test_port(80, "http", "nginx 1.18.0", vec![]);
+        let ids: Vec<String> = index.match_port(&port).into_iter().map(|cve| cve.id).collect();
+        assert_eq!(ids, ["CVE-2021-23017", "CVE-2019-20372", "CVE-2013-2028"]);
+    }
+}
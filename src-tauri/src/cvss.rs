@@ -0,0 +1,699 @@
+use serde::{Serialize, Deserialize};
+use specta::Type;
+use std::collections::HashMap;
+
+use crate::findings::Severity;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize, Type)]
+pub(crate) enum CvssVersion {
+    #[serde(rename = "3.1")]
+    V3_1,
+    #[serde(rename = "4.0")]
+    V4_0,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize, Type)]
+pub(crate) enum Rating {
+    None,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Rating {
+    pub fn from_score(score: f64) -> Rating {
+        if score == 0.0 {
+            Rating::None
+        } else if score < 4.0 {
+            Rating::Low
+        } else if score < 7.0 {
+            Rating::Medium
+        } else if score < 9.0 {
+            Rating::High
+        } else {
+            Rating::Critical
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Rating::None => Severity::Info,
+            Rating::Low => Severity::Low,
+            Rating::Medium => Severity::Medium,
+            Rating::High => Severity::High,
+            Rating::Critical => Severity::Critical,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Type)]
+pub(crate) struct CvssScore {
+    pub version: CvssVersion,
+    pub vector: String,
+    pub base_score: f64,
+    // CVSS 3.1 temporal score, or the CVSS 4.0 base + threat score
+    pub temporal_score: Option<f64>,
+    pub environmental_score: Option<f64>,
+    // The most specific score available, used for the rating
+    pub score: f64,
+    pub rating: Rating,
+}
+
+const V3_METRICS: [(&str, &[&str]); 22] = [
+    ("AV", &["N", "A", "L", "P"]),
+    ("AC", &["L", "H"]),
+    ("PR", &["N", "L", "H"]),
+    ("UI", &["N", "R"]),
+    ("S", &["U", "C"]),
+    ("C", &["H", "L", "N"]),
+    ("I", &["H", "L", "N"]),
+    ("A", &["H", "L", "N"]),
+    ("E", &["X", "H", "F", "P", "U"]),
+    ("RL", &["X", "U", "W", "T", "O"]),
+    ("RC", &["X", "C", "R", "U"]),
+    ("CR", &["X", "H", "M", "L"]),
+    ("IR", &["X", "H", "M", "L"]),
+    ("AR", &["X", "H", "M", "L"]),
+    ("MAV", &["X", "N", "A", "L", "P"]),
+    ("MAC", &["X", "L", "H"]),
+    ("MPR", &["X", "N", "L", "H"]),
+    ("MUI", &["X", "N", "R"]),
+    ("MS", &["X", "U", "C"]),
+    ("MC", &["X", "H", "L", "N"]),
+    ("MI", &["X", "H", "L", "N"]),
+    ("MA", &["X", "H", "L", "N"]),
+];
+const V3_BASE: [&str; 8] = ["AV", "AC", "PR", "UI", "S", "C", "I", "A"];
+const V3_TEMPORAL: [&str; 3] = ["E", "RL", "RC"];
+
+const V4_METRICS: [(&str, &[&str]); 32] = [
+    ("AV", &["N", "A", "L", "P"]),
+    ("AC", &["L", "H"]),
+    ("AT", &["N", "P"]),
+    ("PR", &["N", "L", "H"]),
+    ("UI", &["N", "P", "A"]),
+    ("VC", &["H", "L", "N"]),
+    ("VI", &["H", "L", "N"]),
+    ("VA", &["H", "L", "N"]),
+    ("SC", &["H", "L", "N"]),
+    ("SI", &["H", "L", "N"]),
+    ("SA", &["H", "L", "N"]),
+    ("E", &["X", "A", "P", "U"]),
+    ("CR", &["X", "H", "M", "L"]),
+    ("IR", &["X", "H", "M", "L"]),
+    ("AR", &["X", "H", "M", "L"]),
+    ("MAV", &["X", "N", "A", "L", "P"]),
+    ("MAC", &["X", "L", "H"]),
+    ("MAT", &["X", "N", "P"]),
+    ("MPR", &["X", "N", "L", "H"]),
+    ("MUI", &["X", "N", "P", "A"]),
+    ("MVC", &["X", "H", "L", "N"]),
+    ("MVI", &["X", "H", "L", "N"]),
+    ("MVA", &["X", "H", "L", "N"]),
+    ("MSC", &["X", "H", "L", "N"]),
+    ("MSI", &["X", "S", "H", "L", "N"]),
+    ("MSA", &["X", "S", "H", "L", "N"]),
+    ("S", &["X", "N", "P"]),
+    ("AU", &["X", "N", "Y"]),
+    ("R", &["X", "A", "U", "I"]),
+    ("V", &["X", "D", "C"]),
+    ("RE", &["X", "L", "M", "H"]),
+    ("U", &["X", "Clear", "Green", "Amber", "Red"]),
+];
+const V4_BASE: [&str; 11] = ["AV", "AC", "AT", "PR", "UI", "VC", "VI", "VA", "SC", "SI", "SA"];
+const V4_ENVIRONMENTAL: [&str; 14] = [
+    "CR", "IR", "AR", "MAV", "MAC", "MAT", "MPR", "MUI", "MVC", "MVI", "MVA", "MSC", "MSI", "MSA",
+];
+
+pub(crate) fn score(vector: &str) -> Result<CvssScore, String> {
+    let vector = vector.trim();
+    if let Some(rest) = vector.strip_prefix("CVSS:3.1/") {
+        score_v3(vector, rest)
+    } else if let Some(rest) = vector.strip_prefix("CVSS:4.0/") {
+        score_v4(vector, rest)
+    } else {
+        Err("Unsupported CVSS vector: expected a 'CVSS:3.1/' or 'CVSS:4.0/' prefix".to_string())
+    }
+}
+
+// Splits "AV:N/AC:L/..." into a metric map, checking names, values and duplicates
+fn parse_metrics(
+    rest: &str,
+    allowed: &[(&str, &[&str])],
+    mandatory: &[&str],
+) -> Result<HashMap<String, String>, String> {
+    let mut metrics = HashMap::new();
+    for part in rest.split('/') {
+        let (name, value) = part.split_once(':')
+            .ok_or_else(|| format!("Malformed CVSS metric '{}'", part))?;
+        let values = allowed.iter().find(|(metric, _)| *metric == name)
+            .map(|(_, values)| *values)
+            .ok_or_else(|| format!("Unknown CVSS metric '{}'", name))?;
+        if !values.contains(&value) {
+            return Err(format!("Invalid value '{}' for CVSS metric '{}'", value, name));
+        }
+        if metrics.insert(name.to_string(), value.to_string()).is_some() {
+            return Err(format!("CVSS metric '{}' is defined more than once", name));
+        }
+    }
+
+    for name in mandatory {
+        if !metrics.contains_key(*name) {
+            return Err(format!("Missing mandatory CVSS metric '{}'", name));
+        }
+    }
+    Ok(metrics)
+}
+
+fn is_defined(metrics: &HashMap<String, String>, name: &str) -> bool {
+    metrics.get(name).is_some_and(|value| value != "X")
+}
+
+// CVSS 3.1 Roundup as defined in Appendix A of the specification
+fn roundup(value: f64) -> f64 {
+    let int_input = (value * 100_000.0).round() as i64;
+    if int_input % 10_000 == 0 {
+        int_input as f64 / 100_000.0
+    } else {
+        ((int_input / 10_000) + 1) as f64 / 10.0
+    }
+}
+
+fn score_v3(vector: &str, rest: &str) -> Result<CvssScore, String> {
+    let metrics = parse_metrics(rest, &V3_METRICS, &V3_BASE)?;
+    let get = |name: &str| metrics.get(name).map(String::as_str).unwrap_or("X");
+    // Modified metrics fall back to their base counterpart when not defined
+    let modified = |name: &str| -> &str {
+        match metrics.get(&format!("M{}", name)).map(String::as_str) {
+            Some(value) if value != "X" => value,
+            _ => get(name),
+        }
+    };
+
+    let attack_vector = |value: &str| match value {
+        "N" => 0.85,
+        "A" => 0.62,
+        "L" => 0.55,
+        _ => 0.2,
+    };
+    let attack_complexity = |value: &str| if value == "L" { 0.77 } else { 0.44 };
+    let privileges = |value: &str, changed: bool| match (value, changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        (_, false) => 0.27,
+        (_, true) => 0.5,
+    };
+    let user_interaction = |value: &str| if value == "N" { 0.85 } else { 0.62 };
+    let cia = |value: &str| match value {
+        "H" => 0.56,
+        "L" => 0.22,
+        _ => 0.0,
+    };
+    let requirement = |value: &str| match value {
+        "H" => 1.5,
+        "L" => 0.5,
+        _ => 1.0,
+    };
+    let exploit_maturity = match get("E") {
+        "F" => 0.97,
+        "P" => 0.94,
+        "U" => 0.91,
+        _ => 1.0,
+    };
+    let remediation_level = match get("RL") {
+        "W" => 0.97,
+        "T" => 0.96,
+        "O" => 0.95,
+        _ => 1.0,
+    };
+    let report_confidence = match get("RC") {
+        "R" => 0.96,
+        "U" => 0.92,
+        _ => 1.0,
+    };
+    let temporal_factor = exploit_maturity * remediation_level * report_confidence;
+
+    // Base score
+    let changed = get("S") == "C";
+    let iss: f64 = 1.0 - (1.0 - cia(get("C"))) * (1.0 - cia(get("I"))) * (1.0 - cia(get("A")));
+    let impact: f64 = if changed {
+        7.52 * (iss - 0.029) - 3.25 * (iss - 0.02).powi(15)
+    } else {
+        6.42 * iss
+    };
+    let exploitability: f64 = 8.22
+        * attack_vector(get("AV"))
+        * attack_complexity(get("AC"))
+        * privileges(get("PR"), changed)
+        * user_interaction(get("UI"));
+    let base_score = if impact <= 0.0 {
+        0.0
+    } else if changed {
+        roundup((1.08 * (impact + exploitability)).min(10.0))
+    } else {
+        roundup((impact + exploitability).min(10.0))
+    };
+
+    let temporal_score = V3_TEMPORAL.iter().any(|name| is_defined(&metrics, name))
+        .then(|| roundup(base_score * temporal_factor));
+
+    // Environmental score
+    let environmental_defined = V3_METRICS.iter()
+        .skip(V3_BASE.len() + V3_TEMPORAL.len())
+        .any(|(name, _)| is_defined(&metrics, name));
+    let environmental_score = environmental_defined.then(|| {
+        let changed = modified("S") == "C";
+        let miss: f64 = (1.0
+            - (1.0 - requirement(get("CR")) * cia(modified("C")))
+                * (1.0 - requirement(get("IR")) * cia(modified("I")))
+                * (1.0 - requirement(get("AR")) * cia(modified("A"))))
+            .min(0.915);
+        let impact: f64 = if changed {
+            7.52 * (miss - 0.029) - 3.25 * (miss * 0.9731 - 0.02).powi(13)
+        } else {
+            6.42 * miss
+        };
+        let exploitability: f64 = 8.22
+            * attack_vector(modified("AV"))
+            * attack_complexity(modified("AC"))
+            * privileges(modified("PR"), changed)
+            * user_interaction(modified("UI"));
+        if impact <= 0.0 {
+            0.0
+        } else if changed {
+            roundup(roundup((1.08 * (impact + exploitability)).min(10.0)) * temporal_factor)
+        } else {
+            roundup(roundup((impact + exploitability).min(10.0)) * temporal_factor)
+        }
+    });
+
+    let score = environmental_score.or(temporal_score).unwrap_or(base_score);
+    Ok(CvssScore {
+        version: CvssVersion::V3_1,
+        vector: vector.to_string(),
+        base_score,
+        temporal_score,
+        environmental_score,
+        score,
+        rating: Rating::from_score(score),
+    })
+}
+
+fn score_v4(vector: &str, rest: &str) -> Result<CvssScore, String> {
+    let metrics = parse_metrics(rest, &V4_METRICS, &V4_BASE)?;
+
+    let base_only: HashMap<String, String> = metrics.iter()
+        .filter(|(name, _)| V4_BASE.contains(&name.as_str()))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    let base_score = v4_score(&base_only);
+
+    let threat_defined = is_defined(&metrics, "E");
+    let environmental_defined = V4_ENVIRONMENTAL.iter().any(|name| is_defined(&metrics, name));
+
+    let temporal_score = threat_defined.then(|| {
+        let mut with_threat = base_only.clone();
+        with_threat.insert("E".to_string(), metrics["E"].clone());
+        v4_score(&with_threat)
+    });
+    let environmental_score = environmental_defined.then(|| v4_score(&metrics));
+
+    let score = environmental_score.or(temporal_score).unwrap_or(base_score);
+    Ok(CvssScore {
+        version: CvssVersion::V4_0,
+        vector: vector.to_string(),
+        base_score,
+        temporal_score,
+        environmental_score,
+        score,
+        rating: Rating::from_score(score),
+    })
+}
+
+// Effective value of a CVSS 4.0 metric, taking modified metrics and defaults into account
+fn v4_effective<'a>(metrics: &'a HashMap<String, String>, name: &str) -> &'a str {
+    if let Some(value) = metrics.get(&format!("M{}", name)) {
+        if value != "X" {
+            return value;
+        }
+    }
+    match (name, metrics.get(name).map(String::as_str)) {
+        ("E", None | Some("X")) => "A",
+        ("CR" | "IR" | "AR", None | Some("X")) => "H",
+        (_, Some(value)) => value,
+        (_, None) => "X",
+    }
+}
+
+fn v4_level(name: &str, value: &str) -> f64 {
+    match (name, value) {
+        ("AV", "N") | ("PR", "N") | ("UI", "N") | ("AC", "L") | ("AT", "N") => 0.0,
+        ("AV", "A") | ("PR", "L") | ("UI", "P") | ("AC", "H") | ("AT", "P") => 0.1,
+        ("AV", "L") | ("PR", "H") | ("UI", "A") => 0.2,
+        ("AV", "P") => 0.3,
+        ("VC" | "VI" | "VA", "H") => 0.0,
+        ("VC" | "VI" | "VA", "L") => 0.1,
+        ("VC" | "VI" | "VA", "N") => 0.2,
+        ("SI" | "SA", "S") => 0.0,
+        ("SC" | "SI" | "SA", "H") => 0.1,
+        ("SC" | "SI" | "SA", "L") => 0.2,
+        ("SC" | "SI" | "SA", "N") => 0.3,
+        ("CR" | "IR" | "AR", "H") => 0.0,
+        ("CR" | "IR" | "AR", "M") => 0.1,
+        ("CR" | "IR" | "AR", "L") => 0.2,
+        _ => 0.0,
+    }
+}
+
+// Highest severity vectors of each equivalence class, from the CVSS 4.0 reference implementation
+fn v4_max_composed(eq: &str, level: u8, eq6: u8) -> &'static [&'static str] {
+    match (eq, level, eq6) {
+        ("eq1", 0, _) => &["AV:N/PR:N/UI:N/"],
+        ("eq1", 1, _) => &["AV:A/PR:N/UI:N/", "AV:N/PR:L/UI:N/", "AV:N/PR:N/UI:P/"],
+        ("eq1", 2, _) => &["AV:P/PR:N/UI:N/", "AV:A/PR:L/UI:P/"],
+        ("eq2", 0, _) => &["AC:L/AT:N/"],
+        ("eq2", 1, _) => &["AC:H/AT:N/", "AC:L/AT:P/"],
+        ("eq3", 0, 0) => &["VC:H/VI:H/VA:H/CR:H/IR:H/AR:H/"],
+        ("eq3", 0, 1) => &["VC:H/VI:H/VA:L/CR:M/IR:M/AR:H/", "VC:H/VI:H/VA:H/CR:M/IR:M/AR:M/"],
+        ("eq3", 1, 0) => &["VC:L/VI:H/VA:H/CR:H/IR:H/AR:H/", "VC:H/VI:L/VA:H/CR:H/IR:H/AR:H/"],
+        ("eq3", 1, 1) => &[
+            "VC:L/VI:H/VA:L/CR:H/IR:M/AR:H/",
+            "VC:L/VI:H/VA:H/CR:H/IR:M/AR:M/",
+            "VC:H/VI:L/VA:H/CR:M/IR:H/AR:M/",
+            "VC:H/VI:L/VA:L/CR:M/IR:H/AR:H/",
+            "VC:L/VI:L/VA:H/CR:H/IR:H/AR:M/",
+        ],
+        ("eq3", 2, _) => &["VC:L/VI:L/VA:L/CR:H/IR:H/AR:H/"],
+        ("eq4", 0, _) => &["SC:H/SI:S/SA:S/"],
+        ("eq4", 1, _) => &["SC:H/SI:H/SA:H/"],
+        ("eq4", 2, _) => &["SC:L/SI:L/SA:L/"],
+        ("eq5", 0, _) => &["E:A/"],
+        ("eq5", 1, _) => &["E:P/"],
+        ("eq5", 2, _) => &["E:U/"],
+        _ => &[],
+    }
+}
+
+// Number of severity steps inside each equivalence class
+fn v4_max_severity(eq: &str, level: u8, eq6: u8) -> f64 {
+    match (eq, level, eq6) {
+        ("eq1", 0, _) => 1.0,
+        ("eq1", 1, _) => 4.0,
+        ("eq1", 2, _) => 5.0,
+        ("eq2", 0, _) => 1.0,
+        ("eq2", 1, _) => 2.0,
+        ("eq3eq6", 0, 0) => 7.0,
+        ("eq3eq6", 0, 1) => 6.0,
+        ("eq3eq6", 1, _) => 8.0,
+        ("eq3eq6", 2, _) => 10.0,
+        ("eq4", 0, _) => 6.0,
+        ("eq4", 1, _) => 5.0,
+        ("eq4", 2, _) => 4.0,
+        _ => 1.0,
+    }
+}
+
+fn v4_score(metrics: &HashMap<String, String>) -> f64 {
+    let m = |name: &str| v4_effective(metrics, name);
+
+    if ["VC", "VI", "VA", "SC", "SI", "SA"].iter().all(|name| m(name) == "N") {
+        return 0.0;
+    }
+
+    let eq1: u8 = if m("AV") == "N" && m("PR") == "N" && m("UI") == "N" {
+        0
+    } else if (m("AV") == "N" || m("PR") == "N" || m("UI") == "N") && m("AV") != "P" {
+        1
+    } else {
+        2
+    };
+    let eq2: u8 = if m("AC") == "L" && m("AT") == "N" { 0 } else { 1 };
+    let eq3: u8 = if m("VC") == "H" && m("VI") == "H" {
+        0
+    } else if m("VC") == "H" || m("VI") == "H" || m("VA") == "H" {
+        1
+    } else {
+        2
+    };
+    let eq4: u8 = if m("SI") == "S" || m("SA") == "S" {
+        0
+    } else if m("SC") == "H" || m("SI") == "H" || m("SA") == "H" {
+        1
+    } else {
+        2
+    };
+    let eq5: u8 = match m("E") {
+        "A" => 0,
+        "P" => 1,
+        _ => 2,
+    };
+    let eq6: u8 = if (m("CR") == "H" && m("VC") == "H")
+        || (m("IR") == "H" && m("VI") == "H")
+        || (m("AR") == "H" && m("VA") == "H")
+    {
+        0
+    } else {
+        1
+    };
+
+    let lookup = |eqs: [u8; 6]| -> Option<f64> {
+        let key: String = eqs.iter().map(|eq| eq.to_string()).collect();
+        v4_lookup(&key)
+    };
+    let value = match lookup([eq1, eq2, eq3, eq4, eq5, eq6]) {
+        Some(value) => value,
+        None => return 0.0,
+    };
+
+    // Scores of the next lower macro vector for each equivalence class
+    let lower_eq1 = lookup([eq1 + 1, eq2, eq3, eq4, eq5, eq6]);
+    let lower_eq2 = lookup([eq1, eq2 + 1, eq3, eq4, eq5, eq6]);
+    let lower_eq3eq6 = match (eq3, eq6) {
+        (0, 0) => {
+            let left = lookup([eq1, eq2, eq3, eq4, eq5, eq6 + 1]);
+            let right = lookup([eq1, eq2, eq3 + 1, eq4, eq5, eq6]);
+            match (left, right) {
+                (Some(l), Some(r)) => Some(l.max(r)),
+                (l, r) => l.or(r),
+            }
+        }
+        (1, 0) => lookup([eq1, eq2, eq3, eq4, eq5, eq6 + 1]),
+        _ => lookup([eq1, eq2, eq3 + 1, eq4, eq5, eq6]),
+    };
+    let lower_eq4 = lookup([eq1, eq2, eq3, eq4 + 1, eq5, eq6]);
+    let lower_eq5 = lookup([eq1, eq2, eq3, eq4, eq5 + 1, eq6]);
+
+    // Find the first highest severity vector the current vector is "below" on every metric
+    let distance = |name: &str, max: &HashMap<&str, &str>| -> f64 {
+        let max_value = max.get(name).copied().unwrap_or("X");
+        v4_level(name, m(name)) - v4_level(name, max_value)
+    };
+    let mut distances = None;
+    'search: for a in v4_max_composed("eq1", eq1, eq6) {
+        for b in v4_max_composed("eq2", eq2, eq6) {
+            for c in v4_max_composed("eq3", eq3, eq6) {
+                for d in v4_max_composed("eq4", eq4, eq6) {
+                    for e in v4_max_composed("eq5", eq5, eq6) {
+                        let combined = format!("{}{}{}{}{}", a, b, c, d, e);
+                        let max: HashMap<&str, &str> = combined.split('/')
+                            .filter_map(|part| part.split_once(':'))
+                            .collect();
+                        let names = [
+                            "AV", "PR", "UI", "AC", "AT", "VC", "VI", "VA", "SC", "SI", "SA", "CR", "IR", "AR",
+                        ];
+                        let values: HashMap<&str, f64> = names.iter()
+                            .map(|name| (*name, distance(name, &max)))
+                            .collect();
+                        if values.values().all(|d| *d >= 0.0) {
+                            distances = Some(values);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let distances = match distances {
+        Some(distances) => distances,
+        None => return round_v4(value),
+    };
+
+    let step = 0.1;
+    let current_eq1 = distances["AV"] + distances["PR"] + distances["UI"];
+    let current_eq2 = distances["AC"] + distances["AT"];
+    let current_eq3eq6 = distances["VC"] + distances["VI"] + distances["VA"]
+        + distances["CR"] + distances["IR"] + distances["AR"];
+    let current_eq4 = distances["SC"] + distances["SI"] + distances["SA"];
+
+    let normalized = |lower: Option<f64>, current: f64, max_severity: f64| -> Option<f64> {
+        lower.map(|lower| (value - lower) * (current / (max_severity * step)))
+    };
+    let parts = [
+        normalized(lower_eq1, current_eq1, v4_max_severity("eq1", eq1, eq6)),
+        normalized(lower_eq2, current_eq2, v4_max_severity("eq2", eq2, eq6)),
+        normalized(lower_eq3eq6, current_eq3eq6, v4_max_severity("eq3eq6", eq3, eq6)),
+        normalized(lower_eq4, current_eq4, v4_max_severity("eq4", eq4, eq6)),
+        // EQ5 has no severity distance inside the class
+        lower_eq5.map(|_| 0.0),
+    ];
+
+    let existing: Vec<f64> = parts.iter().flatten().copied().collect();
+    let mean_distance = if existing.is_empty() {
+        0.0
+    } else {
+        existing.iter().sum::<f64>() / existing.len() as f64
+    };
+    round_v4((value - mean_distance).clamp(0.0, 10.0))
+}
+
+fn round_v4(value: f64) -> f64 {
+    // Guard against floating point noise before rounding to one decimal
+    ((value * 10.0 + 1e-9).round()) / 10.0
+}
+
+// Macro vector scores (EQ1..EQ6) from the CVSS 4.0 reference lookup table
+fn v4_lookup(key: &str) -> Option<f64> {
+    let value = match key {
+        "000000" => 10.0, "000001" => 9.9, "000010" => 9.8, "000011" => 9.5, "000020" => 9.5, "000021" => 9.2,
+        "000100" => 10.0, "000101" => 9.6, "000110" => 9.3, "000111" => 8.7, "000120" => 9.1, "000121" => 8.1,
+        "000200" => 9.3, "000201" => 9.0, "000210" => 8.9, "000211" => 8.0, "000220" => 8.1, "000221" => 6.8,
+        "001000" => 9.8, "001001" => 9.5, "001010" => 9.5, "001011" => 9.2, "001020" => 9.0, "001021" => 8.4,
+        "001100" => 9.3, "001101" => 9.2, "001110" => 8.9, "001111" => 8.1, "001120" => 8.1, "001121" => 6.5,
+        "001200" => 8.8, "001201" => 8.0, "001210" => 7.8, "001211" => 7.0, "001220" => 6.9, "001221" => 4.8,
+        "002001" => 9.2, "002011" => 8.2, "002021" => 7.2, "002101" => 7.9, "002111" => 6.9, "002121" => 5.0,
+        "002201" => 6.9, "002211" => 5.5, "002221" => 2.7,
+        "010000" => 9.9, "010001" => 9.7, "010010" => 9.5, "010011" => 9.2, "010020" => 9.2, "010021" => 8.5,
+        "010100" => 9.5, "010101" => 9.1, "010110" => 9.0, "010111" => 8.3, "010120" => 8.4, "010121" => 7.1,
+        "010200" => 9.2, "010201" => 8.1, "010210" => 8.2, "010211" => 7.1, "010220" => 7.2, "010221" => 5.3,
+        "011000" => 9.5, "011001" => 9.3, "011010" => 9.2, "011011" => 8.5, "011020" => 8.5, "011021" => 7.3,
+        "011100" => 9.2, "011101" => 8.2, "011110" => 8.0, "011111" => 7.2, "011120" => 7.0, "011121" => 5.9,
+        "011200" => 8.4, "011201" => 7.0, "011210" => 7.1, "011211" => 5.2, "011220" => 5.0, "011221" => 3.0,
+        "012001" => 8.6, "012011" => 7.5, "012021" => 5.2, "012101" => 7.1, "012111" => 5.2, "012121" => 2.9,
+        "012201" => 6.3, "012211" => 2.9, "012221" => 1.7,
+        "100000" => 9.8, "100001" => 9.5, "100010" => 9.4, "100011" => 8.7, "100020" => 9.1, "100021" => 8.1,
+        "100100" => 9.4, "100101" => 8.9, "100110" => 8.6, "100111" => 7.4, "100120" => 7.7, "100121" => 6.4,
+        "100200" => 8.7, "100201" => 7.5, "100210" => 7.4, "100211" => 6.3, "100220" => 6.3, "100221" => 4.9,
+        "101000" => 9.4, "101001" => 8.9, "101010" => 8.8, "101011" => 7.7, "101020" => 7.6, "101021" => 6.7,
+        "101100" => 8.6, "101101" => 7.6, "101110" => 7.4, "101111" => 5.8, "101120" => 5.9, "101121" => 5.0,
+        "101200" => 7.2, "101201" => 5.7, "101210" => 5.7, "101211" => 5.2, "101220" => 5.2, "101221" => 2.5,
+        "102001" => 8.3, "102011" => 7.0, "102021" => 5.4, "102101" => 6.5, "102111" => 5.8, "102121" => 2.6,
+        "102201" => 5.3, "102211" => 2.1, "102221" => 1.3,
+        "110000" => 9.5, "110001" => 9.0, "110010" => 8.8, "110011" => 7.6, "110020" => 7.6, "110021" => 7.0,
+        "110100" => 9.0, "110101" => 7.7, "110110" => 7.5, "110111" => 6.2, "110120" => 6.1, "110121" => 5.3,
+        "110200" => 7.7, "110201" => 6.6, "110210" => 6.8, "110211" => 5.9, "110220" => 5.2, "110221" => 3.0,
+        "111000" => 8.9, "111001" => 7.8, "111010" => 7.6, "111011" => 6.7, "111020" => 6.2, "111021" => 5.8,
+        "111100" => 7.4, "111101" => 5.9, "111110" => 5.7, "111111" => 5.7, "111120" => 4.7, "111121" => 2.3,
+        "111200" => 6.1, "111201" => 5.2, "111210" => 5.7, "111211" => 2.9, "111220" => 2.4, "111221" => 1.6,
+        "112001" => 7.1, "112011" => 5.9, "112021" => 3.0, "112101" => 5.8, "112111" => 2.6, "112121" => 1.5,
+        "112201" => 2.3, "112211" => 1.3, "112221" => 0.6,
+        "200000" => 9.3, "200001" => 8.7, "200010" => 8.6, "200011" => 7.2, "200020" => 7.5, "200021" => 5.8,
+        "200100" => 8.6, "200101" => 7.4, "200110" => 7.4, "200111" => 6.1, "200120" => 5.6, "200121" => 3.4,
+        "200200" => 7.0, "200201" => 5.4, "200210" => 5.2, "200211" => 4.0, "200220" => 4.0, "200221" => 2.2,
+        "201000" => 8.5, "201001" => 7.5, "201010" => 7.4, "201011" => 5.5, "201020" => 6.2, "201021" => 5.1,
+        "201100" => 7.2, "201101" => 5.7, "201110" => 5.5, "201111" => 4.1, "201120" => 4.6, "201121" => 1.9,
+        "201200" => 5.3, "201201" => 3.6, "201210" => 3.4, "201211" => 1.9, "201220" => 1.9, "201221" => 0.8,
+        "202001" => 6.4, "202011" => 5.1, "202021" => 2.0, "202101" => 4.7, "202111" => 2.1, "202121" => 1.1,
+        "202201" => 2.4, "202211" => 0.9, "202221" => 0.4,
+        "210000" => 8.8, "210001" => 7.5, "210010" => 7.3, "210011" => 5.3, "210020" => 6.0, "210021" => 5.0,
+        "210100" => 7.3, "210101" => 5.5, "210110" => 5.9, "210111" => 4.0, "210120" => 4.1, "210121" => 2.0,
+        "210200" => 5.4, "210201" => 4.3, "210210" => 4.5, "210211" => 2.2, "210220" => 2.0, "210221" => 1.1,
+        "211000" => 7.5, "211001" => 5.5, "211010" => 5.8, "211011" => 4.5, "211020" => 4.0, "211021" => 2.1,
+        "211100" => 6.1, "211101" => 5.1, "211110" => 4.8, "211111" => 1.8, "211120" => 2.0, "211121" => 0.9,
+        "211200" => 4.6, "211201" => 1.8, "211210" => 1.7, "211211" => 0.7, "211220" => 0.8, "211221" => 0.2,
+        "212001" => 5.3, "212011" => 2.4, "212021" => 1.4, "212101" => 2.4, "212111" => 1.2, "212121" => 0.5,
+        "212201" => 1.0, "212211" => 0.3, "212221" => 0.1,
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scores(vector: &str) -> CvssScore {
+        score(vector).unwrap_or_else(|e| panic!("{}: {}", vector, e))
+    }
+
+    #[test]
+    fn v3_base_scores() {
+        let cases = [
+            ("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H", 9.8, Rating::Critical),
+            ("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:C/C:H/I:H/A:H", 10.0, Rating::Critical),
+            ("CVSS:3.1/AV:N/AC:L/PR:N/UI:R/S:C/C:L/I:L/A:N", 6.1, Rating::Medium),
+            ("CVSS:3.1/AV:L/AC:L/PR:L/UI:N/S:U/C:H/I:H/A:H", 7.8, Rating::High),
+            ("CVSS:3.1/AV:N/AC:H/PR:N/UI:N/S:U/C:H/I:N/A:N", 5.9, Rating::Medium),
+            ("CVSS:3.1/AV:N/AC:L/PR:L/UI:N/S:C/C:H/I:H/A:H", 9.9, Rating::Critical),
+            ("CVSS:3.1/AV:P/AC:H/PR:H/UI:R/S:U/C:L/I:N/A:N", 1.6, Rating::Low),
+            ("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:N", 0.0, Rating::None),
+        ];
+        for (vector, expected, rating) in cases {
+            let result = scores(vector);
+            assert_eq!(result.base_score, expected, "{}", vector);
+            assert_eq!(result.rating, rating, "{}", vector);
+            assert!(result.temporal_score.is_none());
+            assert!(result.environmental_score.is_none());
+        }
+    }
+
+    #[test]
+    fn v3_temporal_and_environmental_scores() {
+        let result = scores("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H/E:P/RL:O/RC:C");
+        assert_eq!(result.base_score, 9.8);
+        assert_eq!(result.temporal_score, Some(8.8));
+        assert_eq!(result.score, 8.8);
+
+        let result = scores("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H/E:U/RL:O/RC:R/CR:L/IR:L/AR:L/MAV:L");
+        assert_eq!(result.temporal_score, Some(8.2));
+        assert_eq!(result.environmental_score, Some(5.5));
+        assert_eq!(result.rating, Rating::Medium);
+    }
+
+    #[test]
+    fn v4_scores() {
+        // Scores from the FIRST CVSS v4.0 calculator
+        let cases = [
+            ("CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:H/VI:H/VA:H/SC:H/SI:H/SA:H", 10.0),
+            ("CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:H/VI:H/VA:H/SC:N/SI:N/SA:N", 9.3),
+            ("CVSS:4.0/AV:L/AC:L/AT:N/PR:N/UI:P/VC:H/VI:H/VA:H/SC:N/SI:N/SA:N", 8.5),
+            ("CVSS:4.0/AV:N/AC:L/AT:N/PR:L/UI:N/VC:H/VI:H/VA:H/SC:N/SI:N/SA:N", 8.7),
+            ("CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:N/VI:N/VA:H/SC:N/SI:N/SA:N", 8.7),
+            ("CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:L/VI:N/VA:N/SC:N/SI:N/SA:N", 6.9),
+            ("CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:P/VC:N/VI:N/VA:N/SC:L/SI:L/SA:N", 5.3),
+            ("CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:A/VC:N/VI:N/VA:N/SC:L/SI:L/SA:N", 5.1),
+            ("CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:N/VI:N/VA:N/SC:N/SI:N/SA:N", 0.0),
+        ];
+        for (vector, expected) in cases {
+            assert_eq!(scores(vector).base_score, expected, "{}", vector);
+        }
+
+        let result = scores("CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:H/VI:H/VA:H/SC:N/SI:N/SA:N/E:U");
+        assert_eq!(result.base_score, 9.3);
+        assert_eq!(result.temporal_score, Some(8.1));
+        assert_eq!(result.score, 8.1);
+        assert!(result.environmental_score.is_none());
+        let result = scores("CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:H/VI:H/VA:H/SC:N/SI:N/SA:N/E:P");
+        assert_eq!(result.temporal_score, Some(8.9));
+
+        let result = scores("CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:H/VI:H/VA:H/SC:N/SI:N/SA:N/CR:L/IR:L/AR:L");
+        assert_eq!(result.environmental_score, Some(8.9));
+    }
+
+    #[test]
+    fn rejects_invalid_vectors() {
+        let invalid = [
+            "AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H",
+            "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H",
+            "CVSS:3.1/AV:X/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H",
+            "CVSS:3.1/AV:N/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H",
+            "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H/FOO:1",
+            "CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:H/VI:H/VA:H",
+            "CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:R/VC:H/VI:H/VA:H/SC:N/SI:N/SA:N",
+        ];
+        for vector in invalid {
+            assert!(score(vector).is_err(), "{}", vector);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use serde::{Serialize, Serializer};
+use specta::datatype::reference::Reference;
+use specta::datatype::DataType;
+use specta::{Generics, Type, TypeCollection};
+use std::fmt;
+use std::sync::PoisonError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Type)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Entity {
+    Workspace,
+    Machine,
+    Port,
+    Note,
+    Revision,
+    Finding,
+    Tag,
+    Credential,
+    Conversation,
+}
+
+impl Entity {
+    fn name(self) -> &'static str {
+        match self {
+            Entity::Workspace => "workspace",
+            Entity::Machine => "machine",
+            Entity::Port => "port",
+            Entity::Note => "note",
+            Entity::Revision => "revision",
+            Entity::Finding => "finding",
+            Entity::Tag => "tag",
+            Entity::Credential => "credential",
+            Entity::Conversation => "conversation",
+        }
+    }
+}
+
+// Error returned by every command. The frontend receives it as
+// { code, message } plus { entity, id } for not_found, and can switch on `code`.
+#[derive(Debug)]
+pub(crate) enum AppError {
+    NotFound { entity: Entity, id: String },
+    Validation(String),
+    // The scan/AI sidecar can't be reached or answered with something unusable
+    BackendUnavailable(String),
+    // Reading or writing files in the data directory failed
+    Storage(String),
+    Timeout(String),
+    // Lock poisoning, serialization failures and other bugs
+    Internal(String),
+}
+
+pub(crate) type AppResult<T> = Result<T, AppError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ErrorCode {
+    NotFound,
+    Validation,
+    BackendUnavailable,
+    Storage,
+    Timeout,
+    Internal,
+}
+
+// What an AppError looks like on the wire, also the type exported to the bindings
+#[derive(Serialize, Type)]
+#[serde(rename = "AppError")]
+struct ErrorBody {
+    code: ErrorCode,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entity: Option<Entity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+}
+
+impl AppError {
+    pub(crate) fn not_found(entity: Entity, id: impl fmt::Display) -> Self {
+        AppError::NotFound { entity, id: id.to_string() }
+    }
+
+    pub(crate) fn validation(message: impl Into<String>) -> Self {
+        AppError::Validation(message.into())
+    }
+
+    pub(crate) fn storage(context: &str, error: impl fmt::Display) -> Self {
+        AppError::Storage(format!("{}: {}", context, error))
+    }
+
+    pub(crate) fn backend(context: &str, error: impl fmt::Display) -> Self {
+        AppError::BackendUnavailable(format!("{}: {}", context, error))
+    }
+
+    pub(crate) fn code(&self) -> ErrorCode {
+        match self {
+            AppError::NotFound { .. } => ErrorCode::NotFound,
+            AppError::Validation(_) => ErrorCode::Validation,
+            AppError::BackendUnavailable(_) => ErrorCode::BackendUnavailable,
+            AppError::Storage(_) => ErrorCode::Storage,
+            AppError::Timeout(_) => ErrorCode::Timeout,
+            AppError::Internal(_) => ErrorCode::Internal,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::NotFound { entity, id } => {
+                let name = entity.name();
+                write!(f, "{}{} '{}' not found", name[..1].to_uppercase(), &name[1..], id)
+            }
+            AppError::Validation(message)
+            | AppError::BackendUnavailable(message)
+            | AppError::Storage(message)
+            | AppError::Timeout(message)
+            | AppError::Internal(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (entity, id) = match self {
+            AppError::NotFound { entity, id } => (Some(*entity), Some(id.clone())),
+            _ => (None, None),
+        };
+        ErrorBody { code: self.code(), message: self.to_string(), entity, id }.serialize(serializer)
+    }
+}
+
+impl Type for AppError {
+    fn inline(type_map: &mut TypeCollection, generics: Generics) -> DataType {
+        ErrorBody::inline(type_map, generics)
+    }
+
+    fn reference(type_map: &mut TypeCollection, generics: &[DataType]) -> Reference {
+        ErrorBody::reference(type_map, generics)
+    }
+}
+
+impl<T> From<PoisonError<T>> for AppError {
+    fn from(error: PoisonError<T>) -> Self {
+        AppError::Internal(format!("Failed to lock shared state: {}", error))
+    }
+}
+
+// Only used for serializing command results; parse errors of external data are mapped explicitly
+impl From<serde_json::Error> for AppError {
+    fn from(error: serde_json::Error) -> Self {
+        AppError::Internal(format!("Failed to serialize response: {}", error))
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            AppError::Timeout(format!("Backend request timed out: {}", error))
+        } else {
+            AppError::backend("Failed to call API", error)
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use serde::{Serialize, Deserialize};
+use specta::Type;
+
+use crate::cvss;
+use crate::error::{AppError, AppResult, Entity};
+use crate::Workspace;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Type)]
+pub(crate) enum Severity {
+    Critical,
+    High,
+    Medium,
+    Low,
+    Info,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub(crate) enum FindingStatus {
+    Open,
+    Confirmed,
+    Remediated,
+    Accepted,
+    FalsePositive,
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub(crate) struct AffectedAsset {
+    pub machine_id: u32,
+    pub port_number: Option<u16>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct Finding {
+    pub id: u32,
+    pub title: String,
+    pub severity: Severity,
+    pub cvss_vector: Option<String>,
+    pub cvss_score: Option<f32>,
+    pub affected: Vec<AffectedAsset>,
+    pub description: String,
+    pub evidence: String,
+    pub remediation: String,
+    pub status: FindingStatus,
+}
+
+// What the frontend sends when creating or updating a finding
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct FindingInput {
+    pub title: String,
+    // Only used when no CVSS vector is given, otherwise computed from the vector
+    pub severity: Option<Severity>,
+    pub cvss_vector: Option<String>,
+    pub cvss_score: Option<f32>,
+    #[serde(default)]
+    pub affected: Vec<AffectedAsset>,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub evidence: String,
+    #[serde(default)]
+    pub remediation: String,
+    pub status: Option<FindingStatus>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Type)]
+pub(crate) struct SeverityCounts {
+    pub critical: usize,
+    pub high: usize,
+    pub medium: usize,
+    pub low: usize,
+    pub info: usize,
+}
+
+impl SeverityCounts {
+    fn add(&mut self, severity: Severity) {
+        match severity {
+            Severity::Critical => self.critical += 1,
+            Severity::High => self.high += 1,
+            Severity::Medium => self.medium += 1,
+            Severity::Low => self.low += 1,
+            Severity::Info => self.info += 1,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct FindingSummary {
+    pub workspace_id: u32,
+    pub total: usize,
+    pub by_severity: SeverityCounts,
+    // Findings that still need attention (open or confirmed)
+    pub unresolved: SeverityCounts,
+    pub remediated: usize,
+    pub accepted: usize,
+    pub false_positive: usize,
+}
+
+pub(crate) fn validate(workspace: &Workspace, input: &FindingInput) -> AppResult<()> {
+    if input.title.trim().is_empty() {
+        return Err(AppError::validation("Finding title cannot be empty"));
+    }
+
+    match &input.cvss_vector {
+        Some(vector) if !vector.trim().is_empty() => {
+            cvss::score(vector).map_err(AppError::Validation)?;
+        }
+        _ => {
+            if input.severity.is_none() {
+                return Err(AppError::validation("Finding needs either a severity or a CVSS vector"));
+            }
+            if let Some(score) = input.cvss_score {
+                if !(0.0..=10.0).contains(&score) {
+                    return Err(AppError::validation(format!("CVSS score {} is out of range (0.0 - 10.0)", score)));
+                }
+            }
+        }
+    }
+
+    for asset in &input.affected {
+        let machine = workspace.data.iter().find(|machine| machine.id == asset.machine_id)
+            .ok_or_else(|| AppError::not_found(Entity::Machine, asset.machine_id))?;
+        if let Some(port_number) = asset.port_number {
+            if !machine.ports.iter().any(|port| port.number == port_number) {
+                return Err(AppError::not_found(Entity::Port, port_number));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Never hands out an ID again, so references to a deleted finding can't point at a new one
+pub(crate) fn next_id(workspace: &mut Workspace) -> u32 {
+    let highest = workspace.findings.iter().map(|finding| finding.id).max().unwrap_or(0);
+    workspace.last_finding_id = workspace.last_finding_id.max(highest) + 1;
+    workspace.last_finding_id
+}
+
+pub(crate) fn apply(finding: &mut Finding, input: FindingInput) -> AppResult<()> {
+    match input.cvss_vector.as_deref().map(str::trim).filter(|vector| !vector.is_empty()) {
+        Some(vector) => {
+            let score = cvss::score(vector).map_err(AppError::Validation)?;
+            finding.severity = score.rating.severity();
+            finding.cvss_score = Some(score.score as f32);
+            finding.cvss_vector = Some(score.vector);
+        }
+        None => {
+            if let Some(severity) = input.severity {
+                finding.severity = severity;
+            }
+            finding.cvss_vector = None;
+            finding.cvss_score = input.cvss_score;
+        }
+    }
+    finding.title = input.title;
+    finding.affected = input.affected;
+    finding.description = input.description;
+    finding.evidence = input.evidence;
+    finding.remediation = input.remediation;
+    if let Some(status) = input.status {
+        finding.status = status;
+    }
+    Ok(())
+}
+
+pub(crate) fn summarize(workspace: &Workspace) -> FindingSummary {
+    let mut summary = FindingSummary {
+        workspace_id: workspace.id,
+        total: workspace.findings.len(),
+        by_severity: SeverityCounts::default(),
+        unresolved: SeverityCounts::default(),
+        remediated: 0,
+        accepted: 0,
+        false_positive: 0,
+    };
+
+    for finding in &workspace.findings {
+        summary.by_severity.add(finding.severity);
+        match finding.status {
+            FindingStatus::Open | FindingStatus::Confirmed => summary.unresolved.add(finding.severity),
+            FindingStatus::Remediated => summary.remediated += 1,
+            FindingStatus::Accepted => summary.accepted += 1,
+            FindingStatus::FalsePositive => summary.false_positive += 1,
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::store::tests::test_port;
+    use crate::Machine;
+
+    fn workspace() -> Workspace {
+        let mut workspace = Workspace::new(1, "Lab".to_string(), "10.0.0.0/24".to_string());
+        let mut machine = Machine::new(1, "web".to_string(), "10.0.0.2".to_string());
+        machine.ports.push(test_port(80, "http", "nginx", vec![]));
+        workspace.data.push(machine);
+        workspace
+    }
+
+    pub(crate) fn input(title: &str, severity: Option<Severity>, cvss_vector: Option<&str>) -> FindingInput {
+        FindingInput {
+            title: title.to_string(),
+            severity,
+            cvss_vector: cvss_vector.map(str::to_string),
+            cvss_score: None,
+            affected: vec![],
+            description: String::new(),
+            evidence: String::new(),
+            remediation: String::new(),
+            status: None,
+        }
+    }
+
+    fn finding(severity: Severity, status: FindingStatus) -> Finding {
+        Finding {
+            id: 1,
+            title: "Outdated nginx".to_string(),
+            severity,
+            cvss_vector: None,
+            cvss_score: None,
+            affected: vec![],
+            description: String::new(),
+            evidence: String::new(),
+            remediation: String::new(),
+            status,
+        }
+    }
+
+    #[test]
+    fn inputs_are_validated() {
+        let workspace = workspace();
+        validate(&workspace, &input("Outdated nginx", Some(Severity::Low), None)).unwrap();
+        assert!(matches!(validate(&workspace, &input(" ", Some(Severity::Low), None)), Err(AppError::Validation(_))));
+        assert!(matches!(validate(&workspace, &input("Outdated nginx", None, None)), Err(AppError::Validation(_))));
+        assert!(matches!(validate(&workspace, &input("Outdated nginx", None, Some("CVSS:3.1/AV:X"))), Err(AppError::Validation(_))));
+
+        let mut out_of_range = input("Outdated nginx", Some(Severity::Low), None);
+        out_of_range.cvss_score = Some(10.5);
+        assert!(matches!(validate(&workspace, &out_of_range), Err(AppError::Validation(_))));
+
+        let mut affected = input("Outdated nginx", Some(Severity::Low), None);
+        affected.affected = vec![AffectedAsset { machine_id: 1, port_number: Some(80) }];
+        validate(&workspace, &affected).unwrap();
+        affected.affected = vec![AffectedAsset { machine_id: 1, port_number: Some(443) }];
+        assert!(matches!(validate(&workspace, &affected), Err(AppError::NotFound { .. })));
+        affected.affected = vec![AffectedAsset { machine_id: 2, port_number: None }];
+        assert!(matches!(validate(&workspace, &affected), Err(AppError::NotFound { .. })));
+    }
+
+    #[test]
+    fn vectors_decide_score_and_severity() {
+        let mut finding = finding(Severity::Info, FindingStatus::Open);
+        let mut critical = input("Unauthenticated RCE", Some(Severity::Low), Some(" CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H "));
+        critical.cvss_score = Some(1.0);
+        apply(&mut finding, critical).unwrap();
+        assert_eq!(finding.severity, Severity::Critical);
+        assert_eq!(finding.cvss_score, Some(9.8));
+        assert_eq!(finding.cvss_vector.as_deref(), Some("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"));
+        assert!(finding.status == FindingStatus::Open);
+
+        // Without a vector the given severity and score are used
+        let mut manual = input("Unauthenticated RCE", Some(Severity::Medium), None);
+        manual.cvss_score = Some(5.0);
+        manual.status = Some(FindingStatus::Confirmed);
+        apply(&mut finding, manual).unwrap();
+        assert_eq!(finding.severity, Severity::Medium);
+        assert_eq!((finding.cvss_vector, finding.cvss_score), (None, Some(5.0)));
+        assert!(finding.status == FindingStatus::Confirmed);
+    }
+
+    #[test]
+    fn summaries_count_unresolved_findings_by_severity() {
+        let mut workspace = workspace();
+        workspace.findings = vec![
+            finding(Severity::Critical, FindingStatus::Open),
+            finding(Severity::Critical, FindingStatus::Remediated),
+            finding(Severity::High, FindingStatus::Confirmed),
+            finding(Severity::Low, FindingStatus::Accepted),
+            finding(Severity::Info, FindingStatus::FalsePositive),
+        ];
+        let summary = summarize(&workspace);
+        assert_eq!(summary.total, 5);
+        let by_severity = &summary.by_severity;
+        assert_eq!((by_severity.critical, by_severity.high, by_severity.medium, by_severity.low, by_severity.info), (2, 1, 0, 1, 1));
+        let unresolved = &summary.unresolved;
+        assert_eq!((unresolved.critical, unresolved.high, unresolved.low, unresolved.info), (1, 1, 0, 0));
+        assert_eq!((summary.remediated, summary.accepted, summary.false_positive), (1, 1, 1));
+    }
+}
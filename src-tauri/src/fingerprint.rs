@@ -0,0 +1,327 @@
+use serde::{Serialize, Deserialize};
+use specta::Type;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::{Machine, Port, PortNotes};
+
+// Icon used until a machine has scan data (the frontend's original default)
+pub(crate) const DEFAULT_ICON: &str = "PC";
+
+// Icons the frontend knows how to draw
+pub(crate) const ICONS: &[&str] = &["PC", "Server", "DomainController", "Router", "Printer", "Storage", "Phone"];
+
+// Declaration order breaks ties between equally scored guesses, so keep the likelier ones first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Type)]
+pub(crate) enum OsFamily {
+    #[default]
+    Unknown,
+    Windows,
+    Linux,
+    MacOs,
+    Bsd,
+    // Cisco IOS, JunOS, RouterOS, FortiOS...
+    NetworkOs,
+    Ios,
+    Android,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Type)]
+pub(crate) enum DeviceType {
+    #[default]
+    Unknown,
+    Workstation,
+    Server,
+    DomainController,
+    NetworkDevice,
+    Printer,
+    Storage,
+    Mobile,
+}
+
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct OsFingerprint {
+    pub os: OsFamily,
+    // Most specific name seen, e.g. "Windows Server 2016" or "Ubuntu"
+    pub os_name: Option<String>,
+    pub device: DeviceType,
+    // 0-100
+    pub confidence: u8,
+    // Human readable reasons, e.g. "445/tcp: Microsoft Windows netbios-ssn"
+    pub evidence: Vec<String>,
+}
+
+impl OsFingerprint {
+    pub(crate) fn icon(&self) -> &'static str {
+        match self.device {
+            DeviceType::Server => "Server",
+            DeviceType::DomainController => "DomainController",
+            DeviceType::NetworkDevice => "Router",
+            DeviceType::Printer => "Printer",
+            DeviceType::Storage => "Storage",
+            DeviceType::Mobile => "Phone",
+            DeviceType::Workstation | DeviceType::Unknown => DEFAULT_ICON,
+        }
+    }
+}
+
+// Case-insensitive substring -> (OS, weight, specific name)
+const OS_PATTERNS: &[(&str, OsFamily, u32, Option<&str>)] = &[
+    ("windows server 2022", OsFamily::Windows, 90, Some("Windows Server 2022")),
+    ("windows server 2019", OsFamily::Windows, 90, Some("Windows Server 2019")),
+    ("windows server 2016", OsFamily::Windows, 90, Some("Windows Server 2016")),
+    ("windows server 2012", OsFamily::Windows, 90, Some("Windows Server 2012")),
+    ("windows server 2008", OsFamily::Windows, 90, Some("Windows Server 2008")),
+    ("windows 11", OsFamily::Windows, 90, Some("Windows 11")),
+    ("windows 10", OsFamily::Windows, 90, Some("Windows 10")),
+    ("windows 7", OsFamily::Windows, 90, Some("Windows 7")),
+    ("os: windows", OsFamily::Windows, 80, None),
+    ("microsoft windows", OsFamily::Windows, 50, None),
+    ("microsoft-ds", OsFamily::Windows, 30, None),
+    ("microsoft iis", OsFamily::Windows, 50, None),
+    ("microsoft sql server", OsFamily::Windows, 40, None),
+    ("microsoft terminal services", OsFamily::Windows, 50, None),
+    ("os: linux", OsFamily::Linux, 80, None),
+    ("ubuntu", OsFamily::Linux, 60, Some("Ubuntu")),
+    ("debian", OsFamily::Linux, 60, Some("Debian")),
+    ("centos", OsFamily::Linux, 60, Some("CentOS")),
+    ("red hat", OsFamily::Linux, 60, Some("Red Hat Enterprise Linux")),
+    ("fedora", OsFamily::Linux, 60, Some("Fedora")),
+    ("alpine", OsFamily::Linux, 40, Some("Alpine Linux")),
+    ("linux", OsFamily::Linux, 40, None),
+    ("samba smbd", OsFamily::Linux, 30, None),
+    ("os: mac os x", OsFamily::MacOs, 80, Some("macOS")),
+    ("macos", OsFamily::MacOs, 60, Some("macOS")),
+    ("apple", OsFamily::MacOs, 30, None),
+    ("freebsd", OsFamily::Bsd, 70, Some("FreeBSD")),
+    ("openbsd", OsFamily::Bsd, 70, Some("OpenBSD")),
+    ("pfsense", OsFamily::Bsd, 70, Some("pfSense")),
+    ("cisco ios", OsFamily::NetworkOs, 80, Some("Cisco IOS")),
+    ("os: ios", OsFamily::NetworkOs, 60, Some("Cisco IOS")),
+    ("junos", OsFamily::NetworkOs, 80, Some("JunOS")),
+    ("routeros", OsFamily::NetworkOs, 80, Some("MikroTik RouterOS")),
+    ("mikrotik", OsFamily::NetworkOs, 60, Some("MikroTik RouterOS")),
+    ("fortigate", OsFamily::NetworkOs, 70, Some("FortiOS")),
+    ("iphone", OsFamily::Ios, 70, Some("iOS")),
+    ("android", OsFamily::Android, 70, Some("Android")),
+];
+
+// Case-insensitive substring -> (device, weight)
+const DEVICE_PATTERNS: &[(&str, DeviceType, u32)] = &[
+    ("device: router", DeviceType::NetworkDevice, 80),
+    ("device: switch", DeviceType::NetworkDevice, 80),
+    ("device: firewall", DeviceType::NetworkDevice, 80),
+    ("device: printer", DeviceType::Printer, 80),
+    ("device: storage", DeviceType::Storage, 80),
+    ("device: phone", DeviceType::Mobile, 80),
+    ("active directory ldap", DeviceType::DomainController, 70),
+    ("microsoft windows kerberos", DeviceType::DomainController, 60),
+    ("jetdirect", DeviceType::Printer, 70),
+    ("cups", DeviceType::Printer, 40),
+    ("synology", DeviceType::Storage, 70),
+    ("qnap", DeviceType::Storage, 70),
+    ("freenas", DeviceType::Storage, 70),
+    ("truenas", DeviceType::Storage, 70),
+    ("cisco", DeviceType::NetworkDevice, 40),
+    ("mikrotik", DeviceType::NetworkDevice, 60),
+];
+
+#[derive(Default)]
+struct Votes<T> {
+    scores: HashMap<T, u32>,
+}
+
+impl<T: Copy + Ord + std::hash::Hash + Default> Votes<T> {
+    fn add(&mut self, value: T, weight: u32) {
+        *self.scores.entry(value).or_default() += weight;
+    }
+
+    // Winner and a confidence that drops when other candidates got votes too. On a tie the
+    // candidate declared first wins, so the result doesn't depend on the map's order.
+    fn winner(&self) -> (T, u8) {
+        let total: u32 = self.scores.values().sum();
+        match self.scores.iter().max_by_key(|(value, score)| (**score, Reverse(**value))) {
+            Some((value, score)) if total > 0 => {
+                let confidence = (*score).min(100) * score / total;
+                (*value, confidence as u8)
+            }
+            _ => (T::default(), 0),
+        }
+    }
+}
+
+fn port_lines(port: &Port) -> Vec<String> {
+    let mut lines = vec![port.application.clone()];
+    for note in &port.data {
+        match note {
+            PortNotes::NmapScan(output) => lines.extend(output.iter().cloned()),
+            PortNotes::ScriptOutput { script_id, output, .. } => {
+                lines.extend(output.iter().map(|line| format!("{}: {}", script_id, line)))
+            }
+            _ => {}
+        }
+    }
+    lines
+}
+
+// Typical TTLs: 64 for Linux/macOS/BSD, 128 for Windows, 255 for network gear. Only an
+// explicit "ttl=64", "ttl: 64" or "ttl 64" counts, not words like "Seattle" that contain it.
+fn ttl_guess(line: &str) -> Option<OsFamily> {
+    let lower = line.to_lowercase();
+    let ttl = lower.match_indices("ttl").find_map(|(start, _)| {
+        if lower[..start].chars().next_back().is_some_and(|c| c.is_alphanumeric()) {
+            return None;
+        }
+        let rest = lower[start + 3..].strip_prefix(['=', ':', ' '])?.trim_start();
+        let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        if rest[digits.len()..].chars().next().is_some_and(|c| c.is_alphanumeric()) {
+            return None;
+        }
+        digits.parse::<u32>().ok()
+    })?;
+    match ttl {
+        0 => None,
+        1..=64 => Some(OsFamily::Linux),
+        65..=128 => Some(OsFamily::Windows),
+        129..=255 => Some(OsFamily::NetworkOs),
+        _ => None,
+    }
+}
+
+pub(crate) fn detect(machine: &Machine) -> Option<OsFingerprint> {
+    if machine.ports.is_empty() {
+        return None;
+    }
+    let mut os = Votes::<OsFamily>::default();
+    let mut device = Votes::<DeviceType>::default();
+    let mut names: HashMap<OsFamily, (u32, &str)> = HashMap::new();
+    let mut evidence = vec![];
+
+    for port in &machine.ports {
+        for line in port_lines(port) {
+            let lower = line.to_lowercase();
+            let mut matched = false;
+            for (pattern, family, weight, name) in OS_PATTERNS {
+                if lower.contains(pattern) {
+                    os.add(*family, *weight);
+                    if let Some(name) = name {
+                        let best = names.entry(*family).or_insert((0, name));
+                        if *weight > best.0 {
+                            *best = (*weight, name);
+                        }
+                    }
+                    matched = true;
+                }
+            }
+            for (pattern, kind, weight) in DEVICE_PATTERNS {
+                if lower.contains(pattern) {
+                    device.add(*kind, *weight);
+                    matched = true;
+                }
+            }
+            if let Some(family) = ttl_guess(&line) {
+                os.add(family, 15);
+                matched = true;
+            }
+            if matched && evidence.len() < 10 {
+                evidence.push(format!("{}/{}: {}", port.number, port.protocol, line.trim()));
+            }
+        }
+    }
+
+    // Port-based hints, weaker than what the banners say
+    let open = |number: u16| machine.ports.iter().any(|port| port.number == number);
+    if open(135) || open(3389) || (open(139) && open(445)) {
+        os.add(OsFamily::Windows, 20);
+    }
+    if open(88) && open(389) {
+        device.add(DeviceType::DomainController, 60);
+        evidence.push("Kerberos and LDAP are both open".to_string());
+    }
+    if open(9100) || open(515) || open(631) {
+        device.add(DeviceType::Printer, 30);
+    }
+    if open(2049) || open(548) {
+        device.add(DeviceType::Storage, 20);
+    }
+    if open(62078) {
+        device.add(DeviceType::Mobile, 50);
+        os.add(OsFamily::Ios, 50);
+    }
+
+    let (os, os_confidence) = os.winner();
+    let (mut device, device_confidence) = device.winner();
+    if device == DeviceType::Unknown {
+        device = match os {
+            OsFamily::NetworkOs => DeviceType::NetworkDevice,
+            OsFamily::Ios | OsFamily::Android => DeviceType::Mobile,
+            _ => {
+                const SERVER_PORTS: &[u16] = &[21, 22, 25, 53, 80, 110, 143, 443, 1433, 3306, 5432, 8080, 8443];
+                let is_server = names.get(&os).is_some_and(|(_, name)| name.contains("Server"))
+                    || machine.ports.iter().filter(|port| SERVER_PORTS.contains(&port.number)).count() >= 2;
+                if is_server { DeviceType::Server } else { DeviceType::Workstation }
+            }
+        };
+    }
+
+    Some(OsFingerprint {
+        os,
+        os_name: names.get(&os).map(|(_, name)| name.to_string()),
+        device,
+        confidence: if device_confidence > 0 { os_confidence.max(device_confidence) } else { os_confidence },
+        evidence,
+    })
+}
+
+// Re-runs detection and picks the icon unless the user chose one
+pub(crate) fn refresh(machine: &mut Machine) {
+    machine.fingerprint = detect(machine);
+    machine.icon = match (&machine.icon_override, &machine.fingerprint) {
+        (Some(icon), _) => icon.clone(),
+        (None, Some(fingerprint)) => fingerprint.icon().to_string(),
+        (None, None) => DEFAULT_ICON.to_string(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::test_port;
+
+    fn machine(lines: &[&str]) -> Machine {
+        let mut machine = Machine::new(1, "host".to_string(), "10.0.0.9".to_string());
+        machine.ports.push(test_port(8000, "http", "", vec![PortNotes::NmapScan(lines.iter().map(|line| line.to_string()).collect())]));
+        machine
+    }
+
+    #[test]
+    fn ttl_needs_an_explicit_value() {
+        assert_eq!(ttl_guess("Reply from 10.0.0.9: bytes=32 time<1ms TTL=128"), Some(OsFamily::Windows));
+        assert_eq!(ttl_guess("open  tcp  syn-ack ttl 64"), Some(OsFamily::Linux));
+        assert_eq!(ttl_guess("ttl: 255"), Some(OsFamily::NetworkOs));
+        assert!(ttl_guess("http-title: Little Shop of Seattle, est. 1998").is_none());
+        assert!(ttl_guess("settle=120").is_none());
+        assert!(ttl_guess("ttl=64abc").is_none());
+        assert!(ttl_guess("ttl=0").is_none());
+    }
+
+    #[test]
+    fn titles_do_not_decide_the_os() {
+        let fingerprint = detect(&machine(&["http-title: Seattle 128 Little Shop"])).unwrap();
+        assert_eq!(fingerprint.os, OsFamily::Unknown);
+        assert!(fingerprint.evidence.is_empty());
+
+        let fingerprint = detect(&machine(&["Apache httpd 2.4.41 ((Ubuntu))", "syn-ack ttl 63"])).unwrap();
+        assert_eq!(fingerprint.os, OsFamily::Linux);
+        assert_eq!(fingerprint.os_name.as_deref(), Some("Ubuntu"));
+    }
+
+    #[test]
+    fn ties_are_broken_the_same_way_every_time() {
+        // Windows and Linux both get 30
+        let machine = machine(&["microsoft-ds", "Samba smbd 4.6.2"]);
+        for _ in 0..20 {
+            assert_eq!(detect(&machine).unwrap().os, OsFamily::Windows);
+        }
+    }
+}
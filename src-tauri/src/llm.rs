@@ -0,0 +1,274 @@
+use reqwest::RequestBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use specta::Type;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::chat::{estimate_tokens, ChatMessage, Role};
+use crate::error::{AppError, AppResult};
+
+pub(crate) const DEFAULT_SYSTEM_PROMPT: &str = "You are an expert penetration tester and security analyst. \
+    Focus on identifying vulnerabilities, potential attack vectors, and security implications. \
+    Be direct and concise.";
+
+const OLLAMA_URL: &str = "http://127.0.0.1:11434";
+const OPENAI_URL: &str = "https://api.openai.com/v1";
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Provider {
+    // The scanning service's /ask endpoint
+    Sidecar,
+    // Ollama's native /api/chat
+    Ollama,
+    // Any /chat/completions endpoint: OpenAI, LM Studio, vLLM, llama.cpp server...
+    OpenAi,
+}
+
+#[derive(Clone, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub(crate) struct AiSettings {
+    pub provider: Provider,
+    // Empty for the provider's usual address
+    pub url: String,
+    // Empty lets the sidecar pick; required by the other providers
+    pub model: String,
+    pub api_key: Option<String>,
+    pub temperature: f32,
+    pub system_prompt: String,
+    // Tokens the model accepts, which limits how much conversation history is sent
+    pub context_window: u32,
+    // The sidecar passes prompts on to whichever model it was set up with, so it only counts
+    // as local when the user says it runs one on this machine
+    pub sidecar_is_local: bool,
+}
+
+impl Default for AiSettings {
+    fn default() -> Self {
+        AiSettings {
+            provider: Provider::Sidecar,
+            url: String::new(),
+            model: String::new(),
+            api_key: None,
+            temperature: 0.2,
+            system_prompt: DEFAULT_SYSTEM_PROMPT.to_string(),
+            context_window: 8192,
+            sidecar_is_local: false,
+        }
+    }
+}
+
+impl AiSettings {
+    pub(crate) fn validate(&self) -> AppResult<()> {
+        if !(0.0..=2.0).contains(&self.temperature) {
+            return Err(AppError::validation("Temperature must be between 0 and 2"));
+        }
+        if self.context_window < 512 {
+            return Err(AppError::validation("The context window must be at least 512 tokens"));
+        }
+        if self.provider != Provider::Sidecar && self.model.trim().is_empty() {
+            return Err(AppError::validation("Choose a model for this provider"));
+        }
+        Ok(())
+    }
+
+    fn base_url(&self, sidecar_url: &str) -> String {
+        let url = match (self.url.trim(), self.provider) {
+            ("", Provider::Sidecar) => sidecar_url,
+            ("", Provider::Ollama) => OLLAMA_URL,
+            ("", Provider::OpenAi) => OPENAI_URL,
+            (url, _) => url,
+        };
+        url.trim_end_matches('/').to_string()
+    }
+
+    // Whether prompts stay on this machine
+    pub(crate) fn is_local(&self, sidecar_url: &str) -> bool {
+        if self.provider == Provider::Sidecar && !self.sidecar_is_local {
+            return false;
+        }
+        let host = reqwest::Url::parse(&self.base_url(sidecar_url))
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string));
+        match host.as_deref() {
+            Some("localhost") => true,
+            Some(host) => host.trim_matches(['[', ']']).parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback()),
+            None => false,
+        }
+    }
+
+    // Tokens left for earlier messages once the prompt is in and a quarter of the window is
+    // kept for the answer
+    pub(crate) fn history_budget(&self, prompt_tokens: usize) -> usize {
+        let window = self.context_window as usize;
+        window.saturating_sub(window / 4).saturating_sub(prompt_tokens)
+    }
+}
+
+fn settings_path(dir: &Path) -> PathBuf {
+    dir.join("ai.json")
+}
+
+pub(crate) fn load_settings(dir: &Path) -> AiSettings {
+    fs::read_to_string(settings_path(dir))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+pub(crate) fn save_settings(dir: &Path, settings: &AiSettings) -> AppResult<()> {
+    let json = serde_json::to_string_pretty(settings)?;
+    fs::write(settings_path(dir), json).map_err(|e| AppError::storage("Failed to write AI settings", e))
+}
+
+// What is asked: the workspace data it's about, the conversation so far and the question
+pub(crate) struct Prompt {
+    // Empty when the question carries everything, as for port analysis
+    pub context: String,
+    pub history: Vec<ChatMessage>,
+    pub question: String,
+}
+
+impl Prompt {
+    pub(crate) fn new(question: String) -> Self {
+        Prompt { context: String::new(), history: vec![], question }
+    }
+
+    // Everything in one piece of text, for the sidecar
+    pub(crate) fn flatten(&self, system_prompt: &str) -> String {
+        if self.context.is_empty() && self.history.is_empty() {
+            return self.question.clone();
+        }
+        let mut conversation = String::new();
+        for message in &self.history {
+            conversation.push_str(&format!("{}: {}\n\n", speaker(message.role), message.content.trim()));
+        }
+        if !conversation.is_empty() {
+            conversation = format!("Conversation so far:\n\n{}", conversation);
+        }
+        format!(
+            "{} Provide practical security insights based on this context:\n\n{}\n\n{}Question: {}",
+            system_prompt,
+            self.context,
+            conversation,
+            self.question
+        )
+    }
+
+    // The system prompt with the context, then the conversation, for chat APIs
+    pub(crate) fn messages(&self, system_prompt: &str) -> Vec<Value> {
+        let system = match self.context.is_empty() {
+            true => system_prompt.to_string(),
+            false => format!("{}\n\nContext:\n\n{}", system_prompt, self.context),
+        };
+        let mut messages = vec![json!({ "role": "system", "content": system })];
+        for message in &self.history {
+            let role = match message.role {
+                Role::User => "user",
+                Role::Assistant => "assistant",
+            };
+            messages.push(json!({ "role": role, "content": message.content }));
+        }
+        messages.push(json!({ "role": "user", "content": self.question }));
+        messages
+    }
+
+    // What the prompt costs before any history is added
+    pub(crate) fn tokens(&self, system_prompt: &str) -> usize {
+        estimate_tokens(system_prompt) + estimate_tokens(&self.context) + estimate_tokens(&self.question)
+    }
+}
+
+fn speaker(role: Role) -> &'static str {
+    match role {
+        Role::User => "User",
+        Role::Assistant => "Assistant",
+    }
+}
+
+// Where a streaming request for the configured provider goes and what it carries
+pub(crate) fn request_body(settings: &AiSettings, sidecar_url: &str, prompt: &Prompt) -> (String, Value) {
+    let url = settings.base_url(sidecar_url);
+    match settings.provider {
+        Provider::Sidecar => {
+            let mut body = json!({ "question": prompt.flatten(&settings.system_prompt), "stream": true });
+            if !settings.model.is_empty() {
+                body["model"] = json!(settings.model);
+            }
+            (format!("{}/ask", url), body)
+        }
+        Provider::Ollama => (format!("{}/api/chat", url), json!({
+            "model": settings.model,
+            "messages": prompt.messages(&settings.system_prompt),
+            "stream": true,
+            "options": { "temperature": settings.temperature, "num_ctx": settings.context_window },
+        })),
+        Provider::OpenAi => (format!("{}/chat/completions", url), json!({
+            "model": settings.model,
+            "messages": prompt.messages(&settings.system_prompt),
+            "stream": true,
+            "temperature": settings.temperature,
+        })),
+    }
+}
+
+pub(crate) fn request(client: &reqwest::Client, settings: &AiSettings, sidecar_url: &str, prompt: &Prompt) -> RequestBuilder {
+    let (url, body) = request_body(settings, sidecar_url, prompt);
+    let request = client.post(url).json(&body);
+    match settings.api_key.as_deref().filter(|key| !key.is_empty()) {
+        Some(key) => request.bearer_auth(key),
+        None => request,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange() -> Prompt {
+        Prompt {
+            context: "Machine: dc01 (10.0.0.5)".to_string(),
+            history: vec![
+                ChatMessage { role: Role::User, content: "Any SMB?".to_string(), timestamp: String::new(), model: None },
+                ChatMessage { role: Role::Assistant, content: "Port 445 is open".to_string(), timestamp: String::new(), model: None },
+            ],
+            question: "Signing?".to_string(),
+        }
+    }
+
+    #[test]
+    fn prompts_for_each_kind_of_provider() {
+        let prompt = exchange();
+        let text = prompt.flatten("Be brief.");
+        assert!(text.starts_with("Be brief. Provide practical security insights"));
+        assert!(text.contains("User: Any SMB?\n\nAssistant: Port 445 is open\n\nQuestion: Signing?"));
+        assert_eq!(Prompt::new("Analyze this port".to_string()).flatten("Be brief."), "Analyze this port");
+
+        let messages = prompt.messages("Be brief.");
+        let roles: Vec<&str> = messages.iter().map(|message| message["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, ["system", "user", "assistant", "user"]);
+        assert!(messages[0]["content"].as_str().unwrap().ends_with("Context:\n\nMachine: dc01 (10.0.0.5)"));
+    }
+
+    #[test]
+    fn settings_are_checked() {
+        let mut settings = AiSettings { provider: Provider::Ollama, ..AiSettings::default() };
+        assert!(settings.validate().is_err());
+        settings.model = "llama3.1".to_string();
+        settings.validate().unwrap();
+        settings.temperature = 3.0;
+        assert!(settings.validate().is_err());
+
+        assert_eq!(settings.base_url("http://127.0.0.1:8084"), OLLAMA_URL);
+        settings.url = "http://gpu-box:11434/".to_string();
+        assert_eq!(settings.base_url("http://127.0.0.1:8084"), "http://gpu-box:11434");
+        assert!(!settings.is_local("http://127.0.0.1:8084"));
+        settings.url = "http://[::1]:11434".to_string();
+        assert!(settings.is_local("http://127.0.0.1:8084"));
+        assert!(!AiSettings::default().is_local("http://localhost:8084"));
+        assert!(AiSettings { sidecar_is_local: true, ..AiSettings::default() }.is_local("http://localhost:8084"));
+        assert!(!AiSettings { provider: Provider::OpenAi, ..AiSettings::default() }.is_local("http://127.0.0.1:8084"));
+        assert_eq!(AiSettings::default().history_budget(1000), 5144);
+    }
+}
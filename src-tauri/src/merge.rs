@@ -0,0 +1,533 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use specta::Type;
+use std::collections::{HashMap, HashSet};
+
+use crate::compromise::Evidence;
+use crate::error::{AppError, AppResult};
+use crate::store;
+use crate::{Database, Note, PortNotes, Workspace};
+
+// A field both copies changed in different ways
+#[derive(Serialize, Type)]
+pub(crate) struct Conflict {
+    pub workspace: String,
+    // e.g. "machine 10.0.0.5 / port 80/tcp / application"
+    pub field: String,
+    // The value that was kept
+    pub ours: Value,
+    pub theirs: Value,
+}
+
+#[derive(Default, Serialize, Type)]
+pub(crate) struct MergeReport {
+    pub workspaces_added: u32,
+    pub machines_added: u32,
+    pub ports_added: u32,
+    pub notes_added: u32,
+    pub conflicts: Vec<Conflict>,
+}
+
+// What can be merged in: a whole database.json or a single workspace
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Import {
+    Database(Database),
+    Workspace(Workspace),
+}
+
+struct Merger {
+    // Whether the incoming side wins a conflict; the current one is kept otherwise
+    prefer_incoming: bool,
+    workspace: String,
+    report: MergeReport,
+}
+
+// Applies the changes `incoming` made since `base` on top of `current`. Without a base both
+// sides are combined and `incoming` wins where they differ. Lists are matched by identity, so
+// additions made on both sides since the base are all kept.
+pub(crate) fn merge_workspace(current: &Workspace, base: Option<&Workspace>, incoming: &Workspace) -> AppResult<Workspace> {
+    let mut merger = Merger { prefer_incoming: true, workspace: current.name.clone(), report: MergeReport::default() };
+    merger.workspace(current, base, incoming)
+}
+
+impl Import {
+    // Brought up to date like the local database, since exports can come from older versions
+    pub(crate) fn into_database(self) -> Database {
+        let mut database = match self {
+            Import::Database(database) => database,
+            Import::Workspace(workspace) => Database { data: vec![workspace], ..Database::default() },
+        };
+        store::migrate_database(&mut database);
+        database
+    }
+}
+
+// Merges another copy of the database into this one. Workspaces are matched by name, or the
+// single imported workspace goes into `target` when given. Where both copies have a field
+// with different values ours is kept and the difference reported.
+pub(crate) fn merge_database(database: &mut Database, incoming: Database, target: Option<u32>) -> AppResult<MergeReport> {
+    if target.is_some() && incoming.data.len() != 1 {
+        return Err(AppError::validation("Only a single workspace can be merged into a chosen workspace"));
+    }
+    let mut merger = Merger { prefer_incoming: false, workspace: String::new(), report: MergeReport::default() };
+    for workspace in incoming.data {
+        let existing = match target {
+            Some(id) => Some(database.workspace_mut(id)?),
+            None => database.data.iter_mut().find(|existing| existing.name.eq_ignore_ascii_case(&workspace.name)),
+        };
+        match existing {
+            Some(existing) => {
+                merger.workspace = existing.name.clone();
+                *existing = merger.workspace(existing, None, &workspace)?;
+            }
+            None => {
+                let mut workspace = workspace;
+                workspace.id = database.next_workspace_id();
+                database.data.push(workspace);
+                merger.report.workspaces_added += 1;
+            }
+        }
+    }
+    Ok(merger.report)
+}
+
+// What makes two list entries the same object; `path` is the list's field path in the workspace
+fn identity(path: &str, item: &Value) -> String {
+    let field = |name: &str| item.get(name).map(Value::to_string).unwrap_or_default();
+    match path {
+        // Machines by IP and ports by number and protocol, since IDs are handed out per copy
+        "data" => field("ip"),
+        "data.ports" => format!("{}/{}", field("number"), field("protocol")),
+        "findings" => field("title"),
+        "data.ports.data" => {
+            if let Some(note) = item.get("PentestNote") {
+                format!("note:{}", note["id"])
+            } else if let Some(credentials) = item.get("Credentials") {
+                // The same secret found twice is one credential, whoever added it
+                format!("credentials:{}:{}:{}", credentials["name"], credentials["hash"], credentials["password"])
+            } else if let Some((kind, note)) = item.as_object().and_then(|variant| variant.iter().next()) {
+                // Other notes by their content, since each copy gives older ones their own IDs
+                let mut note = note.clone();
+                if let Some(fields) = note.as_object_mut() {
+                    fields.remove("id");
+                }
+                format!("{}:{}", kind, note)
+            } else {
+                item.to_string()
+            }
+        }
+        _ => match item.get("id") {
+            Some(id) if id != "" => id.to_string(),
+            _ => item.to_string(),
+        },
+    }
+}
+
+// How a list entry is named in conflicts
+fn label(path: &str, item: &Value) -> String {
+    let text = |value: &Value| value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string());
+    match path {
+        "data" => format!("machine {}", text(&item["ip"])),
+        "data.ports" => format!("port {}/{}", text(&item["number"]), text(&item["protocol"])),
+        "findings" => format!("finding {}", text(&item["title"])),
+        "notes" | "data.notes" => format!("note {}", text(&item["id"])),
+        "data.ports.data" => match item.as_object().and_then(|variant| variant.iter().next()) {
+            Some((kind, note)) if kind == "PentestNote" => format!("note {}", text(&note["id"])),
+            Some((kind, _)) => kind.to_lowercase(),
+            None => text(item),
+        },
+        _ => path.rsplit('.').next().unwrap_or(path).to_string(),
+    }
+}
+
+fn join(location: &str, part: &str) -> String {
+    if location.is_empty() {
+        part.to_string()
+    } else {
+        format!("{} / {}", location, part)
+    }
+}
+
+impl Merger {
+    fn workspace(&mut self, current: &Workspace, base: Option<&Workspace>, incoming: &Workspace) -> AppResult<Workspace> {
+        let base = base.map(serde_json::to_value).transpose()?;
+        let incoming = align_ids(current, incoming);
+        let merged = self.merge("", "", &serde_json::to_value(current)?, base.as_ref(), &serde_json::to_value(&incoming)?);
+        let mut workspace: Workspace = serde_json::from_value(merged)
+            .map_err(|e| AppError::validation(format!("The merged workspace is not valid: {}", e)))?;
+        workspace.last_machine_id = workspace.data.iter().map(|machine| machine.id)
+            .fold(current.last_machine_id.max(incoming.last_machine_id), u32::max);
+        workspace.last_finding_id = workspace.findings.iter().map(|finding| finding.id)
+            .fold(current.last_finding_id.max(incoming.last_finding_id), u32::max);
+        Ok(workspace)
+    }
+
+    fn merge(&mut self, path: &str, location: &str, current: &Value, base: Option<&Value>, incoming: &Value) -> Value {
+        // IDs are handed out by each copy separately, entries are matched by identity instead.
+        // The counters are worked out again once everything is merged.
+        if path == "id" || path.ends_with(".id") || matches!(path, "last_machine_id" | "last_finding_id") {
+            return current.clone();
+        }
+        if base == Some(incoming) || current == incoming {
+            return current.clone();
+        }
+        if base == Some(current) {
+            return incoming.clone();
+        }
+        match (current, incoming) {
+            // Objects with different fields are different enum variants and handled as a whole
+            (Value::Object(current), Value::Object(incoming)) if current.keys().eq(incoming.keys()) => {
+                let base = base.and_then(Value::as_object);
+                let merged: Map<String, Value> = current.iter()
+                    .map(|(key, value)| {
+                        let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                        // Enum variant names and lists don't help anyone find the field, list
+                        // entries are named by their label instead
+                        let location = match key.starts_with(char::is_uppercase) || value.is_array() {
+                            true => location.to_string(),
+                            false => join(location, key),
+                        };
+                        let base = base.and_then(|base| base.get(key));
+                        (key.clone(), self.merge(&child, &location, value, base, &incoming[key]))
+                    })
+                    .collect();
+                Value::Object(merged)
+            }
+            (Value::Array(current), Value::Array(incoming)) => {
+                Value::Array(self.merge_list(path, location, current, base.and_then(Value::as_array), incoming))
+            }
+            _ => {
+                self.report.conflicts.push(Conflict {
+                    workspace: self.workspace.clone(),
+                    field: location.to_string(),
+                    ours: current.clone(),
+                    theirs: incoming.clone(),
+                });
+                match self.prefer_incoming {
+                    true => incoming.clone(),
+                    false => current.clone(),
+                }
+            }
+        }
+    }
+
+    fn merge_list(&mut self, path: &str, location: &str, current: &[Value], base: Option<&Vec<Value>>, incoming: &[Value]) -> Vec<Value> {
+        let by_identity = |items: &[Value]| -> HashMap<String, Value> {
+            items.iter().map(|item| (identity(path, item), item.clone())).collect()
+        };
+        let base_items = base.map(|base| by_identity(base)).unwrap_or_default();
+        let incoming_items = by_identity(incoming);
+
+        let mut merged = vec![];
+        let mut seen = HashSet::new();
+        for item in current {
+            let key = identity(path, item);
+            match incoming_items.get(&key) {
+                Some(theirs) => {
+                    let location = join(location, &label(path, item));
+                    merged.push(self.merge(path, &location, item, base_items.get(&key), theirs));
+                }
+                // Removed by the incoming side
+                None if base_items.contains_key(&key) => {}
+                None => merged.push(item.clone()),
+            }
+            seen.insert(key);
+        }
+        for item in incoming {
+            let key = identity(path, item);
+            // Anything in the base but no longer here was removed on the current side
+            if !seen.contains(&key) && !base_items.contains_key(&key) {
+                merged.push(item.clone());
+                seen.insert(key);
+                match path {
+                    "data" => self.report.machines_added += 1,
+                    "data.ports" => self.report.ports_added += 1,
+                    "notes" | "data.notes" | "data.ports.data" => self.report.notes_added += 1,
+                    _ => {}
+                }
+            }
+        }
+        merged
+    }
+}
+
+// The ID each incoming entry gets: that of the current entry with the same key, its own if the
+// current copy never handed it out, and a fresh one otherwise
+fn new_ids(current: &[(u32, &str)], handed_out: u32, incoming: &[(u32, &str)]) -> Vec<u32> {
+    let by_key: HashMap<&str, u32> = current.iter().map(|&(id, key)| (key, id)).collect();
+    let handed_out = current.iter().map(|&(id, _)| id).fold(handed_out, u32::max);
+    let mut used = HashSet::new();
+    let mut next = incoming.iter().map(|&(id, _)| id).fold(handed_out, u32::max) + 1;
+    incoming.iter()
+        .map(|&(id, key)| match by_key.get(key) {
+            Some(&existing) => existing,
+            None if id > handed_out && used.insert(id) => id,
+            None => {
+                next += 1;
+                next - 1
+            }
+        })
+        .collect()
+}
+
+// Each copy hands out machine and finding IDs separately. Renumbers the incoming copy to match
+// the current one and rewrites everything that refers to them, so entries matched by identity
+// agree on their ID and new ones don't take one that is already in use.
+fn align_ids(current: &Workspace, incoming: &Workspace) -> Workspace {
+    let mut incoming = incoming.clone();
+    let machine_ids = new_ids(
+        &current.data.iter().map(|machine| (machine.id, machine.ip.as_str())).collect::<Vec<_>>(),
+        current.last_machine_id,
+        &incoming.data.iter().map(|machine| (machine.id, machine.ip.as_str())).collect::<Vec<_>>(),
+    );
+    let finding_ids = new_ids(
+        &current.findings.iter().map(|finding| (finding.id, finding.title.as_str())).collect::<Vec<_>>(),
+        current.last_finding_id,
+        &incoming.findings.iter().map(|finding| (finding.id, finding.title.as_str())).collect::<Vec<_>>(),
+    );
+    let machines: HashMap<u32, u32> = incoming.data.iter().map(|machine| machine.id).zip(machine_ids.iter().copied()).collect();
+    let findings: HashMap<u32, u32> = incoming.findings.iter().map(|finding| finding.id).zip(finding_ids.iter().copied()).collect();
+    let machine_id = |id: &mut u32| *id = machines.get(id).copied().unwrap_or(*id);
+    let finding_id = |id: &mut u32| *id = findings.get(id).copied().unwrap_or(*id);
+
+    for (machine, id) in incoming.data.iter_mut().zip(machine_ids) {
+        machine.id = id;
+        for change in machine.status_history.iter_mut() {
+            if let Some(Evidence::Credential { machine_id: evidence_machine, .. }) = &mut change.evidence {
+                machine_id(evidence_machine);
+            }
+        }
+        for note in machine.ports.iter_mut().flat_map(|port| port.data.iter_mut()) {
+            if let PortNotes::FindingRef { finding_id: id, .. } = note {
+                finding_id(id);
+            }
+        }
+    }
+    for (finding, id) in incoming.findings.iter_mut().zip(finding_ids) {
+        finding.id = id;
+        for asset in finding.affected.iter_mut() {
+            machine_id(&mut asset.machine_id);
+        }
+    }
+
+    align_note_ids(current.notes.iter().map(note_key), incoming.notes.iter_mut().map(note_key_mut));
+    for machine in incoming.data.iter_mut() {
+        let Some(ours) = current.data.iter().find(|ours| ours.ip == machine.ip) else {
+            continue;
+        };
+        align_note_ids(ours.notes.iter().map(note_key), machine.notes.iter_mut().map(note_key_mut));
+        for port in machine.ports.iter_mut() {
+            let Some(our_port) = ours.ports.iter().find(|ours| ours.number == port.number && ours.protocol == port.protocol) else {
+                continue;
+            };
+            align_note_ids(our_port.data.iter().filter_map(port_note_key), port.data.iter_mut().filter_map(port_note_key_mut));
+        }
+    }
+    incoming
+}
+
+// Stage, content and creation time, which tell two notes apart when their IDs don't
+type NoteKey<'a> = (&'a str, &'a str, &'a str);
+
+fn note_key(note: &Note) -> (&str, NoteKey<'_>) {
+    (&note.id, (&note.stage, &note.content, &note.timestamp))
+}
+
+fn note_key_mut(note: &mut Note) -> (&mut String, NoteKey<'_>) {
+    (&mut note.id, (&note.stage, &note.content, &note.timestamp))
+}
+
+fn port_note_key(note: &PortNotes) -> Option<(&str, NoteKey<'_>)> {
+    match note {
+        PortNotes::PentestNote { id, stage, content, timestamp, .. } => Some((id, (stage, content, timestamp))),
+        _ => None,
+    }
+}
+
+fn port_note_key_mut(note: &mut PortNotes) -> Option<(&mut String, NoteKey<'_>)> {
+    match note {
+        PortNotes::PentestNote { id, stage, content, timestamp, .. } => Some((id, (stage.as_str(), content.as_str(), timestamp.as_str()))),
+        _ => None,
+    }
+}
+
+// Each copy gives a note it migrated or imported an ID of its own. An incoming note whose ID
+// the current copy doesn't know takes that of the current note with the same key.
+fn align_note_ids<'a, 'b>(current: impl Iterator<Item = (&'a str, NoteKey<'a>)>, incoming: impl Iterator<Item = (&'b mut String, NoteKey<'b>)>) {
+    let current: Vec<_> = current.collect();
+    for (id, key) in incoming {
+        if current.iter().any(|(known, _)| *known == id.as_str()) {
+            continue;
+        }
+        if let Some((known, _)) = current.iter().find(|(_, other)| *other == key) {
+            *id = known.to_string();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::findings::{AffectedAsset, Finding, FindingStatus, Severity};
+    use crate::notes::new_port_note;
+    use crate::store::tests::test_port;
+    use crate::Machine;
+
+    fn workspace() -> Workspace {
+        let mut workspace = Workspace::new(1, "Lab".to_string(), "10.0.0.0/24".to_string());
+        let mut machine = Machine::new(1, "web".to_string(), "10.0.0.2".to_string());
+        machine.ports.push(test_port(80, "http", "nginx", vec![]));
+        workspace.data.push(machine);
+        workspace
+    }
+
+    fn note_count(workspace: &Workspace) -> usize {
+        workspace.data[0].ports[0].data.len()
+    }
+
+    #[test]
+    fn keeps_additions_from_both_sides() {
+        let base = workspace();
+        let mut ours = base.clone();
+        let mut theirs = base.clone();
+        ours.data[0].ports[0].data.push(new_port_note("Enumeration".to_string(), "Ours".to_string()));
+        theirs.data[0].ports[0].data.push(new_port_note("Enumeration".to_string(), "Theirs".to_string()));
+        ours.data.push(Machine::new(2, "db".to_string(), "10.0.0.3".to_string()));
+        theirs.data.push(Machine::new(2, "mail".to_string(), "10.0.0.4".to_string()));
+
+        let merged = merge_workspace(&ours, Some(&base), &theirs).unwrap();
+        assert_eq!(note_count(&merged), 2);
+        let ids: Vec<u32> = merged.data.iter().map(|machine| machine.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn applies_incoming_edits_and_deletions() {
+        let mut base = workspace();
+        base.data[0].ports[0].data.push(new_port_note("Enumeration".to_string(), "Old".to_string()));
+        let mut ours = base.clone();
+        ours.data[0].hostname = "www".to_string();
+        let mut theirs = base.clone();
+        theirs.data[0].ports[0].data.clear();
+        theirs.data[0].ports[0].application = "nginx 1.25".to_string();
+
+        let merged = merge_workspace(&ours, Some(&base), &theirs).unwrap();
+        assert_eq!(merged.data[0].hostname, "www");
+        assert_eq!(merged.data[0].ports[0].application, "nginx 1.25");
+        assert_eq!(note_count(&merged), 0);
+    }
+
+    #[test]
+    fn without_base_nothing_is_removed() {
+        let mut ours = workspace();
+        ours.data[0].ports[0].data.push(PortNotes::Credentials {
+            id: "c1".to_string(),
+            name: Some("admin".to_string()),
+            hash: None,
+            password: Some("admin".to_string()),
+            author: Some("alice".to_string()),
+        });
+        let mut theirs = workspace();
+        theirs.data[0].ports[0].data.push(PortNotes::Credentials {
+            id: "c2".to_string(),
+            name: Some("admin".to_string()),
+            hash: None,
+            password: Some("admin".to_string()),
+            author: Some("bob".to_string()),
+        });
+        theirs.data[0].hostname = "www".to_string();
+
+        let merged = merge_workspace(&ours, None, &theirs).unwrap();
+        assert_eq!(note_count(&merged), 1);
+        assert_eq!(merged.data[0].hostname, "www");
+    }
+
+    #[test]
+    fn notes_with_different_ids_match_by_content() {
+        let mut ours = workspace();
+        let note = new_port_note("Enumeration".to_string(), "Default page".to_string());
+        ours.data[0].ports[0].data.push(note);
+        ours.notes.push(crate::notes::new_note("Recon".to_string(), "Scope agreed".to_string()));
+        let mut theirs = ours.clone();
+        if let PortNotes::PentestNote { id, .. } = &mut theirs.data[0].ports[0].data[0] {
+            *id = "theirs".to_string();
+        }
+        theirs.notes[0].id = "theirs".to_string();
+
+        let mut merger = Merger { prefer_incoming: false, workspace: String::new(), report: MergeReport::default() };
+        let merged = merger.workspace(&ours, None, &theirs).unwrap();
+        assert_eq!(note_count(&merged), 1);
+        assert_eq!(merged.notes.len(), 1);
+        assert_eq!(merger.report.notes_added, 0);
+    }
+
+    #[test]
+    fn database_merge_keeps_ours_and_reports_conflicts() {
+        let mut database = Database { data: vec![workspace()], ..Database::default() };
+        let mut theirs = workspace();
+        theirs.id = 7;
+        theirs.data[0].id = 4;
+        theirs.data[0].ports[0].application = "Apache".to_string();
+        theirs.data[0].ports[0].data.push(new_port_note("Enumeration".to_string(), "Theirs".to_string()));
+        theirs.data.push(Machine::new(5, "db".to_string(), "10.0.0.3".to_string()));
+        let other = Workspace::new(2, "DMZ".to_string(), "192.168.1.0/24".to_string());
+        let incoming = Database { data: vec![theirs, other], ..Database::default() };
+
+        let report = merge_database(&mut database, incoming, None).unwrap();
+        assert_eq!((report.workspaces_added, report.machines_added, report.notes_added), (1, 1, 1));
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].field, "machine 10.0.0.2 / port 80/tcp / application");
+        assert_eq!(report.conflicts[0].theirs, "Apache");
+
+        let lab = &database.data[0];
+        assert_eq!(lab.data[0].ports[0].application, "nginx");
+        assert_eq!(note_count(lab), 1);
+        let ids: Vec<u32> = lab.data.iter().map(|machine| machine.id).collect();
+        assert_eq!(ids, vec![1, 5]);
+        assert_eq!(database.data[1].id, 2);
+    }
+
+    fn finding(id: u32, title: &str, machines: &[u32]) -> Finding {
+        Finding {
+            id,
+            title: title.to_string(),
+            severity: Severity::High,
+            cvss_vector: None,
+            cvss_score: None,
+            affected: machines.iter().map(|&machine_id| AffectedAsset { machine_id, port_number: None }).collect(),
+            description: String::new(),
+            evidence: String::new(),
+            remediation: String::new(),
+            status: FindingStatus::Open,
+        }
+    }
+
+    #[test]
+    fn overlapping_ids_are_renumbered_with_their_references() {
+        let mut ours = workspace();
+        ours.data.push(Machine::new(2, "db".to_string(), "10.0.0.3".to_string()));
+        ours.findings.push(finding(1, "SQL injection", &[2]));
+
+        // Their web server is machine 5, their new mail server and finding take IDs we use
+        let mut theirs = workspace();
+        theirs.data[0].id = 5;
+        theirs.data.push(Machine::new(2, "mail".to_string(), "10.0.0.9".to_string()));
+        theirs.findings.push(finding(1, "Weak TLS", &[2, 5]));
+        theirs.findings.push(finding(2, "SQL injection", &[5]));
+        theirs.data[0].ports[0].data.push(PortNotes::FindingRef { id: "r1".to_string(), finding_id: 1 });
+
+        let mut database = Database { data: vec![ours], ..Database::default() };
+        let report = merge_database(&mut database, Database { data: vec![theirs], ..Database::default() }, None).unwrap();
+        assert!(report.conflicts.is_empty());
+
+        let lab = &database.data[0];
+        let machines: Vec<(u32, &str)> = lab.data.iter().map(|machine| (machine.id, machine.hostname.as_str())).collect();
+        assert_eq!(machines, vec![(1, "web"), (2, "db"), (6, "mail")]);
+        let findings: Vec<(u32, &str, Vec<u32>)> = lab.findings.iter()
+            .map(|finding| (finding.id, finding.title.as_str(), finding.affected.iter().map(|asset| asset.machine_id).collect()))
+            .collect();
+        assert_eq!(findings, vec![(1, "SQL injection", vec![2, 1]), (3, "Weak TLS", vec![6, 1])]);
+        assert!(matches!(lab.data[0].ports[0].data[..], [PortNotes::FindingRef { finding_id: 3, .. }]));
+    }
+}
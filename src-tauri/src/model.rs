@@ -0,0 +1,379 @@
+use serde::{Serialize, Deserialize};
+use specta::Type;
+use std::env;
+
+use crate::compromise::{AccessLevel, StatusChange};
+use crate::cve::CveMatch;
+use crate::error::{AppError, AppResult, Entity};
+use crate::findings::Finding;
+use crate::fingerprint::{self, OsFingerprint};
+use crate::notes::NoteRevision;
+use crate::redact::RedactionPolicy;
+use crate::tags::Tag;
+
+#[derive(Clone,Serialize,Deserialize)]
+pub(crate) struct Port {
+    pub service: String,
+    pub application: String,
+    pub protocol: String,
+    pub number: u16,
+    pub state: String,
+    pub data: Vec<PortNotes>,
+    #[serde(default)]
+    pub cves: Vec<CveMatch>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Clone,Serialize,Deserialize,Type)]
+pub(crate) enum PortNotes {
+    NmapScan(Vec<String>),
+    Credentials {
+        #[serde(default)]
+        id: String,
+        name: Option<String>,
+        hash: Option<String>,
+        password: Option<String>,
+        // Who recorded them; unset for credentials added before attribution
+        #[serde(default)]
+        author: Option<String>
+    },
+    PentestNote {
+        #[serde(default)]
+        id: String,
+        stage: String,  // e.g., "Enumeration", "Exploitation", "Post-Exploitation"
+        content: String,
+        timestamp: String,  // creation time
+        #[serde(default)]
+        author: String,
+        #[serde(default)]
+        updated_at: String,
+        #[serde(default)]
+        revisions: Vec<NoteRevision>,
+        #[serde(default)]
+        tags: Vec<String>
+    },
+    AiAnalysis {
+        #[serde(default)]
+        id: String,
+        model: Option<String>,
+        prompt: Option<String>,
+        content: String,
+        timestamp: String
+    },
+    ScriptOutput {
+        #[serde(default)]
+        id: String,
+        script_id: String,
+        output: Vec<String>
+    },
+    Screenshot {
+        #[serde(default)]
+        id: String,
+        path: String,
+        caption: Option<String>,
+        timestamp: String
+    },
+    CommandOutput {
+        #[serde(default)]
+        id: String,
+        command: String,
+        output: String,
+        exit_code: Option<i32>,
+        timestamp: String
+    },
+    FindingRef {
+        #[serde(default)]
+        id: String,
+        finding_id: u32
+    },
+    None,
+}
+
+// Free-form note attached to a whole machine or workspace rather than a port
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct Note {
+    #[serde(default)]
+    pub id: String,
+    pub stage: String,
+    pub content: String,
+    pub timestamp: String,  // creation time
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub updated_at: String,
+    #[serde(default)]
+    pub revisions: Vec<NoteRevision>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Machine {
+    pub id: u32,
+    pub hostname: String,
+    pub icon: String,
+    pub ip: String,
+    pub ports: Vec<Port>,
+    #[serde(default)]
+    pub notes: Vec<Note>,
+    #[serde(default)]
+    pub last_scanned: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub status: AccessLevel,
+    #[serde(default)]
+    pub status_history: Vec<StatusChange>,
+    #[serde(default)]
+    pub fingerprint: Option<OsFingerprint>,
+    // Icon chosen by the user; `icon` follows the fingerprint when unset
+    #[serde(default)]
+    pub icon_override: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct JsMachine {
+    pub id: u32,
+    pub hostname: String,
+    pub icon: String,
+    pub ip: String,
+    pub tags: Vec<String>,
+    pub status: AccessLevel,
+    pub fingerprint: Option<OsFingerprint>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct JsPort {
+    pub service: String,
+    pub number: u16,
+    pub protocol: String,
+    pub state: String,
+    pub application: String,
+    pub data: Vec<PortNotes>,  // Add details field to JsPort
+    pub cves: Vec<CveMatch>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct JsWorkspace {
+    pub name: String,
+    pub id: u32,
+    pub ip_range: String,
+    pub tags: Vec<String>,
+    pub redaction: RedactionPolicy,
+}
+
+impl From<&Machine> for JsMachine {
+    fn from(machine: &Machine) -> Self {
+        JsMachine {
+            id: machine.id,
+            hostname: machine.hostname.clone(),
+            icon: machine.icon.clone(),
+            ip: machine.ip.clone(),
+            tags: machine.tags.clone(),
+            status: machine.status,
+            fingerprint: machine.fingerprint.clone(),
+        }
+    }
+}
+
+impl From<&Port> for JsPort {
+    fn from(port: &Port) -> Self {
+        JsPort {
+            service: port.service.clone(),
+            number: port.number,
+            protocol: port.protocol.clone(),
+            state: port.state.clone(),
+            application: port.application.clone(),
+            data: port.data.clone(),
+            cves: port.cves.clone(),
+            tags: port.tags.clone(),
+        }
+    }
+}
+
+impl From<&Workspace> for JsWorkspace {
+    fn from(workspace: &Workspace) -> Self {
+        JsWorkspace {
+            id: workspace.id,
+            name: workspace.name.clone(),
+            ip_range: workspace.ip_range.clone(),
+            tags: workspace.tags.clone(),
+            redaction: workspace.redaction,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Workspace {
+    pub name: String,
+    pub id: u32,
+    pub data: Vec<Machine>,
+    pub ip_range: String,
+    #[serde(default)]
+    pub findings: Vec<Finding>,
+    #[serde(default)]
+    pub notes: Vec<Note>,
+    #[serde(default)]
+    pub last_discovery: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // How much of the recorded credentials AI prompts may include
+    #[serde(default)]
+    pub redaction: RedactionPolicy,
+    // Highest machine ID handed out so far, including deleted machines
+    #[serde(default)]
+    pub last_machine_id: u32,
+    // The same for findings
+    #[serde(default)]
+    pub last_finding_id: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Database {
+    pub name: String,
+    pub data: Vec<Workspace>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    // Highest workspace ID handed out so far, including deleted workspaces
+    #[serde(default)]
+    pub last_workspace_id: u32,
+}
+
+#[derive(Serialize, Deserialize, Type)]
+pub(crate) struct ChatContext {
+    pub type_: String,
+    pub workspace_id: u32,
+    pub machine_id: Option<u32>,
+    pub port_number: Option<u16>,
+}
+
+
+impl Default for Database {
+    fn default() -> Self {
+        Database {
+            name: "Default".to_string(),
+            data: vec![],
+            tags: vec![],
+            last_workspace_id: 0,
+        }
+    }
+}
+
+impl Database {
+    // Never hands out an ID again, so nothing left behind by a deleted workspace (its audit
+    // log, sync links) gets attached to a new one
+    pub(crate) fn next_workspace_id(&mut self) -> u32 {
+        let highest = self.data.iter().map(|workspace| workspace.id).max().unwrap_or(0);
+        self.last_workspace_id = self.last_workspace_id.max(highest) + 1;
+        self.last_workspace_id
+    }
+
+    pub(crate) fn workspace(&self, workspace_id: u32) -> AppResult<&Workspace> {
+        self.data.iter().find(|workspace| workspace.id == workspace_id)
+            .ok_or_else(|| AppError::not_found(Entity::Workspace, workspace_id))
+    }
+
+    pub(crate) fn workspace_mut(&mut self, workspace_id: u32) -> AppResult<&mut Workspace> {
+        self.data.iter_mut().find(|workspace| workspace.id == workspace_id)
+            .ok_or_else(|| AppError::not_found(Entity::Workspace, workspace_id))
+    }
+
+    // Missing workspaces are reported as a missing machine, like the commands always did
+    pub(crate) fn machine(&self, workspace_id: u32, machine_id: u32) -> AppResult<&Machine> {
+        self.workspace(workspace_id).ok().and_then(|workspace| workspace.machine(machine_id).ok())
+            .ok_or_else(|| AppError::not_found(Entity::Machine, machine_id))
+    }
+
+    pub(crate) fn machine_mut(&mut self, workspace_id: u32, machine_id: u32) -> AppResult<&mut Machine> {
+        self.workspace_mut(workspace_id).ok().and_then(|workspace| workspace.machine_mut(machine_id).ok())
+            .ok_or_else(|| AppError::not_found(Entity::Machine, machine_id))
+    }
+
+    pub(crate) fn port(&self, workspace_id: u32, machine_id: u32, port_number: u16) -> AppResult<&Port> {
+        self.machine(workspace_id, machine_id).ok().and_then(|machine| machine.port(port_number).ok())
+            .ok_or_else(|| AppError::not_found(Entity::Port, port_number))
+    }
+
+    pub(crate) fn port_mut(&mut self, workspace_id: u32, machine_id: u32, port_number: u16) -> AppResult<&mut Port> {
+        self.machine_mut(workspace_id, machine_id).ok().and_then(|machine| machine.port_mut(port_number).ok())
+            .ok_or_else(|| AppError::not_found(Entity::Port, port_number))
+    }
+}
+
+impl Workspace {
+    pub(crate) fn new(id: u32, name: String, ip_range: String) -> Self {
+        Workspace {
+            name,
+            id,
+            data: vec![],
+            ip_range,
+            findings: vec![],
+            notes: vec![],
+            last_discovery: None,
+            tags: vec![],
+            redaction: RedactionPolicy::default(),
+            last_machine_id: 0,
+            last_finding_id: 0,
+        }
+    }
+
+    // Never hands out an ID again, like workspace IDs
+    pub(crate) fn next_machine_id(&mut self) -> u32 {
+        let highest = self.data.iter().map(|machine| machine.id).max().unwrap_or(0);
+        self.last_machine_id = self.last_machine_id.max(highest) + 1;
+        self.last_machine_id
+    }
+
+    pub(crate) fn machine(&self, machine_id: u32) -> AppResult<&Machine> {
+        self.data.iter().find(|machine| machine.id == machine_id)
+            .ok_or_else(|| AppError::not_found(Entity::Machine, machine_id))
+    }
+
+    pub(crate) fn machine_mut(&mut self, machine_id: u32) -> AppResult<&mut Machine> {
+        self.data.iter_mut().find(|machine| machine.id == machine_id)
+            .ok_or_else(|| AppError::not_found(Entity::Machine, machine_id))
+    }
+}
+
+impl Machine {
+    pub(crate) fn new(id: u32, hostname: String, ip: String) -> Self {
+        Machine {
+            id,
+            hostname,
+            icon: fingerprint::DEFAULT_ICON.to_string(),
+            ip,
+            ports: vec![],
+            notes: vec![],
+            last_scanned: None,
+            tags: vec![],
+            status: AccessLevel::default(),
+            status_history: vec![],
+            fingerprint: None,
+            icon_override: None,
+        }
+    }
+
+    pub(crate) fn port(&self, port_number: u16) -> AppResult<&Port> {
+        self.ports.iter().find(|port| port.number == port_number)
+            .ok_or_else(|| AppError::not_found(Entity::Port, port_number))
+    }
+
+    pub(crate) fn port_mut(&mut self, port_number: u16) -> AppResult<&mut Port> {
+        self.ports.iter_mut().find(|port| port.number == port_number)
+            .ok_or_else(|| AppError::not_found(Entity::Port, port_number))
+    }
+}
+
+// RFC 3339 timestamp used for everything the backend stamps
+pub(crate) fn now() -> String {
+    chrono::Local::now().to_rfc3339()
+}
+
+// Name recorded as the author/operator of changes made here
+pub(crate) fn current_operator() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
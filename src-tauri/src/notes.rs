@@ -0,0 +1,400 @@
+use serde::{Serialize, Deserialize};
+use specta::Type;
+use similar::TextDiff;
+use std::collections::HashSet;
+use std::mem;
+
+use crate::error::{AppError, AppResult, Entity};
+use crate::{current_operator, now, Database, Note, Port, PortNotes, Workspace};
+
+// A previous version of a note, kept whenever its stage or content is replaced
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct NoteRevision {
+    pub stage: String,
+    pub content: String,
+    // When this version was written
+    pub timestamp: String,
+}
+
+#[derive(Serialize, Type)]
+pub(crate) struct RevisionView {
+    pub revision: usize,
+    pub stage: String,
+    pub content: String,
+    pub timestamp: String,
+    // Unified diff from this revision to the version that replaced it
+    pub diff: String,
+}
+
+// Mutable view over the editable parts of a workspace, machine or port note
+pub(crate) struct NoteFields<'a> {
+    pub stage: &'a mut String,
+    pub content: &'a mut String,
+    pub updated_at: &'a mut String,
+    pub revisions: &'a mut Vec<NoteRevision>,
+    pub tags: &'a mut Vec<String>,
+}
+
+impl NoteFields<'_> {
+    // Replaces the note's text, keeping the previous version as a revision
+    pub(crate) fn revise(&mut self, stage: String, content: String) {
+        if *self.stage == stage && *self.content == content {
+            return;
+        }
+        self.revisions.push(NoteRevision {
+            stage: mem::replace(self.stage, stage),
+            content: mem::replace(self.content, content),
+            timestamp: mem::replace(self.updated_at, now()),
+        });
+    }
+
+    // Brings back an old version; the version being replaced becomes a revision itself
+    pub(crate) fn restore(&mut self, revision: usize) -> AppResult<()> {
+        let old = self.revisions.get(revision).cloned()
+            .ok_or_else(|| AppError::not_found(Entity::Revision, revision))?;
+        self.revise(old.stage, old.content);
+        Ok(())
+    }
+}
+
+// Read-only view of a note's text and history. Notes without text, such as credentials, have
+// an empty one.
+pub(crate) struct NoteView<'a> {
+    pub content: &'a str,
+    pub revisions: &'a [NoteRevision],
+}
+
+impl NoteView<'_> {
+    pub(crate) fn history(&self) -> Vec<RevisionView> {
+        self.revisions.iter().enumerate().map(|(index, revision)| {
+            let (next, label) = match self.revisions.get(index + 1) {
+                Some(next) => (next.content.as_str(), format!("revision {}", index + 1)),
+                None => (self.content, "current".to_string()),
+            };
+            let diff = TextDiff::from_lines(revision.content.as_str(), next)
+                .unified_diff()
+                .header(&format!("revision {}", index), &label)
+                .to_string();
+            RevisionView {
+                revision: index,
+                stage: revision.stage.clone(),
+                content: revision.content.clone(),
+                timestamp: revision.timestamp.clone(),
+                diff,
+            }
+        }).collect()
+    }
+}
+
+pub(crate) fn note_fields(note: &mut Note) -> NoteFields<'_> {
+    NoteFields {
+        stage: &mut note.stage,
+        content: &mut note.content,
+        updated_at: &mut note.updated_at,
+        revisions: &mut note.revisions,
+        tags: &mut note.tags,
+    }
+}
+
+pub(crate) fn port_note_fields(note: &mut PortNotes) -> Option<NoteFields<'_>> {
+    match note {
+        PortNotes::PentestNote { stage, content, updated_at, revisions, tags, .. } => Some(NoteFields { stage, content, updated_at, revisions, tags }),
+        _ => None,
+    }
+}
+
+// Looks a note up by ID among the workspace, machine and port notes of a workspace
+pub(crate) fn find_in_workspace<'a>(workspace: &'a mut Workspace, note_id: &str) -> Option<NoteFields<'a>> {
+    if let Some(note) = workspace.notes.iter_mut().find(|note| note.id == note_id) {
+        return Some(note_fields(note));
+    }
+    for machine in workspace.data.iter_mut() {
+        if let Some(note) = machine.notes.iter_mut().find(|note| note.id == note_id) {
+            return Some(note_fields(note));
+        }
+        for port in machine.ports.iter_mut() {
+            if let Some(note) = port.data.iter_mut().find(|note| port_note_id(note) == Some(note_id)) {
+                return port_note_fields(note);
+            }
+        }
+    }
+    None
+}
+
+fn port_note_view(note: &PortNotes) -> NoteView<'_> {
+    match note {
+        PortNotes::PentestNote { content, revisions, .. } => NoteView { content, revisions },
+        _ => NoteView { content: "", revisions: &[] },
+    }
+}
+
+// Same lookup as `find_in_workspace` for reading; finds port notes of every kind
+pub(crate) fn view_in_workspace<'a>(workspace: &'a Workspace, note_id: &str) -> Option<NoteView<'a>> {
+    let note_view = |note: &'a Note| NoteView { content: &note.content, revisions: &note.revisions };
+    if let Some(note) = workspace.notes.iter().find(|note| note.id == note_id) {
+        return Some(note_view(note));
+    }
+    for machine in &workspace.data {
+        if let Some(note) = machine.notes.iter().find(|note| note.id == note_id) {
+            return Some(note_view(note));
+        }
+        for port in &machine.ports {
+            if let Some(note) = port.data.iter().find(|note| port_note_id(note) == Some(note_id)) {
+                return Some(port_note_view(note));
+            }
+        }
+    }
+    None
+}
+
+pub(crate) fn new_note_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+// Notes are always credited to the operator of this install; the team server credits synced
+// notes to the teammate who sent them
+pub(crate) fn new_note(stage: String, content: String) -> Note {
+    let timestamp = now();
+    Note {
+        id: new_note_id(),
+        stage,
+        content,
+        author: current_operator(),
+        timestamp: timestamp.clone(),
+        updated_at: timestamp,
+        revisions: vec![],
+        tags: vec![],
+    }
+}
+
+pub(crate) fn new_port_note(stage: String, content: String) -> PortNotes {
+    let note = new_note(stage, content);
+    PortNotes::PentestNote {
+        id: note.id,
+        stage: note.stage,
+        content: note.content,
+        timestamp: note.timestamp,
+        author: note.author,
+        updated_at: note.updated_at,
+        revisions: note.revisions,
+        tags: note.tags,
+    }
+}
+
+pub(crate) fn validate(stage: &str, content: &str) -> AppResult<()> {
+    if stage.trim().is_empty() {
+        return Err(AppError::validation("Note stage cannot be empty"));
+    }
+    if content.trim().is_empty() {
+        return Err(AppError::validation("Note content cannot be empty"));
+    }
+    Ok(())
+}
+
+// Every kind of note has an ID except scan output, which is replaced by the next scan
+// The ID field of a port note, by reference or mutably depending on how `note` is passed
+macro_rules! port_note_id_field {
+    ($note:expr) => {
+        match $note {
+            PortNotes::Credentials { id, .. }
+            | PortNotes::PentestNote { id, .. }
+            | PortNotes::AiAnalysis { id, .. }
+            | PortNotes::ScriptOutput { id, .. }
+            | PortNotes::Screenshot { id, .. }
+            | PortNotes::CommandOutput { id, .. }
+            | PortNotes::FindingRef { id, .. } => Some(id),
+            PortNotes::NmapScan(_) | PortNotes::None => None,
+        }
+    };
+}
+
+pub(crate) fn port_note_id(note: &PortNotes) -> Option<&str> {
+    port_note_id_field!(note).map(String::as_str)
+}
+
+fn port_note_id_mut(note: &mut PortNotes) -> Option<&mut String> {
+    port_note_id_field!(note)
+}
+
+pub(crate) fn position(port: &Port, note_id: &str) -> AppResult<usize> {
+    port.data.iter().position(|note| port_note_id(note) == Some(note_id))
+        .ok_or_else(|| AppError::not_found(Entity::Note, note_id))
+}
+
+pub(crate) fn edit_port_note(port: &mut Port, note_id: &str, new_stage: Option<String>, new_content: Option<String>) -> AppResult<()> {
+    let index = position(port, note_id)?;
+    let mut fields = port_note_fields(&mut port.data[index])
+        .ok_or_else(|| AppError::validation("Only pentest notes can be edited"))?;
+    let stage = new_stage.unwrap_or_else(|| fields.stage.clone());
+    let content = new_content.unwrap_or_else(|| fields.content.clone());
+    validate(&stage, &content)?;
+    fields.revise(stage, content);
+    Ok(())
+}
+
+// Reorders the notes of a port; scan output keeps its slots
+pub(crate) fn reorder_port_notes(port: &mut Port, order: &[String]) -> AppResult<()> {
+    let slots: Vec<usize> = port.data.iter().enumerate()
+        .filter(|(_, note)| port_note_id(note).is_some())
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut current: Vec<&str> = slots.iter().filter_map(|index| port_note_id(&port.data[*index])).collect();
+    let mut requested: Vec<&str> = order.iter().map(String::as_str).collect();
+    current.sort_unstable();
+    requested.sort_unstable();
+    if current != requested {
+        return Err(AppError::validation("Note order must contain every note of the port exactly once"));
+    }
+
+    let mut notes: Vec<PortNotes> = slots.iter().map(|index| port.data[*index].clone()).collect();
+    for (slot, id) in slots.iter().zip(order) {
+        let index = notes.iter().position(|note| port_note_id(note) == Some(id.as_str()))
+            .ok_or_else(|| AppError::not_found(Entity::Note, id))?;
+        port.data[*slot] = notes.remove(index);
+    }
+    Ok(())
+}
+
+// Who wrote a legacy note isn't known, so its author stays empty
+fn fill_metadata(id: &mut String, timestamp: &mut String, updated_at: &mut String) -> bool {
+    let mut changed = false;
+    if id.is_empty() {
+        *id = new_note_id();
+        changed = true;
+    }
+    if timestamp.is_empty() {
+        *timestamp = now();
+        changed = true;
+    }
+    if updated_at.is_empty() {
+        *updated_at = timestamp.clone();
+        changed = true;
+    }
+    changed
+}
+
+// Fills in IDs, and timestamps for pentest notes, where notes don't carry them yet
+fn complete_port_notes(port: &mut Port) -> bool {
+    let mut changed = false;
+    for note in port.data.iter_mut() {
+        if let PortNotes::PentestNote { id, timestamp, updated_at, .. } = note {
+            changed |= fill_metadata(id, timestamp, updated_at);
+        } else if let Some(id) = port_note_id_mut(note).filter(|id| id.is_empty()) {
+            *id = new_note_id();
+            changed = true;
+        }
+    }
+    changed
+}
+
+// Timestamp the server already recorded for an equivalent entry, if any
+fn known_timestamp(old: &[PortNotes], note: &PortNotes) -> Option<String> {
+    old.iter().find_map(|existing| match (existing, note) {
+        (PortNotes::AiAnalysis { content: a, timestamp, .. }, PortNotes::AiAnalysis { content: b, .. }) if a == b => Some(timestamp.clone()),
+        (PortNotes::Screenshot { path: a, timestamp, .. }, PortNotes::Screenshot { path: b, .. }) if a == b => Some(timestamp.clone()),
+        (PortNotes::CommandOutput { command: a, output: c, timestamp, .. }, PortNotes::CommandOutput { command: b, output: d, .. }) if a == b && c == d => Some(timestamp.clone()),
+        _ => None,
+    })
+}
+
+// Takes a note list sent by a client and replaces any client-supplied ids,
+// authors and timestamps with the ones the server recorded (or fresh ones for new notes).
+// Finding references have to point at findings of the workspace, and scan output can be
+// kept or dropped but not written by the client.
+pub(crate) fn merge_client_notes(old: &[PortNotes], new: Vec<PortNotes>, finding_ids: &[u32]) -> AppResult<Vec<PortNotes>> {
+    let mut seen = HashSet::new();
+    new.into_iter().map(|note| {
+        let mut note = match note {
+            PortNotes::PentestNote { id, stage, content, .. } => {
+                let existing = old.iter().find(|existing| !id.is_empty() && port_note_id(existing) == Some(id.as_str()));
+                match existing {
+                    Some(existing) => {
+                        let mut note = existing.clone();
+                        if let Some(mut fields) = port_note_fields(&mut note) {
+                            fields.revise(stage, content);
+                        }
+                        note
+                    }
+                    None => new_port_note(stage, content),
+                }
+            }
+            PortNotes::AiAnalysis { id, model, prompt, content, .. } => {
+                let note = PortNotes::AiAnalysis { id, model, prompt, content, timestamp: String::new() };
+                stamp(old, note)
+            }
+            PortNotes::Screenshot { id, path, caption, .. } => {
+                let note = PortNotes::Screenshot { id, path, caption, timestamp: String::new() };
+                stamp(old, note)
+            }
+            PortNotes::CommandOutput { id, command, output, exit_code, .. } => {
+                let note = PortNotes::CommandOutput { id, command, output, exit_code, timestamp: String::new() };
+                stamp(old, note)
+            }
+            // New credentials are attributed to the current operator, known ones keep their author
+            PortNotes::Credentials { id, name, hash, password, .. } => {
+                let known = old.iter().find_map(|existing| match existing {
+                    PortNotes::Credentials { id: i, author, .. } if !id.is_empty() && *i == id => Some(author.clone()),
+                    PortNotes::Credentials { name: n, hash: h, password: p, author, .. } if *n == name && *h == hash && *p == password => Some(author.clone()),
+                    _ => None,
+                });
+                let author = known.unwrap_or_else(|| Some(current_operator()));
+                PortNotes::Credentials { id, name, hash, password, author }
+            }
+            PortNotes::FindingRef { id, finding_id } => {
+                if !finding_ids.contains(&finding_id) {
+                    return Err(AppError::not_found(Entity::Finding, finding_id));
+                }
+                PortNotes::FindingRef { id, finding_id }
+            }
+            PortNotes::ScriptOutput { id, .. } => old.iter()
+                .find(|existing| matches!(existing, PortNotes::ScriptOutput { id: known, .. } if !id.is_empty() && *known == id))
+                .cloned()
+                .ok_or_else(|| AppError::validation("Script output can only come from scans"))?,
+            PortNotes::NmapScan(lines) => {
+                if !old.iter().any(|existing| matches!(existing, PortNotes::NmapScan(known) if *known == lines)) {
+                    return Err(AppError::validation("Scan output can only come from scans"));
+                }
+                PortNotes::NmapScan(lines)
+            }
+            PortNotes::None => PortNotes::None,
+        };
+        // Known IDs are kept; anything the server didn't hand out is replaced
+        if let Some(id) = port_note_id_mut(&mut note) {
+            if id.is_empty() || !old.iter().any(|existing| port_note_id(existing) == Some(id.as_str())) {
+                *id = new_note_id();
+            }
+            if !seen.insert(id.clone()) {
+                return Err(AppError::validation(format!("Note {} appears more than once", id)));
+            }
+        }
+        Ok(note)
+    }).collect()
+}
+
+fn stamp(old: &[PortNotes], mut note: PortNotes) -> PortNotes {
+    let recorded = known_timestamp(old, &note).unwrap_or_else(now);
+    if let PortNotes::AiAnalysis { timestamp, .. } | PortNotes::Screenshot { timestamp, .. } | PortNotes::CommandOutput { timestamp, .. } = &mut note {
+        *timestamp = recorded;
+    }
+    note
+}
+
+// Gives notes written by older versions their metadata; returns true if anything changed
+pub(crate) fn migrate(database: &mut Database) -> bool {
+    let mut changed = false;
+    for workspace in database.data.iter_mut() {
+        for note in workspace.notes.iter_mut() {
+            changed |= fill_metadata(&mut note.id, &mut note.timestamp, &mut note.updated_at);
+        }
+        for machine in workspace.data.iter_mut() {
+            for note in machine.notes.iter_mut() {
+                changed |= fill_metadata(&mut note.id, &mut note.timestamp, &mut note.updated_at);
+            }
+            for port in machine.ports.iter_mut() {
+                changed |= complete_port_notes(port);
+            }
+        }
+    }
+    changed
+}
@@ -0,0 +1,233 @@
+use serde::{Serialize, Deserialize};
+use specta::Type;
+use std::cmp::Ordering;
+use std::net::IpAddr;
+
+use crate::{tags, Machine, Port, Workspace};
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize, Type)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PortSort {
+    #[default]
+    Ip,
+    Hostname,
+    Port,
+    Service,
+    Application,
+}
+
+// Every field is optional; an empty query lists every port in the workspace
+#[derive(Clone, Default, Serialize, Deserialize, Type)]
+pub(crate) struct PortQuery {
+    #[serde(default)]
+    pub port: Option<u16>,
+    // Case-insensitive substring matches
+    #[serde(default)]
+    pub service: Option<String>,
+    #[serde(default)]
+    pub application: Option<String>,
+    // Hostname substring, whole IP or CIDR range
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default)]
+    pub protocol: Option<String>,
+    #[serde(default)]
+    pub state: Option<String>,
+    // Matches tags on the port or on its machine
+    #[serde(default)]
+    pub tag: Option<String>,
+    // Only ports with at least one candidate CVE
+    #[serde(default)]
+    pub has_cves: Option<bool>,
+    // Return machines that were never scanned instead of ports
+    #[serde(default)]
+    pub unscanned: bool,
+    #[serde(default)]
+    pub sort: PortSort,
+    #[serde(default)]
+    pub descending: bool,
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Type)]
+pub(crate) struct PortSummary {
+    pub number: u16,
+    pub protocol: String,
+    pub service: String,
+    pub application: String,
+    pub state: String,
+    pub note_count: usize,
+    pub cve_count: usize,
+}
+
+#[derive(Serialize, Type)]
+pub(crate) struct PortRow {
+    pub machine_id: u32,
+    pub hostname: String,
+    pub ip: String,
+    pub last_scanned: Option<String>,
+    pub tags: Vec<String>,
+    // None for rows of an `unscanned` query
+    pub port: Option<PortSummary>,
+}
+
+#[derive(Serialize, Type)]
+pub(crate) struct PortPage {
+    pub total: usize,
+    pub offset: usize,
+    pub rows: Vec<PortRow>,
+}
+
+fn contains(value: &str, needle: &Option<String>) -> bool {
+    needle.as_ref().is_none_or(|needle| value.to_lowercase().contains(&needle.to_lowercase()))
+}
+
+// An exact address or a CIDR range; addresses that don't parse must match as written
+pub(crate) fn ip_matches(ip: &str, filter: &str) -> bool {
+    let filter = filter.trim();
+    let Ok(address) = ip.parse::<IpAddr>() else {
+        return ip.eq_ignore_ascii_case(filter);
+    };
+    let (network, prefix) = match filter.split_once('/') {
+        Some((network, prefix)) => match (network.parse::<IpAddr>(), prefix.parse::<u32>()) {
+            (Ok(network), Ok(prefix)) => (network, Some(prefix)),
+            _ => return false,
+        },
+        None => match filter.parse::<IpAddr>() {
+            Ok(network) => (network, None),
+            Err(_) => return false,
+        },
+    };
+    match (address, network) {
+        (IpAddr::V4(address), IpAddr::V4(network)) => {
+            let prefix = prefix.unwrap_or(32);
+            let mask = u32::MAX.checked_shl(32 - prefix.min(32)).unwrap_or(0);
+            prefix <= 32 && u32::from(address) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(address), IpAddr::V6(network)) => {
+            let prefix = prefix.unwrap_or(128);
+            let mask = u128::MAX.checked_shl(128 - prefix.min(128)).unwrap_or(0);
+            prefix <= 128 && u128::from(address) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+fn matches_host(machine: &Machine, query: &PortQuery) -> bool {
+    query.host.as_ref().is_none_or(|host| {
+        machine.hostname.to_lowercase().contains(&host.to_lowercase()) || ip_matches(&machine.ip, host)
+    })
+}
+
+fn matches_port(port: &Port, query: &PortQuery) -> bool {
+    query.port.is_none_or(|number| port.number == number)
+        && contains(&port.service, &query.service)
+        && contains(&port.application, &query.application)
+        && query.protocol.as_ref().is_none_or(|protocol| port.protocol.eq_ignore_ascii_case(protocol))
+        && query.state.as_ref().is_none_or(|state| port.state.eq_ignore_ascii_case(state))
+        && query.has_cves.is_none_or(|has_cves| port.cves.is_empty() != has_cves)
+}
+
+// Machines scanned before scan timestamps existed still count as scanned if they have ports
+fn never_scanned(machine: &Machine) -> bool {
+    machine.last_scanned.is_none() && machine.ports.is_empty()
+}
+
+fn row(machine: &Machine, port: Option<&Port>) -> PortRow {
+    PortRow {
+        machine_id: machine.id,
+        hostname: machine.hostname.clone(),
+        ip: machine.ip.clone(),
+        last_scanned: machine.last_scanned.clone(),
+        tags: machine.tags.iter().chain(port.map(|port| &port.tags).into_iter().flatten()).cloned().collect(),
+        port: port.map(|port| PortSummary {
+            number: port.number,
+            protocol: port.protocol.clone(),
+            service: port.service.clone(),
+            application: port.application.clone(),
+            state: port.state.clone(),
+            note_count: port.data.len(),
+            cve_count: port.cves.len(),
+        }),
+    }
+}
+
+fn compare_ip(a: &str, b: &str) -> Ordering {
+    match (a.parse::<IpAddr>(), b.parse::<IpAddr>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+fn compare(a: &PortRow, b: &PortRow, sort: PortSort) -> Ordering {
+    let number = |row: &PortRow| row.port.as_ref().map(|port| port.number);
+    let text = |row: &PortRow, field: fn(&PortSummary) -> &str| row.port.as_ref().map(|port| field(port).to_lowercase());
+    let primary = match sort {
+        PortSort::Ip => compare_ip(&a.ip, &b.ip),
+        PortSort::Hostname => a.hostname.to_lowercase().cmp(&b.hostname.to_lowercase()),
+        PortSort::Port => number(a).cmp(&number(b)),
+        PortSort::Service => text(a, |port| &port.service).cmp(&text(b, |port| &port.service)),
+        PortSort::Application => text(a, |port| &port.application).cmp(&text(b, |port| &port.application)),
+    };
+    // Keep the order stable between pages
+    primary
+        .then_with(|| compare_ip(&a.ip, &b.ip))
+        .then_with(|| number(a).cmp(&number(b)))
+}
+
+pub(crate) fn run(workspace: &Workspace, query: &PortQuery) -> PortPage {
+    let machines = workspace.data.iter().filter(|machine| matches_host(machine, query));
+    let mut rows: Vec<PortRow> = if query.unscanned {
+        machines
+            .filter(|machine| never_scanned(machine) && tags::has_tag(&machine.tags, query.tag.as_deref()))
+            .map(|machine| row(machine, None)).collect()
+    } else {
+        machines
+            .flat_map(|machine| machine.ports.iter().map(move |port| (machine, port)))
+            .filter(|(_, port)| matches_port(port, query))
+            .filter(|(machine, port)| {
+                tags::has_tag(&machine.tags, query.tag.as_deref()) || tags::has_tag(&port.tags, query.tag.as_deref())
+            })
+            .map(|(machine, port)| row(machine, Some(port)))
+            .collect()
+    };
+
+    rows.sort_by(|a, b| compare(a, b, query.sort));
+    if query.descending {
+        rows.reverse();
+    }
+
+    let total = rows.len();
+    let rows = rows.into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
+    PortPage { total, offset: query.offset, rows }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hosts_match_whole_ips_or_ranges() {
+        assert!(ip_matches("10.0.0.1", "10.0.0.1"));
+        assert!(!ip_matches("10.0.0.10", "10.0.0.1"));
+        assert!(!ip_matches("10.0.0.1", "10.0.0"));
+        assert!(ip_matches("10.0.0.10", "10.0.0.0/24"));
+        assert!(!ip_matches("10.0.1.10", "10.0.0.0/24"));
+        assert!(ip_matches("10.0.1.10", "0.0.0.0/0"));
+        assert!(!ip_matches("10.0.0.1", "10.0.0.0/33"));
+        assert!(ip_matches("fe80::1", "FE80::/64"));
+        assert!(!ip_matches("fe80::1", "10.0.0.0/8"));
+
+        let machine = |ip: &str| Machine::new(1, "web".to_string(), ip.to_string());
+        let query = PortQuery { host: Some("10.0.0.1".to_string()), ..Default::default() };
+        assert!(matches_host(&machine("10.0.0.1"), &query));
+        assert!(!matches_host(&machine("10.0.0.10"), &query));
+        assert!(matches_host(&machine("10.0.0.10"), &PortQuery { host: Some("WEB".to_string()), ..Default::default() }));
+    }
+}
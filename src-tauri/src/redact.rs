@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::llm::Prompt;
+use crate::{PortNotes, Workspace};
+
+// What the AI may see of the passwords and hashes recorded in a workspace
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RedactionPolicy {
+    // Replaced with a placeholder
+    Never,
+    // Only the first and last characters, e.g. to recognise a hash type
+    Masked,
+    // In full to a model on this machine, not at all to anything else
+    #[default]
+    LocalOnly,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Remove,
+    Mask,
+    Keep,
+}
+
+// A password or hash from the workspace's credentials
+struct Secret {
+    value: String,
+    // Hex hashes are written in either case by different tools
+    ignore_case: bool,
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Secret {
+    // Whether `rest` starts with the secret as a word of its own rather than part of a longer one
+    fn matches(&self, before: Option<char>, rest: &str) -> bool {
+        let Some(candidate) = rest.get(..self.value.len()) else {
+            return false;
+        };
+        let equal = match self.ignore_case {
+            true => candidate.eq_ignore_ascii_case(&self.value),
+            false => candidate == self.value,
+        };
+        let after = rest[self.value.len()..].chars().next();
+        equal
+            && !(self.value.starts_with(is_word) && before.is_some_and(is_word))
+            && !(self.value.ends_with(is_word) && after.is_some_and(is_word))
+    }
+}
+
+// Every password and hash of the workspace's credentials
+fn secrets(workspace: &Workspace) -> Vec<Secret> {
+    let mut secrets: Vec<Secret> = workspace.data.iter()
+        .flat_map(|machine| machine.ports.iter())
+        .flat_map(|port| port.data.iter())
+        .flat_map(|note| match note {
+            PortNotes::Credentials { hash, password, .. } => vec![
+                hash.as_ref().map(|hash| Secret {
+                    value: hash.clone(),
+                    ignore_case: hash.chars().all(|c| c.is_ascii_hexdigit() || c == ':'),
+                }),
+                password.as_ref().map(|password| Secret { value: password.clone(), ignore_case: false }),
+            ],
+            _ => vec![],
+        })
+        .flatten()
+        .filter(|secret| !secret.value.is_empty())
+        .collect();
+    // A hash that is also used as a password is still found in any case
+    secrets.sort_by(|a, b| b.value.len().cmp(&a.value.len())
+        .then_with(|| a.value.cmp(&b.value))
+        .then_with(|| b.ignore_case.cmp(&a.ignore_case)));
+    secrets.dedup_by(|later, earlier| later.value == earlier.value);
+    secrets
+}
+
+// Applies a workspace's policy to everything sent to a model
+pub(crate) struct Redactor {
+    mode: Mode,
+    // Longest first, so a secret containing another is replaced whole
+    secrets: Vec<Secret>,
+}
+
+impl Redactor {
+    pub(crate) fn new(workspace: &Workspace, local_model: bool) -> Self {
+        let mode = match (workspace.redaction, local_model) {
+            (RedactionPolicy::Never, _) | (RedactionPolicy::LocalOnly, false) => Mode::Remove,
+            (RedactionPolicy::Masked, _) => Mode::Mask,
+            (RedactionPolicy::LocalOnly, true) => Mode::Keep,
+        };
+        Redactor { mode, secrets: secrets(workspace) }
+    }
+
+    // Leaves every secret out whatever the policy, e.g. for reports without credentials
+    pub(crate) fn removing(workspace: &Workspace) -> Self {
+        Redactor { mode: Mode::Remove, secrets: secrets(workspace) }
+    }
+
+    // Sends everything as it is, for workspaces that can't be found
+    pub(crate) fn none() -> Self {
+        Redactor { mode: Mode::Keep, secrets: vec![] }
+    }
+
+    // How one password or hash is shown
+    pub(crate) fn secret(&self, secret: &str) -> String {
+        match self.mode {
+            Mode::Keep => secret.to_string(),
+            Mode::Remove => "[redacted]".to_string(),
+            Mode::Mask => {
+                let chars: Vec<char> = secret.chars().collect();
+                if chars.len() < 8 {
+                    return "*".repeat(chars.len());
+                }
+                let (start, end) = (&chars[..2], &chars[chars.len() - 2..]);
+                format!("{}{}{}", start.iter().collect::<String>(), "*".repeat(chars.len() - 4), end.iter().collect::<String>())
+            }
+        }
+    }
+
+    // Secrets can also turn up in notes, command output and earlier answers
+    pub(crate) fn text(&self, text: &str) -> String {
+        if self.mode == Mode::Keep {
+            return text.to_string();
+        }
+        let mut redacted = String::with_capacity(text.len());
+        let (mut before, mut rest) = (None, text);
+        while let Some(c) = rest.chars().next() {
+            let length = match self.secrets.iter().find(|secret| secret.matches(before, rest)) {
+                Some(secret) => {
+                    redacted.push_str(&self.secret(&rest[..secret.value.len()]));
+                    secret.value.len()
+                }
+                None => {
+                    redacted.push(c);
+                    c.len_utf8()
+                }
+            };
+            before = rest[..length].chars().next_back();
+            rest = &rest[length..];
+        }
+        redacted
+    }
+
+    pub(crate) fn prompt(&self, prompt: &mut Prompt) {
+        prompt.context = self.text(&prompt.context);
+        prompt.question = self.text(&prompt.question);
+        for message in prompt.history.iter_mut() {
+            message.content = self.text(&message.content);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::test_port;
+    use crate::Machine;
+
+    fn workspace(policy: RedactionPolicy) -> Workspace {
+        let mut workspace = Workspace::new(1, "Lab".to_string(), "10.0.0.0/24".to_string());
+        let mut machine = Machine::new(1, "dc01".to_string(), "10.0.0.5".to_string());
+        machine.ports.push(test_port(445, "smb", "", vec![PortNotes::Credentials {
+            id: String::new(),
+            name: Some("svc_backup".to_string()),
+            hash: Some("aad3b435b51404ee".to_string()),
+            password: Some("Winter2024".to_string()),
+            author: None,
+        }]));
+        workspace.data.push(machine);
+        workspace.redaction = policy;
+        workspace
+    }
+
+    #[test]
+    fn policies_decide_what_the_model_sees() {
+        let note = "Logged in as svc_backup with Winter2024";
+        let never = Redactor::new(&workspace(RedactionPolicy::Never), true);
+        assert_eq!(never.text(note), "Logged in as svc_backup with [redacted]");
+
+        let masked = Redactor::new(&workspace(RedactionPolicy::Masked), false);
+        assert_eq!(masked.secret("Winter2024"), "Wi******24");
+        assert_eq!(masked.secret("abc"), "***");
+        assert_eq!(masked.text("hash aad3b435b51404ee"), "hash aa************ee");
+
+        let local_only = workspace(RedactionPolicy::LocalOnly);
+        assert_eq!(Redactor::new(&local_only, true).text(note), note);
+        assert_eq!(Redactor::new(&local_only, false).secret("Winter2024"), "[redacted]");
+        assert_eq!(RedactionPolicy::default(), RedactionPolicy::LocalOnly);
+    }
+
+    #[test]
+    fn short_secrets_are_scrubbed_too() {
+        let mut workspace = workspace(RedactionPolicy::LocalOnly);
+        workspace.data[0].ports[0].data.push(PortNotes::Credentials {
+            id: String::new(),
+            name: Some("guest".to_string()),
+            hash: None,
+            password: Some("x9".to_string()),
+            author: None,
+        });
+        let redactor = Redactor::new(&workspace, false);
+        assert_eq!(redactor.text("guest:x9 works"), "guest:[redacted] works");
+    }
+
+    #[test]
+    fn secrets_match_whole_words_and_hashes_in_any_case() {
+        let mut workspace = workspace(RedactionPolicy::Never);
+        workspace.data[0].ports[0].data.push(PortNotes::Credentials {
+            id: String::new(),
+            name: Some("admin".to_string()),
+            hash: None,
+            password: Some("admin".to_string()),
+            author: None,
+        });
+        let redactor = Redactor::new(&workspace, false);
+        assert_eq!(redactor.text("admin/admin on /administrator"), "[redacted]/[redacted] on /administrator");
+        assert_eq!(redactor.text("NTLM AAD3B435B51404EE"), "NTLM [redacted]");
+        // Passwords are case-sensitive
+        assert_eq!(redactor.text("winter2024 or Winter2024"), "winter2024 or [redacted]");
+    }
+}
@@ -0,0 +1,816 @@
+use serde::{Serialize, Deserialize};
+use specta::Type;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{AppError, AppResult};
+use crate::findings::{Finding, FindingStatus, Severity};
+use crate::redact::Redactor;
+use crate::{Machine, Note, Port, PortNotes, Workspace};
+
+// Known stages in the order they appear in a report; anything else goes last
+const STAGE_ORDER: [&str; 9] = [
+    "Information Gathering",
+    "Enumeration",
+    "Vulnerability Analysis",
+    "Exploitation",
+    "Post-Exploitation",
+    "Privilege Escalation",
+    "Lateral Movement",
+    "Persistence",
+    "General Notes",
+];
+
+const REDACTED: &str = "[REDACTED]";
+
+const DEFAULT_MARKDOWN_TEMPLATE: &str = "# Penetration Test Report: {{workspace_name}}
+
+- **Scope:** {{ip_range}}
+- **Generated:** {{generated_at}}
+
+## Executive Summary
+
+{{executive_summary}}
+
+## Findings
+
+{{findings}}
+
+## Hosts
+
+{{hosts}}
+";
+
+const DEFAULT_HTML_TEMPLATE: &str = "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<title>Penetration Test Report: {{workspace_name}}</title>
+<style>
+body { font-family: -apple-system, \"Segoe UI\", Helvetica, Arial, sans-serif; max-width: 960px; margin: 2rem auto; padding: 0 1rem; color: #1f2328; }
+h1 { border-bottom: 2px solid #d0d7de; padding-bottom: .3rem; }
+h2 { border-bottom: 1px solid #d0d7de; padding-bottom: .2rem; margin-top: 2rem; }
+table { border-collapse: collapse; width: 100%; margin: 1rem 0; }
+th, td { border: 1px solid #d0d7de; padding: .4rem .6rem; text-align: left; }
+th { background: #f6f8fa; }
+pre { background: #f6f8fa; padding: .8rem; overflow-x: auto; white-space: pre-wrap; }
+.meta { color: #57606a; }
+</style>
+</head>
+<body>
+<h1>Penetration Test Report: {{workspace_name}}</h1>
+<p class=\"meta\">Scope: {{ip_range}}<br>Generated: {{generated_at}}</p>
+<h2>Executive Summary</h2>
+{{executive_summary}}
+<h2>Findings</h2>
+{{findings}}
+<h2>Hosts</h2>
+{{hosts}}
+</body>
+</html>
+";
+
+#[derive(Clone, Copy, Serialize, Deserialize, Type)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    fn template_file(&self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "report.md",
+            ReportFormat::Html => "report.html",
+        }
+    }
+
+    fn default_template(&self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => DEFAULT_MARKDOWN_TEMPLATE,
+            ReportFormat::Html => DEFAULT_HTML_TEMPLATE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub(crate) struct ReportOptions {
+    #[serde(default)]
+    pub include_credentials: bool,
+}
+
+fn templates_dir(dir: &Path) -> AppResult<PathBuf> {
+    let path = dir.join("templates");
+    fs::create_dir_all(&path)
+        .map_err(|e| AppError::storage("Failed to create templates directory", e))?;
+    Ok(path)
+}
+
+// Returns the user's template for the format, falling back to the built-in one
+pub(crate) fn load_template(dir: &Path, format: ReportFormat) -> AppResult<String> {
+    let path = templates_dir(dir)?.join(format.template_file());
+    if path.exists() {
+        fs::read_to_string(&path).map_err(|e| AppError::storage("Failed to read report template", e))
+    } else {
+        Ok(format.default_template().to_string())
+    }
+}
+
+pub(crate) fn save_template(dir: &Path, format: ReportFormat, template: &str) -> AppResult<()> {
+    let path = templates_dir(dir)?.join(format.template_file());
+    fs::write(path, template).map_err(|e| AppError::storage("Failed to write report template", e))
+}
+
+pub(crate) fn reset_template(dir: &Path, format: ReportFormat) -> AppResult<()> {
+    let path = templates_dir(dir)?.join(format.template_file());
+    if path.exists() {
+        fs::remove_file(path).map_err(|e| AppError::storage("Failed to remove report template", e))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn render(workspace: &Workspace, template: &str, format: ReportFormat, options: ReportOptions) -> String {
+    let scrubbed;
+    let workspace = match options.include_credentials {
+        true => workspace,
+        false => {
+            scrubbed = scrub(workspace);
+            &scrubbed
+        }
+    };
+    let generated_at = chrono::Local::now().format("%Y-%m-%d %H:%M").to_string();
+
+    let (summary, findings, hosts) = match format {
+        ReportFormat::Markdown => (markdown_summary(workspace), markdown_findings(workspace), markdown_hosts(workspace, options)),
+        ReportFormat::Html => (html_summary(workspace), html_findings(workspace), html_hosts(workspace, options)),
+    };
+    let (name, ip_range) = match format {
+        ReportFormat::Markdown => (workspace.name.clone(), workspace.ip_range.clone()),
+        ReportFormat::Html => (escape_html(&workspace.name), escape_html(&workspace.ip_range)),
+    };
+
+    fill(template, &[
+        ("workspace_name", &name),
+        ("ip_range", &ip_range),
+        ("generated_at", &generated_at),
+        ("executive_summary", &summary),
+        ("findings", &findings),
+        ("hosts", &hosts),
+    ])
+}
+
+// Replaces each `{{key}}` in one pass, so placeholders in the data itself are left as written.
+// Unknown placeholders stay in the output.
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        match values.iter().find(|(key, _)| *key == &after[..end]) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+struct Summary {
+    hosts: usize,
+    hosts_with_ports: usize,
+    open_ports: usize,
+    services: Vec<(String, usize)>,
+    notes_by_stage: Vec<(String, usize)>,
+    credentials: usize,
+}
+
+fn summarize(workspace: &Workspace) -> Summary {
+    let mut services: BTreeMap<String, usize> = BTreeMap::new();
+    let mut stages: BTreeMap<String, usize> = BTreeMap::new();
+    let mut credentials = 0;
+    let mut open_ports = 0;
+
+    for machine in &workspace.data {
+        for port in &machine.ports {
+            if port.state == "open" {
+                open_ports += 1;
+            }
+            *services.entry(port.service.clone()).or_default() += 1;
+            for note in &port.data {
+                match note {
+                    PortNotes::PentestNote { stage, .. } => *stages.entry(stage.clone()).or_default() += 1,
+                    PortNotes::Credentials { .. } => credentials += 1,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let mut services: Vec<(String, usize)> = services.into_iter().collect();
+    services.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    services.truncate(10);
+
+    let mut notes_by_stage: Vec<(String, usize)> = stages.into_iter().collect();
+    notes_by_stage.sort_by_key(|(stage, _)| stage_rank(stage));
+
+    Summary {
+        hosts: workspace.data.len(),
+        hosts_with_ports: workspace.data.iter().filter(|m| !m.ports.is_empty()).count(),
+        open_ports,
+        services,
+        notes_by_stage,
+        credentials,
+    }
+}
+
+fn stage_rank(stage: &str) -> usize {
+    STAGE_ORDER.iter().position(|s| *s == stage).unwrap_or(STAGE_ORDER.len())
+}
+
+type StageGroups<'a> = Vec<(String, Vec<(&'a String, &'a String)>)>;
+
+// (stage, timestamp, content) triples grouped by stage, in report order
+fn group_by_stage<'a>(notes: impl Iterator<Item = (&'a String, &'a String, &'a String)>) -> StageGroups<'a> {
+    let mut groups: StageGroups<'a> = vec![];
+    for (stage, timestamp, content) in notes {
+        match groups.iter_mut().find(|(s, _)| s == stage) {
+            Some((_, notes)) => notes.push((timestamp, content)),
+            None => groups.push((stage.clone(), vec![(timestamp, content)])),
+        }
+    }
+    groups.sort_by_key(|(stage, _)| stage_rank(stage));
+    groups
+}
+
+fn notes_by_stage(port: &Port) -> StageGroups<'_> {
+    group_by_stage(port.data.iter().filter_map(|note| match note {
+        PortNotes::PentestNote { stage, content, timestamp, .. } => Some((stage, timestamp, content)),
+        _ => None,
+    }))
+}
+
+fn general_notes_by_stage(notes: &[Note]) -> StageGroups<'_> {
+    group_by_stage(notes.iter().map(|note| (&note.stage, &note.timestamp, &note.content)))
+}
+
+fn markdown_stage_groups(groups: StageGroups) -> String {
+    let mut out = String::new();
+    for (stage, notes) in groups {
+        out.push_str(&format!("**{}**\n\n", stage));
+        for (timestamp, content) in notes {
+            out.push_str(&format!("- _{}_: {}\n", timestamp, content.replace('\n', "\n  ")));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn html_stage_groups(groups: StageGroups) -> String {
+    let mut out = String::new();
+    for (stage, notes) in groups {
+        out.push_str(&format!("<p><strong>{}</strong></p>\n<ul>\n", escape_html(&stage)));
+        for (timestamp, content) in notes {
+            out.push_str(&format!(
+                "<li><em>{}</em>: {}</li>\n",
+                escape_html(timestamp),
+                escape_html(content).replace('\n', "<br>")
+            ));
+        }
+        out.push_str("</ul>\n");
+    }
+    out
+}
+
+// Passwords and hashes also turn up in notes, command output and AI answers
+fn scrub(workspace: &Workspace) -> Workspace {
+    let redactor = Redactor::removing(workspace);
+    let mut workspace = workspace.clone();
+    for note in workspace.notes.iter_mut().chain(workspace.data.iter_mut().flat_map(|machine| machine.notes.iter_mut())) {
+        note.content = redactor.text(&note.content);
+    }
+    for note in workspace.data.iter_mut().flat_map(|machine| machine.ports.iter_mut()).flat_map(|port| port.data.iter_mut()) {
+        match note {
+            PortNotes::PentestNote { content, .. } | PortNotes::AiAnalysis { content, .. } => *content = redactor.text(content),
+            PortNotes::CommandOutput { command, output, .. } => {
+                *command = redactor.text(command);
+                *output = redactor.text(output);
+            }
+            _ => {}
+        }
+    }
+    workspace
+}
+
+fn credential_value(value: &Option<String>, options: ReportOptions) -> String {
+    match value {
+        Some(_) if !options.include_credentials => REDACTED.to_string(),
+        Some(v) => v.clone(),
+        None => "-".to_string(),
+    }
+}
+
+// Most severe first
+fn sorted_findings(workspace: &Workspace) -> Vec<&Finding> {
+    let mut findings: Vec<&Finding> = workspace.findings.iter().collect();
+    findings.sort_by_key(|finding| (finding.severity, finding.id));
+    findings
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "Critical",
+        Severity::High => "High",
+        Severity::Medium => "Medium",
+        Severity::Low => "Low",
+        Severity::Info => "Informational",
+    }
+}
+
+fn status_name(status: FindingStatus) -> &'static str {
+    match status {
+        FindingStatus::Open => "Open",
+        FindingStatus::Confirmed => "Confirmed",
+        FindingStatus::Remediated => "Remediated",
+        FindingStatus::Accepted => "Risk accepted",
+        FindingStatus::FalsePositive => "False positive",
+    }
+}
+
+fn cvss(finding: &Finding) -> Option<String> {
+    match (finding.cvss_score, &finding.cvss_vector) {
+        (Some(score), Some(vector)) => Some(format!("{:.1} ({})", score, vector)),
+        (Some(score), None) => Some(format!("{:.1}", score)),
+        (None, _) => None,
+    }
+}
+
+// e.g. "dc01 (10.0.0.5) port 445"
+fn affected_assets(workspace: &Workspace, finding: &Finding) -> Vec<String> {
+    finding.affected.iter().map(|asset| {
+        let machine = workspace.data.iter().find(|machine| machine.id == asset.machine_id)
+            .map(|machine| format!("{} ({})", machine.hostname, machine.ip))
+            .unwrap_or_else(|| format!("machine #{}", asset.machine_id));
+        match asset.port_number {
+            Some(port) => format!("{} port {}", machine, port),
+            None => machine,
+        }
+    }).collect()
+}
+
+// Where a FindingRef links to in the report
+fn finding_anchor(finding_id: u32) -> String {
+    format!("finding-{}", finding_id)
+}
+
+fn sorted_machines(workspace: &Workspace) -> Vec<&Machine> {
+    let mut machines: Vec<&Machine> = workspace.data.iter().collect();
+    machines.sort_by_key(|m| m.id);
+    machines
+}
+
+fn markdown_summary(workspace: &Workspace) -> String {
+    let summary = summarize(workspace);
+    let mut out = String::new();
+    out.push_str(&format!(
+        "The assessment of **{}** covered {} host(s), {} of which exposed services. \
+        A total of {} open port(s) were identified and {} credential set(s) were recorded.\n",
+        workspace.name, summary.hosts, summary.hosts_with_ports, summary.open_ports, summary.credentials
+    ));
+
+    if !summary.services.is_empty() {
+        out.push_str("\n| Service | Count |\n|---|---|\n");
+        for (service, count) in &summary.services {
+            out.push_str(&format!("| {} | {} |\n", escape_table(service), count));
+        }
+    }
+
+    if !summary.notes_by_stage.is_empty() {
+        out.push_str("\n| Stage | Notes |\n|---|---|\n");
+        for (stage, count) in &summary.notes_by_stage {
+            out.push_str(&format!("| {} | {} |\n", escape_table(stage), count));
+        }
+    }
+
+    if !workspace.notes.is_empty() {
+        out.push_str("\n### General Observations\n\n");
+        out.push_str(&markdown_stage_groups(general_notes_by_stage(&workspace.notes)));
+    }
+    out
+}
+
+fn markdown_findings(workspace: &Workspace) -> String {
+    let findings = sorted_findings(workspace);
+    if findings.is_empty() {
+        return "No findings recorded.\n".to_string();
+    }
+    let mut out = String::new();
+    for finding in findings {
+        out.push_str(&format!("### <a id=\"{}\"></a>#{} {}\n\n", finding_anchor(finding.id), finding.id, finding.title));
+        out.push_str(&format!("- **Severity:** {}\n", severity_name(finding.severity)));
+        if let Some(cvss) = cvss(finding) {
+            out.push_str(&format!("- **CVSS:** {}\n", cvss));
+        }
+        out.push_str(&format!("- **Status:** {}\n", status_name(finding.status)));
+        let affected = affected_assets(workspace, finding);
+        if !affected.is_empty() {
+            out.push_str(&format!("- **Affected:** {}\n", affected.join(", ")));
+        }
+        out.push('\n');
+        for (heading, text) in [("", &finding.description), ("Evidence", &finding.evidence), ("Remediation", &finding.remediation)] {
+            if text.trim().is_empty() {
+                continue;
+            }
+            if !heading.is_empty() {
+                out.push_str(&format!("**{}**\n\n", heading));
+            }
+            out.push_str(&format!("{}\n\n", text.trim()));
+        }
+    }
+    out
+}
+
+fn markdown_hosts(workspace: &Workspace, options: ReportOptions) -> String {
+    let mut out = String::new();
+    for machine in sorted_machines(workspace) {
+        out.push_str(&format!("### {} ({})\n\n", machine.hostname, machine.ip));
+        out.push_str(&markdown_stage_groups(general_notes_by_stage(&machine.notes)));
+
+        if machine.ports.is_empty() {
+            out.push_str("No open ports recorded.\n\n");
+            continue;
+        }
+
+        out.push_str("| Port | Protocol | State | Service | Application |\n|---|---|---|---|---|\n");
+        for port in &machine.ports {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {} |\n",
+                port.number,
+                escape_table(&port.protocol),
+                escape_table(&port.state),
+                escape_table(&port.service),
+                escape_table(&port.application)
+            ));
+        }
+        out.push('\n');
+
+        for port in &machine.ports {
+            let details = markdown_port_details(port, options);
+            if !details.is_empty() {
+                out.push_str(&format!("#### {}/{} - {}\n\n", port.number, port.protocol, port.service));
+                out.push_str(&details);
+            }
+        }
+    }
+    out
+}
+
+fn markdown_port_details(port: &Port, options: ReportOptions) -> String {
+    let mut out = String::new();
+
+    for note in &port.data {
+        match note {
+            PortNotes::NmapScan(lines) => {
+                out.push_str(&format!("```\n{}\n```\n\n", lines.join("\n")));
+            }
+            PortNotes::ScriptOutput { script_id, output, .. } => {
+                out.push_str(&format!("**Script `{}`**\n\n```\n{}\n```\n\n", script_id, output.join("\n")));
+            }
+            PortNotes::CommandOutput { command, output, .. } => {
+                out.push_str(&format!("**Command**\n\n```\n$ {}\n{}\n```\n\n", command, output));
+            }
+            PortNotes::Screenshot { path, caption, .. } => {
+                let caption = caption.as_deref().unwrap_or("Screenshot");
+                out.push_str(&format!("![{}]({})\n\n", caption, path));
+            }
+            PortNotes::FindingRef { finding_id, .. } => {
+                out.push_str(&format!("_See [finding #{}](#{})_\n\n", finding_id, finding_anchor(*finding_id)));
+            }
+            PortNotes::AiAnalysis { model, content, .. } => {
+                let model = model.as_deref().map(|m| format!(" ({})", m)).unwrap_or_default();
+                out.push_str(&format!("**AI Analysis{}**\n\n{}\n\n", model, content));
+            }
+            _ => {}
+        }
+    }
+
+    let credentials: Vec<&PortNotes> = port.data.iter()
+        .filter(|note| matches!(note, PortNotes::Credentials { .. }))
+        .collect();
+    if !credentials.is_empty() {
+        out.push_str("**Credentials**\n\n| Username | Password | Hash |\n|---|---|---|\n");
+        for note in credentials {
+            if let PortNotes::Credentials { name, hash, password, .. } = note {
+                out.push_str(&format!(
+                    "| {} | {} | {} |\n",
+                    escape_table(name.as_deref().unwrap_or("-")),
+                    escape_table(&credential_value(password, options)),
+                    escape_table(&credential_value(hash, options))
+                ));
+            }
+        }
+        out.push('\n');
+    }
+
+    out.push_str(&markdown_stage_groups(notes_by_stage(port)));
+    out
+}
+
+fn html_summary(workspace: &Workspace) -> String {
+    let summary = summarize(workspace);
+    let mut out = format!(
+        "<p>The assessment of <strong>{}</strong> covered {} host(s), {} of which exposed services. \
+        A total of {} open port(s) were identified and {} credential set(s) were recorded.</p>\n",
+        escape_html(&workspace.name), summary.hosts, summary.hosts_with_ports, summary.open_ports, summary.credentials
+    );
+
+    if !summary.services.is_empty() {
+        out.push_str("<table>\n<tr><th>Service</th><th>Count</th></tr>\n");
+        for (service, count) in &summary.services {
+            out.push_str(&format!("<tr><td>{}</td><td>{}</td></tr>\n", escape_html(service), count));
+        }
+        out.push_str("</table>\n");
+    }
+
+    if !summary.notes_by_stage.is_empty() {
+        out.push_str("<table>\n<tr><th>Stage</th><th>Notes</th></tr>\n");
+        for (stage, count) in &summary.notes_by_stage {
+            out.push_str(&format!("<tr><td>{}</td><td>{}</td></tr>\n", escape_html(stage), count));
+        }
+        out.push_str("</table>\n");
+    }
+
+    if !workspace.notes.is_empty() {
+        out.push_str("<h3>General Observations</h3>\n");
+        out.push_str(&html_stage_groups(general_notes_by_stage(&workspace.notes)));
+    }
+    out
+}
+
+fn html_findings(workspace: &Workspace) -> String {
+    let findings = sorted_findings(workspace);
+    if findings.is_empty() {
+        return "<p>No findings recorded.</p>\n".to_string();
+    }
+    let mut out = String::new();
+    for finding in findings {
+        out.push_str(&format!(
+            "<section id=\"{}\">\n<h3>#{} {}</h3>\n<table>\n<tr><th>Severity</th><td>{}</td></tr>\n",
+            finding_anchor(finding.id),
+            finding.id,
+            escape_html(&finding.title),
+            severity_name(finding.severity)
+        ));
+        if let Some(cvss) = cvss(finding) {
+            out.push_str(&format!("<tr><th>CVSS</th><td>{}</td></tr>\n", escape_html(&cvss)));
+        }
+        out.push_str(&format!("<tr><th>Status</th><td>{}</td></tr>\n", status_name(finding.status)));
+        let affected = affected_assets(workspace, finding);
+        if !affected.is_empty() {
+            out.push_str(&format!("<tr><th>Affected</th><td>{}</td></tr>\n", escape_html(&affected.join(", "))));
+        }
+        out.push_str("</table>\n");
+        for (heading, text) in [("", &finding.description), ("Evidence", &finding.evidence), ("Remediation", &finding.remediation)] {
+            if text.trim().is_empty() {
+                continue;
+            }
+            if !heading.is_empty() {
+                out.push_str(&format!("<p><strong>{}</strong></p>\n", heading));
+            }
+            out.push_str(&format!("<p>{}</p>\n", escape_html(text.trim()).replace('\n', "<br>\n")));
+        }
+        out.push_str("</section>\n");
+    }
+    out
+}
+
+fn html_hosts(workspace: &Workspace, options: ReportOptions) -> String {
+    let mut out = String::new();
+    for machine in sorted_machines(workspace) {
+        out.push_str(&format!(
+            "<section>\n<h3>{} ({})</h3>\n",
+            escape_html(&machine.hostname),
+            escape_html(&machine.ip)
+        ));
+        out.push_str(&html_stage_groups(general_notes_by_stage(&machine.notes)));
+
+        if machine.ports.is_empty() {
+            out.push_str("<p>No open ports recorded.</p>\n</section>\n");
+            continue;
+        }
+
+        out.push_str("<table>\n<tr><th>Port</th><th>Protocol</th><th>State</th><th>Service</th><th>Application</th></tr>\n");
+        for port in &machine.ports {
+            out.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                port.number,
+                escape_html(&port.protocol),
+                escape_html(&port.state),
+                escape_html(&port.service),
+                escape_html(&port.application)
+            ));
+        }
+        out.push_str("</table>\n");
+
+        for port in &machine.ports {
+            let details = html_port_details(port, options);
+            if !details.is_empty() {
+                out.push_str(&format!(
+                    "<h4>{}/{} - {}</h4>\n",
+                    port.number,
+                    escape_html(&port.protocol),
+                    escape_html(&port.service)
+                ));
+                out.push_str(&details);
+            }
+        }
+        out.push_str("</section>\n");
+    }
+    out
+}
+
+fn html_port_details(port: &Port, options: ReportOptions) -> String {
+    let mut out = String::new();
+
+    for note in &port.data {
+        match note {
+            PortNotes::NmapScan(lines) => {
+                out.push_str(&format!("<pre>{}</pre>\n", escape_html(&lines.join("\n"))));
+            }
+            PortNotes::ScriptOutput { script_id, output, .. } => {
+                out.push_str(&format!(
+                    "<p><strong>Script <code>{}</code></strong></p>\n<pre>{}</pre>\n",
+                    escape_html(script_id),
+                    escape_html(&output.join("\n"))
+                ));
+            }
+            PortNotes::CommandOutput { command, output, .. } => {
+                out.push_str(&format!(
+                    "<p><strong>Command</strong></p>\n<pre>$ {}\n{}</pre>\n",
+                    escape_html(command),
+                    escape_html(output)
+                ));
+            }
+            PortNotes::Screenshot { path, caption, .. } => {
+                let caption = caption.as_deref().unwrap_or("Screenshot");
+                out.push_str(&format!(
+                    "<figure><img src=\"{}\" alt=\"{}\"><figcaption>{}</figcaption></figure>\n",
+                    escape_html(path),
+                    escape_html(caption),
+                    escape_html(caption)
+                ));
+            }
+            PortNotes::FindingRef { finding_id, .. } => {
+                out.push_str(&format!("<p><em>See <a href=\"#{}\">finding #{}</a></em></p>\n", finding_anchor(*finding_id), finding_id));
+            }
+            PortNotes::AiAnalysis { model, content, .. } => {
+                let model = model.as_deref().map(|m| format!(" ({})", escape_html(m))).unwrap_or_default();
+                out.push_str(&format!(
+                    "<p><strong>AI Analysis{}</strong></p>\n<pre>{}</pre>\n",
+                    model,
+                    escape_html(content)
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    let credentials: Vec<&PortNotes> = port.data.iter()
+        .filter(|note| matches!(note, PortNotes::Credentials { .. }))
+        .collect();
+    if !credentials.is_empty() {
+        out.push_str("<p><strong>Credentials</strong></p>\n<table>\n<tr><th>Username</th><th>Password</th><th>Hash</th></tr>\n");
+        for note in credentials {
+            if let PortNotes::Credentials { name, hash, password, .. } = note {
+                out.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape_html(name.as_deref().unwrap_or("-")),
+                    escape_html(&credential_value(password, options)),
+                    escape_html(&credential_value(hash, options))
+                ));
+            }
+        }
+        out.push_str("</table>\n");
+    }
+
+    out.push_str(&html_stage_groups(notes_by_stage(port)));
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn escape_table(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::findings::AffectedAsset;
+    use crate::notes::new_port_note;
+    use crate::store::tests::test_port;
+
+    fn workspace() -> Workspace {
+        let mut workspace = Workspace::new(1, "Lab {{hosts}}".to_string(), "10.0.0.0/24".to_string());
+        let mut machine = Machine::new(1, "dc01".to_string(), "10.0.0.5".to_string());
+        machine.ports.push(test_port(445, "smb", "Samba <4.13>", vec![PortNotes::Credentials {
+            id: String::new(),
+            name: Some("svc_backup".to_string()),
+            hash: None,
+            password: Some("Winter2024".to_string()),
+            author: None,
+        }]));
+        workspace.data.push(machine);
+        workspace
+    }
+
+    #[test]
+    fn placeholders_are_filled_once() {
+        assert_eq!(fill("{{a}} and {{b}}", &[("a", "{{b}}"), ("b", "x")]), "{{b}} and x");
+        assert_eq!(fill("{{unknown}} {{a", &[("a", "x")]), "{{unknown}} {{a");
+
+        let report = render(&workspace(), "{{workspace_name}}\n{{hosts}}", ReportFormat::Markdown, ReportOptions::default());
+        assert!(report.starts_with("Lab {{hosts}}\n### dc01 (10.0.0.5)"));
+    }
+
+    #[test]
+    fn credentials_are_left_out_unless_asked_for() {
+        let template = DEFAULT_MARKDOWN_TEMPLATE;
+        let report = render(&workspace(), template, ReportFormat::Markdown, ReportOptions::default());
+        assert!(report.contains("| svc_backup | [REDACTED] | - |"));
+        assert!(!report.contains("Winter2024"));
+
+        let options = ReportOptions { include_credentials: true };
+        let report = render(&workspace(), template, ReportFormat::Markdown, options);
+        assert!(report.contains("| svc_backup | Winter2024 | - |"));
+
+        let html = render(&workspace(), DEFAULT_HTML_TEMPLATE, ReportFormat::Html, ReportOptions::default());
+        assert!(html.contains("<td>Samba &lt;4.13&gt;</td>"));
+        assert!(!html.contains("Winter2024"));
+    }
+
+    #[test]
+    fn secrets_are_scrubbed_from_notes_without_credentials() {
+        let mut workspace = workspace();
+        workspace.data[0].ports[0].data.extend([
+            new_port_note("Exploitation".to_string(), "Logged in with Winter2024".to_string()),
+            PortNotes::CommandOutput {
+                id: String::new(),
+                command: "smbclient -U svc_backup%Winter2024 //10.0.0.5/C$".to_string(),
+                output: "Winter2024 accepted".to_string(),
+                exit_code: Some(0),
+                timestamp: String::new(),
+            },
+            PortNotes::AiAnalysis { id: String::new(), model: None, prompt: None, content: "Reuse Winter2024".to_string(), timestamp: String::new() },
+        ]);
+        for format in [ReportFormat::Markdown, ReportFormat::Html] {
+            let template = format.default_template();
+            assert!(!render(&workspace, template, format, ReportOptions::default()).contains("Winter2024"));
+            assert!(render(&workspace, template, format, ReportOptions { include_credentials: true }).contains("Logged in with Winter2024"));
+        }
+    }
+
+    #[test]
+    fn findings_are_listed_and_linked() {
+        let mut workspace = workspace();
+        assert!(render(&workspace, DEFAULT_MARKDOWN_TEMPLATE, ReportFormat::Markdown, ReportOptions::default())
+            .contains("## Findings\n\nNo findings recorded."));
+
+        workspace.findings.push(Finding {
+            id: 3,
+            title: "SMB signing <disabled>".to_string(),
+            severity: Severity::High,
+            cvss_vector: Some("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:N".to_string()),
+            cvss_score: Some(9.1),
+            affected: vec![AffectedAsset { machine_id: 1, port_number: Some(445) }],
+            description: "Relaying is possible.".to_string(),
+            evidence: String::new(),
+            remediation: "Require signing.".to_string(),
+            status: FindingStatus::Open,
+        });
+        workspace.data[0].ports[0].data.push(PortNotes::FindingRef { id: String::new(), finding_id: 3 });
+
+        let markdown = render(&workspace, DEFAULT_MARKDOWN_TEMPLATE, ReportFormat::Markdown, ReportOptions::default());
+        assert!(markdown.contains("### <a id=\"finding-3\"></a>#3 SMB signing <disabled>\n\n- **Severity:** High\n\
+            - **CVSS:** 9.1 (CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:N)\n- **Status:** Open\n- **Affected:** dc01 (10.0.0.5) port 445\n"));
+        assert!(markdown.contains("**Remediation**\n\nRequire signing."));
+        assert!(markdown.contains("_See [finding #3](#finding-3)_"));
+
+        let html = render(&workspace, DEFAULT_HTML_TEMPLATE, ReportFormat::Html, ReportOptions::default());
+        assert!(html.contains("<section id=\"finding-3\">\n<h3>#3 SMB signing &lt;disabled&gt;</h3>"));
+        assert!(html.contains("<tr><th>Affected</th><td>dc01 (10.0.0.5) port 445</td></tr>"));
+        assert!(html.contains("<a href=\"#finding-3\">finding #3</a>"));
+    }
+}
@@ -0,0 +1,392 @@
+use serde::Serialize;
+use specta::Type;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{query, Database, PortNotes};
+
+#[derive(Clone, Serialize, Type)]
+pub(crate) struct HitPath {
+    pub workspace_id: u32,
+    pub workspace_name: String,
+    pub machine_id: Option<u32>,
+    pub hostname: Option<String>,
+    pub ip: Option<String>,
+    pub port_number: Option<u16>,
+    pub service: Option<String>,
+    pub note_id: Option<String>,
+    // Tags of the object and everything containing it
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Type)]
+pub(crate) struct SearchHit {
+    // "machine", "port", "scan", "note", "credentials", ...
+    pub kind: String,
+    pub path: HitPath,
+    // Human readable location, e.g. "Internal / dc01 (10.0.0.5) / 445/tcp"
+    pub location: String,
+    pub stage: Option<String>,
+    pub snippet: String,
+    pub score: f32,
+}
+
+struct Document {
+    kind: &'static str,
+    path: HitPath,
+    location: String,
+    stage: Option<String>,
+    text: String,
+    // Hostnames and services should outrank a passing mention in a scan line
+    weight: f32,
+}
+
+#[derive(Default)]
+pub(crate) struct SearchIndex {
+    // The store's revision of the database the index was built from
+    revision: u64,
+    documents: Vec<Document>,
+    // token -> (document, occurrences)
+    postings: BTreeMap<String, Vec<(usize, u32)>>,
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn with_tags(path: &HitPath, tags: &[String]) -> Vec<String> {
+    path.tags.iter().chain(tags).cloned().collect()
+}
+
+fn location(path: &HitPath) -> String {
+    let mut parts = vec![path.workspace_name.clone()];
+    if let (Some(hostname), Some(ip)) = (&path.hostname, &path.ip) {
+        parts.push(format!("{} ({})", hostname, ip));
+    }
+    if let Some(port) = path.port_number {
+        parts.push(format!("{}/{}", port, path.service.as_deref().unwrap_or("unknown")));
+    }
+    if let Some(note_id) = &path.note_id {
+        parts.push(format!("note {}", note_id));
+    }
+    parts.join(" / ")
+}
+
+impl SearchIndex {
+    fn push(&mut self, kind: &'static str, path: HitPath, stage: Option<String>, text: String, weight: f32) {
+        if text.trim().is_empty() {
+            return;
+        }
+        let index = self.documents.len();
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for token in tokenize(&text) {
+            *counts.entry(token).or_default() += 1;
+        }
+        for (token, count) in counts {
+            self.postings.entry(token).or_default().push((index, count));
+        }
+        self.documents.push(Document { kind, location: location(&path), path, stage, text, weight });
+    }
+
+    fn build(database: &Database, revision: u64) -> SearchIndex {
+        let mut index = SearchIndex { revision, ..Default::default() };
+        for workspace in &database.data {
+            let workspace_path = HitPath {
+                workspace_id: workspace.id,
+                workspace_name: workspace.name.clone(),
+                machine_id: None,
+                hostname: None,
+                ip: None,
+                port_number: None,
+                service: None,
+                note_id: None,
+                tags: workspace.tags.clone(),
+            };
+            index.push("workspace", workspace_path.clone(), None, format!("{} {}", workspace.name, workspace.ip_range), 2.0);
+            for note in &workspace.notes {
+                let path = HitPath { note_id: Some(note.id.clone()), tags: with_tags(&workspace_path, &note.tags), ..workspace_path.clone() };
+                index.push("note", path, Some(note.stage.clone()), note.content.clone(), 1.5);
+            }
+
+            for machine in &workspace.data {
+                let machine_path = HitPath {
+                    machine_id: Some(machine.id),
+                    hostname: Some(machine.hostname.clone()),
+                    ip: Some(machine.ip.clone()),
+                    tags: with_tags(&workspace_path, &machine.tags),
+                    ..workspace_path.clone()
+                };
+                index.push("machine", machine_path.clone(), None, format!("{} {}", machine.hostname, machine.ip), 3.0);
+                for note in &machine.notes {
+                    let path = HitPath { note_id: Some(note.id.clone()), tags: with_tags(&machine_path, &note.tags), ..machine_path.clone() };
+                    index.push("note", path, Some(note.stage.clone()), note.content.clone(), 1.5);
+                }
+
+                for port in &machine.ports {
+                    let port_path = HitPath {
+                        port_number: Some(port.number),
+                        service: Some(port.service.clone()),
+                        tags: with_tags(&machine_path, &port.tags),
+                        ..machine_path.clone()
+                    };
+                    index.push("port", port_path.clone(), None,
+                        format!("{} {} {} {}", port.number, port.protocol, port.service, port.application), 2.0);
+
+                    for entry in &port.data {
+                        let path = port_path.clone();
+                        match entry {
+                            PortNotes::NmapScan(lines) => index.push("scan", path, None, lines.join("\n"), 1.0),
+                            PortNotes::ScriptOutput { script_id, output, .. } => {
+                                index.push("scan", path, None, format!("{}\n{}", script_id, output.join("\n")), 1.0)
+                            }
+                            PortNotes::Credentials { name, hash, password, .. } => {
+                                // Labels included so "tomcat password" finds the entry, not just the literal value
+                                let text = [("user", name), ("password", password), ("hash", hash)].iter()
+                                    .filter_map(|(label, value)| value.as_deref().map(|value| format!("{} {}", label, value)))
+                                    .collect::<Vec<_>>()
+                                    .join(" ");
+                                index.push("credentials", path, None, text, 2.0)
+                            }
+                            PortNotes::PentestNote { id, stage, content, tags, .. } => {
+                                let path = HitPath { note_id: Some(id.clone()), tags: with_tags(&path, tags), ..path };
+                                index.push("note", path, Some(stage.clone()), content.clone(), 1.5)
+                            }
+                            PortNotes::AiAnalysis { content, .. } => index.push("analysis", path, None, content.clone(), 0.8),
+                            PortNotes::CommandOutput { command, output, .. } => {
+                                index.push("command", path, None, format!("{}\n{}", command, output), 1.0)
+                            }
+                            PortNotes::Screenshot { path: file, caption, .. } => {
+                                index.push("screenshot", path, None, format!("{} {}", file, caption.as_deref().unwrap_or("")), 0.8)
+                            }
+                            PortNotes::FindingRef { .. } | PortNotes::None => {}
+                        }
+                    }
+                }
+            }
+        }
+        index
+    }
+
+    // Rebuilds the index if the database changed since it was last built
+    pub(crate) fn refresh(&mut self, database: &Database, revision: u64) {
+        if self.revision != revision {
+            *self = SearchIndex::build(database, revision);
+        }
+    }
+
+    pub(crate) fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let query = Query::parse(query);
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+
+        if query.terms.is_empty() {
+            // Filters only: every document is a candidate
+            for index in 0..self.documents.len() {
+                scores.insert(index, 1.0);
+            }
+        } else {
+            let mut matched_terms: HashMap<usize, usize> = HashMap::new();
+            for term in &query.terms {
+                let mut term_scores: HashMap<usize, f32> = HashMap::new();
+                // Prefix matches count, exact token matches count more
+                for (token, postings) in self.postings.range(term.clone()..).take_while(|(token, _)| token.starts_with(term.as_str())) {
+                    let exact = if token == term { 1.0 } else { 0.5 };
+                    for (document, count) in postings {
+                        let score = term_scores.entry(*document).or_default();
+                        *score = score.max(exact * (1.0 + (*count as f32).ln()));
+                    }
+                }
+                for (document, score) in term_scores {
+                    *scores.entry(document).or_default() += score;
+                    *matched_terms.entry(document).or_default() += 1;
+                }
+            }
+            // Every term has to appear in the document
+            scores.retain(|document, _| matched_terms.get(document) == Some(&query.terms.len()));
+        }
+
+        let mut hits: Vec<(usize, SearchHit)> = scores.into_iter()
+            .filter(|(index, _)| query.matches(&self.documents[*index]))
+            .map(|(index, score)| {
+                let document = &self.documents[index];
+                let mut score = score * document.weight;
+                if !query.phrase.is_empty() && document.text.to_lowercase().contains(&query.phrase) {
+                    score *= 1.5;
+                }
+                (index, SearchHit {
+                    kind: document.kind.to_string(),
+                    path: document.path.clone(),
+                    location: document.location.clone(),
+                    stage: document.stage.clone(),
+                    snippet: snippet(&document.text, &query.terms),
+                    score,
+                })
+            })
+            .collect();
+        // Entries of the same port share a location, so ties fall back to the order they were indexed in
+        hits.sort_by(|(a_index, a), (b_index, b)| {
+            b.score.total_cmp(&a.score)
+                .then_with(|| a.location.cmp(&b.location))
+                .then_with(|| a_index.cmp(b_index))
+        });
+        hits.truncate(limit);
+        hits.into_iter().map(|(_, hit)| hit).collect()
+    }
+}
+
+#[derive(Default)]
+struct Query {
+    terms: Vec<String>,
+    phrase: String,
+    filters: Vec<(String, String)>,
+}
+
+impl Query {
+    // Splits "tomcat password service:http stage:Exploitation" into terms and key:value filters
+    fn parse(query: &str) -> Query {
+        let mut parsed = Query::default();
+        let mut words = vec![];
+        for word in query.split_whitespace() {
+            match word.split_once(':') {
+                Some((key, value)) if !key.is_empty() && !value.is_empty() && FILTERS.contains(&key.to_lowercase().as_str()) => {
+                    parsed.filters.push((key.to_lowercase(), value.to_lowercase()));
+                }
+                _ => {
+                    parsed.terms.extend(tokenize(word));
+                    words.push(word.to_lowercase());
+                }
+            }
+        }
+        parsed.phrase = words.join(" ");
+        parsed
+    }
+
+    fn matches(&self, document: &Document) -> bool {
+        let path = &document.path;
+        self.filters.iter().all(|(key, value)| match key.as_str() {
+            "service" => path.service.as_deref().is_some_and(|service| service.to_lowercase().contains(value)),
+            "stage" => document.stage.as_deref().is_some_and(|stage| stage.to_lowercase() == *value),
+            "port" => path.port_number.is_some_and(|port| port.to_string() == *value),
+            "host" => path.hostname.as_deref().is_some_and(|host| host.to_lowercase().contains(value))
+                || path.ip.as_deref().is_some_and(|ip| query::ip_matches(ip, value)),
+            "workspace" => path.workspace_id.to_string() == *value || path.workspace_name.to_lowercase().contains(value),
+            "kind" => document.kind == value,
+            "tag" => path.tags.iter().any(|tag| tag.to_lowercase() == *value),
+            _ => true,
+        })
+    }
+}
+
+const FILTERS: &[&str] = &["service", "stage", "port", "host", "workspace", "kind", "tag"];
+
+// The first line containing a query term, or the start of the text
+fn snippet(text: &str, terms: &[String]) -> String {
+    let line = text.lines()
+        .find(|line| {
+            let tokens = tokenize(line);
+            terms.iter().any(|term| tokens.iter().any(|token| token.starts_with(term.as_str())))
+        })
+        .or_else(|| text.lines().next())
+        .unwrap_or("")
+        .trim();
+
+    if line.chars().count() > 160 {
+        format!("{}…", line.chars().take(160).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notes::{new_note, new_port_note};
+    use crate::store::tests::test_port;
+    use crate::{Machine, Workspace};
+
+    fn index() -> SearchIndex {
+        let mut workspace = Workspace::new(1, "Internal".to_string(), "10.0.0.0/24".to_string());
+        let mut dc = Machine::new(1, "dc01".to_string(), "10.0.0.5".to_string());
+        dc.tags.push("Crown-Jewels".to_string());
+        dc.ports.push(test_port(445, "microsoft-ds", "Samba smbd", vec![
+            PortNotes::NmapScan(vec!["smb-os-discovery: Windows Server 2019".to_string(), "signing disabled".to_string()]),
+        ]));
+        let mut web = Machine::new(2, "web".to_string(), "10.0.0.8".to_string());
+        web.ports.push(test_port(8080, "http", "Apache Tomcat 9.0.31", vec![
+            new_port_note("Exploitation".to_string(), "Deployed a WAR through the tomcat manager".to_string()),
+            PortNotes::Credentials {
+                id: String::new(),
+                name: Some("tomcat".to_string()),
+                hash: None,
+                password: Some("s3cret".to_string()),
+                author: None,
+            },
+        ]));
+        web.notes.push(new_note("Enumeration".to_string(), "Reused the dc01 admin password here".to_string()));
+        workspace.data.extend([dc, web]);
+        SearchIndex::build(&Database { data: vec![workspace], ..Database::default() }, 1)
+    }
+
+    fn kinds(hits: &[SearchHit]) -> Vec<&str> {
+        hits.iter().map(|hit| hit.kind.as_str()).collect()
+    }
+
+    #[test]
+    fn text_is_split_into_lowercase_words() {
+        assert_eq!(tokenize("Apache/2.4.41 (Ubuntu) smb-OS"), ["apache", "2", "4", "41", "ubuntu", "smb", "os"]);
+        assert!(tokenize(" -- ").is_empty());
+    }
+
+    #[test]
+    fn prefixes_match_and_exact_words_rank_higher() {
+        let index = index();
+        let hits = index.search("tomc", 10);
+        assert_eq!(kinds(&hits), ["port", "credentials", "note"]);
+        assert!(hits.iter().all(|hit| hit.path.machine_id == Some(2)));
+        let exact = index.search("tomcat", 10);
+        assert_eq!(kinds(&exact), ["port", "credentials", "note"]);
+        assert!(exact.iter().zip(&hits).all(|(exact, prefix)| exact.score > prefix.score));
+
+        // Every term has to match
+        assert_eq!(kinds(&index.search("tomcat password", 10)), ["credentials"]);
+        assert!(index.search("tomcat kerberos", 10).is_empty());
+        assert_eq!(index.search("tomc", 1).len(), 1);
+    }
+
+    #[test]
+    fn weights_put_hosts_before_mentions() {
+        let hits = index().search("dc01", 10);
+        assert_eq!(kinds(&hits), ["machine", "note"]);
+        assert!(hits[0].score > hits[1].score);
+        assert_eq!(hits[0].location, "Internal / dc01 (10.0.0.5)");
+        assert_eq!(hits[1].location, format!("Internal / web (10.0.0.8) / note {}", hits[1].path.note_id.as_deref().unwrap()));
+    }
+
+    #[test]
+    fn filters_narrow_the_hits() {
+        let index = index();
+        assert_eq!(kinds(&index.search("service:http", 10)), ["port", "credentials", "note"]);
+        let exploitation = index.search("stage:exploitation", 10);
+        assert_eq!(kinds(&exploitation), ["note"]);
+        assert_eq!(exploitation[0].stage.as_deref(), Some("Exploitation"));
+        assert_eq!(kinds(&index.search("signing tag:crown-jewels", 10)), ["scan"]);
+        assert!(index.search("tomcat tag:crown-jewels", 10).is_empty());
+        assert_eq!(kinds(&index.search("tomcat host:10.0.0.8", 10)), ["port", "credentials", "note"]);
+        assert_eq!(kinds(&index.search("tomcat host:10.0.0.0/24", 10)), ["port", "credentials", "note"]);
+        assert!(index.search("tomcat host:10.0.0", 10).is_empty());
+        assert!(index.search("tomcat host:10.0.0.5", 10).is_empty());
+        // Unknown keys are searched for like any other word
+        assert_eq!(kinds(&index.search("smb-os-discovery:", 10)), ["scan"]);
+    }
+
+    #[test]
+    fn snippets_show_the_matching_line() {
+        let index = index();
+        assert_eq!(index.search("signing", 1)[0].snippet, "signing disabled");
+        assert_eq!(index.search("kind:scan", 1)[0].snippet, "smb-os-discovery: Windows Server 2019");
+
+        let long = format!("{} needle", "x".repeat(200));
+        assert_eq!(snippet(&long, &["needle".to_string()]), format!("{}…", "x".repeat(160)));
+    }
+}
@@ -0,0 +1,415 @@
+use futures::future::{self, Either};
+use serde::Serialize;
+use serde_json::Value;
+use specta::Type;
+use std::collections::HashMap;
+use std::pin::pin;
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+
+use crate::audit;
+use crate::backend::{AiAnswer, Backend};
+use crate::chat::{self, Conversation, Role};
+use crate::llm::{self, AiSettings, Prompt};
+use crate::notes;
+use crate::error::{AppError, AppResult};
+use crate::redact::{RedactionPolicy, Redactor};
+use crate::store::Store;
+use crate::{now, ChatContext, Database, Port, PortNotes};
+
+// Everything known about the workspace, machine and port the question is asked from, with
+// passwords and hashes shown as `redactor` allows
+pub(crate) fn context(db: &Database, context: &ChatContext, redactor: &Redactor) -> String {
+    let mut context_str = String::new();
+
+    if let Ok(workspace) = db.workspace(context.workspace_id) {
+        context_str.push_str(&format!("Workspace: {}\n", workspace.name));
+        for note in &workspace.notes {
+            context_str.push_str(&format!("Workspace Note ({} - {}):\n{}\n", note.stage, note.timestamp, note.content));
+        }
+
+        if let Some(machine) = context.machine_id.and_then(|machine_id| workspace.machine(machine_id).ok()) {
+            context_str.push_str(&format!("Machine: {} ({})\n", machine.hostname, machine.ip));
+            for note in &machine.notes {
+                context_str.push_str(&format!("Machine Note ({} - {}):\n{}\n", note.stage, note.timestamp, note.content));
+            }
+
+            if let Some(port) = context.port_number.and_then(|port_number| machine.port(port_number).ok()) {
+                context_str.push_str(&format!("Port {}/{} - {} ({})\n",
+                    port.number, port.protocol, port.service, port.application));
+
+                // Add all port notes
+                for note in &port.data {
+                    match note {
+                        PortNotes::NmapScan(details) => {
+                            context_str.push_str("Nmap Scan Results:\n");
+                            for detail in details {
+                                context_str.push_str(&format!("- {}\n", detail));
+                            }
+                        },
+                        PortNotes::Credentials { name, hash, password, .. } => {
+                            context_str.push_str("Credentials Found:\n");
+                            if let Some(n) = name { context_str.push_str(&format!("- Username: {}\n", n)); }
+                            if let Some(h) = hash { context_str.push_str(&format!("- Hash: {}\n", redactor.secret(h))); }
+                            if let Some(p) = password { context_str.push_str(&format!("- Password: {}\n", redactor.secret(p))); }
+                        },
+                        PortNotes::PentestNote { stage, content, timestamp, .. } => {
+                            context_str.push_str(&format!("Pentest Note ({} - {}):\n{}\n", stage, timestamp, content));
+                        },
+                        PortNotes::AiAnalysis { content, .. } => {
+                            context_str.push_str(&format!("Previous AI Analysis:\n{}\n", content));
+                        },
+                        PortNotes::ScriptOutput { script_id, output, .. } => {
+                            context_str.push_str(&format!("Script Output ({}):\n", script_id));
+                            for line in output {
+                                context_str.push_str(&format!("- {}\n", line));
+                            }
+                        },
+                        PortNotes::CommandOutput { command, output, .. } => {
+                            context_str.push_str(&format!("Command Output ($ {}):\n{}\n", command, output));
+                        },
+                        PortNotes::Screenshot { .. } | PortNotes::FindingRef { .. } | PortNotes::None => {}
+                    }
+                }
+            }
+        }
+    }
+    context_str
+}
+
+pub(crate) fn analysis_prompt(port: &Port) -> String {
+    let port_info = format!(
+        "Analyze this port for security vulnerabilities:\n\
+        Service: {}\n\
+        Port: {}\n\
+        Protocol: {}\n\
+        State: {}\n\
+        Application: {}\n\n\
+        Scan Results:\n{}",
+        port.service,
+        port.number,
+        port.protocol,
+        port.state,
+        port.application,
+        port.data.iter()
+            .filter_map(|note| match note {
+                PortNotes::NmapScan(details) => Some(details.join("\n")),
+                PortNotes::ScriptOutput { script_id, output, .. } => Some(format!("{}:\n{}", script_id, output.join("\n"))),
+                _ => None
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+    format!(
+        "Security analysis of port scan.\n\
+        Format in Markdown:\n\
+        1. Critical Vulnerabilities\n\
+        2. Exploitation Methods\n\
+        3. Security Recommendations\n\n\
+        {}",
+        port_info
+    )
+}
+
+// Saved next to the database and used from the next question on
+pub(crate) fn update_settings(store: &Store, backend: &Backend, settings: AiSettings) -> AppResult<AiSettings> {
+    settings.validate()?;
+    llm::save_settings(store.dir(), &settings)?;
+    backend.set_ai_settings(settings.clone())?;
+    Ok(settings)
+}
+
+// A piece of a streamed answer, sent to the frontend as an "ai-chunk" event
+#[derive(Clone, Serialize, Type)]
+pub(crate) struct AiChunk {
+    pub request_id: String,
+    pub text: String,
+}
+
+// Questions still being answered, by the request ID the frontend picked
+#[derive(Default)]
+pub(crate) struct AiRequests {
+    running: Mutex<HashMap<String, oneshot::Sender<()>>>,
+}
+
+impl AiRequests {
+    fn start(&self, request_id: &str) -> AppResult<oneshot::Receiver<()>> {
+        let mut running = self.running.lock()?;
+        if running.contains_key(request_id) {
+            return Err(AppError::validation(format!("Request {} is already running", request_id)));
+        }
+        let (sender, receiver) = oneshot::channel();
+        running.insert(request_id.to_string(), sender);
+        Ok(receiver)
+    }
+
+    fn finish(&self, request_id: &str) -> AppResult<()> {
+        self.running.lock()?.remove(request_id);
+        Ok(())
+    }
+
+    // False when the request already finished
+    pub(crate) fn cancel(&self, request_id: &str) -> AppResult<bool> {
+        Ok(match self.running.lock()?.remove(request_id) {
+            Some(sender) => sender.send(()).is_ok(),
+            None => false,
+        })
+    }
+}
+
+// What the chat window gets back once an answer is complete
+#[derive(Serialize, Type)]
+pub(crate) struct ChatReply {
+    pub conversation_id: String,
+    pub text: String,
+    pub model: Option<String>,
+}
+
+// The workspace's redaction policy as it applies to the configured model. Nothing is known
+// about a workspace that doesn't exist, so there's nothing to hide either.
+fn redactor(db: &Database, workspace_id: u32, local_model: bool) -> Redactor {
+    match db.workspace(workspace_id) {
+        Ok(workspace) => Redactor::new(workspace, local_model),
+        Err(_) => Redactor::none(),
+    }
+}
+
+// An existing conversation, which keeps its own context, or a new one about `context`
+fn conversation(store: &Store, conversation_id: Option<&str>, question: &str, context: &ChatContext) -> AppResult<Conversation> {
+    match conversation_id {
+        Some(id) => store.conversations(context.workspace_id, |conversations| Ok(chat::get(conversations, id)?.clone())),
+        None => Ok(Conversation::new(context, question)),
+    }
+}
+
+// The question with the conversation's context and as much of its history as the model's
+// context window leaves room for, redacted
+fn chat_prompt(store: &Store, backend: &Backend, conversation: &Conversation, question: &str) -> AppResult<Prompt> {
+    let local_model = backend.local_model()?;
+    let (context, redactor) = store.read(|db| {
+        let redactor = redactor(db, conversation.workspace_id, local_model);
+        Ok((self::context(db, &conversation.context(), &redactor), redactor))
+    })?;
+    let mut prompt = Prompt::new(question.to_string());
+    prompt.context = context;
+    let settings = backend.ai_settings()?;
+    let budget = settings.history_budget(prompt.tokens(&settings.system_prompt));
+    prompt.history = chat::recent_history(&conversation.messages, budget).to_vec();
+    redactor.prompt(&mut prompt);
+    Ok(prompt)
+}
+
+// Exactly what asking the question would send, for checking before anything leaves the machine
+#[derive(Serialize, Type)]
+pub(crate) struct PromptPreview {
+    pub url: String,
+    pub body: Value,
+    // Whether the model runs on this machine
+    pub local: bool,
+    pub redaction: RedactionPolicy,
+}
+
+pub(crate) fn preview_prompt(
+    store: &Store,
+    backend: &Backend,
+    conversation_id: Option<&str>,
+    question: &str,
+    context: &ChatContext,
+) -> AppResult<PromptPreview> {
+    let redaction = store.read(|db| Ok(db.workspace(context.workspace_id)?.redaction))?;
+    let conversation = conversation(store, conversation_id, question, context)?;
+    let prompt = chat_prompt(store, backend, &conversation, question)?;
+    let (url, body) = backend.request_body(&prompt)?;
+    Ok(PromptPreview { url, body, local: backend.local_model()?, redaction })
+}
+
+// Streams the answer to `on_text` and saves the exchange. Without `conversation_id` a new
+// conversation about `context` is started; an existing one keeps its own context. A
+// cancelled request returns and saves what was received so far.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn ask_question(
+    store: &Store,
+    backend: &Backend,
+    requests: &AiRequests,
+    request_id: &str,
+    conversation_id: Option<&str>,
+    question: &str,
+    context: &ChatContext,
+    mut on_text: impl FnMut(&str),
+) -> AppResult<ChatReply> {
+    let workspace_id = context.workspace_id;
+    let conversation = conversation(store, conversation_id, question, context)?;
+    let prompt = chat_prompt(store, backend, &conversation, question)?;
+
+    let cancelled = requests.start(request_id)?;
+    let mut received = String::new();
+    let answer = {
+        let ask = backend.ask_stream(&prompt, |text| {
+            received.push_str(text);
+            on_text(text);
+        });
+        match future::select(pin!(ask), cancelled).await {
+            Either::Left((answer, _)) => Some(answer),
+            Either::Right(_) => None,
+        }
+    };
+    requests.finish(request_id)?;
+    let answer = answer.unwrap_or(Ok(AiAnswer { text: received, model: None }))?;
+
+    let conversation_id = conversation.id.clone();
+    if !answer.text.is_empty() {
+        store.update_conversations(workspace_id, |conversations| {
+            if !conversations.iter().any(|existing| existing.id == conversation_id) {
+                conversations.push(conversation);
+            }
+            let saved = chat::find(conversations, &conversation_id)?;
+            saved.push(Role::User, question.to_string(), None);
+            saved.push(Role::Assistant, answer.text.clone(), answer.model.clone());
+            Ok(())
+        })?;
+    }
+    Ok(ChatReply { conversation_id, text: answer.text, model: answer.model })
+}
+
+// Asks for an analysis of the port and saves the answer as a note on it
+pub(crate) async fn analyze_port(store: &Store, backend: &Backend, workspace_id: u32, machine_id: u32, port_number: u16) -> AppResult<()> {
+    let local_model = backend.local_model()?;
+    let prompt = store.read(|db| {
+        let port = db.port(workspace_id, machine_id, port_number)?;
+        Ok(redactor(db, workspace_id, local_model).text(&analysis_prompt(port)))
+    })?;
+    let analysis = backend.ask(&Prompt::new(prompt.clone())).await?;
+
+    let ai_note = PortNotes::AiAnalysis {
+        id: notes::new_note_id(),
+        model: analysis.model,
+        prompt: Some(prompt),
+        content: analysis.text,
+        timestamp: now(),
+    };
+    store.update_audited(|db, changes| {
+        changes.record(workspace_id, "analyze", format!("port:{}/{}", machine_id, port_number), None, audit::to_value(&ai_note));
+        db.port_mut(workspace_id, machine_id, port_number)?.data.push(ai_note);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api;
+    use crate::store::tests::{temp_store, test_port};
+    use crate::{Machine, Workspace};
+    use axum::body::{Body, Bytes};
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use futures::stream::{self, StreamExt};
+    use std::convert::Infallible;
+    use std::net::TcpListener;
+
+    #[test]
+    fn context_covers_the_selected_port() {
+        let mut db = Database::default();
+        let mut workspace = Workspace::new(1, "Lab".to_string(), "10.0.0.0/24".to_string());
+        let mut machine = Machine::new(1, "dc01".to_string(), "10.0.0.5".to_string());
+        machine.ports.push(test_port(445, "smb", "Samba 4.13", vec![PortNotes::Credentials { id: String::new(), name: Some("svc_backup".to_string()), hash: None, password: Some("Winter2024".to_string()), author: None }]));
+        workspace.data.push(machine);
+        db.data.push(workspace);
+
+        let chat = ChatContext { type_: "port".to_string(), workspace_id: 1, machine_id: Some(1), port_number: Some(445) };
+        let text = context(&db, &chat, &Redactor::none());
+        assert!(text.contains("Machine: dc01 (10.0.0.5)"));
+        assert!(text.contains("Port 445/tcp - smb (Samba 4.13)"));
+        assert!(text.contains("- Username: svc_backup"));
+        assert!(text.contains("- Password: Winter2024"));
+        db.data[0].redaction = RedactionPolicy::Masked;
+        let masked = context(&db, &chat, &Redactor::new(&db.data[0], false));
+        assert!(masked.contains("- Password: Wi******24"));
+
+        let unknown = ChatContext { type_: "workspace".to_string(), workspace_id: 2, machine_id: None, port_number: None };
+        assert!(context(&db, &unknown, &Redactor::none()).is_empty());
+        assert!(analysis_prompt(db.port(1, 1, 445).unwrap()).contains("Application: Samba 4.13"));
+    }
+
+    #[test]
+    fn previews_show_what_a_remote_model_would_get() {
+        let (_dir, store) = temp_store();
+        store.update(|db| {
+            let mut workspace = Workspace::new(1, "Lab".to_string(), "10.0.0.0/24".to_string());
+            let mut machine = Machine::new(1, "dc01".to_string(), "10.0.0.5".to_string());
+            machine.ports.push(test_port(445, "smb", "", vec![PortNotes::Credentials { id: String::new(), name: Some("svc_backup".to_string()), hash: None, password: Some("Winter2024".to_string()), author: None }]));
+            workspace.data.push(machine);
+            workspace.redaction = RedactionPolicy::LocalOnly;
+            db.data.push(workspace);
+            Ok(())
+        }).unwrap();
+        let backend = Backend::new("http://127.0.0.1:8084");
+        let chat = ChatContext { type_: "port".to_string(), workspace_id: 1, machine_id: Some(1), port_number: Some(445) };
+        assert!(!preview_prompt(&store, &backend, None, "Does Winter2024 work on WinRM?", &chat).unwrap().local);
+
+        backend.set_ai_settings(AiSettings { sidecar_is_local: true, ..AiSettings::default() }).unwrap();
+        let local = preview_prompt(&store, &backend, None, "Does Winter2024 work on WinRM?", &chat).unwrap();
+        assert!(local.local);
+        assert!(local.body["question"].as_str().unwrap().contains("- Password: Winter2024"));
+
+        backend.set_ai_settings(AiSettings { provider: llm::Provider::OpenAi, model: "gpt-4o".to_string(), ..AiSettings::default() }).unwrap();
+        let remote = preview_prompt(&store, &backend, None, "Does Winter2024 work on WinRM?", &chat).unwrap();
+        assert!(!remote.local);
+        assert_eq!(remote.url, "https://api.openai.com/v1/chat/completions");
+        let sent = remote.body.to_string();
+        assert!(!sent.contains("Winter2024"));
+        assert!(sent.contains("Does [redacted] work on WinRM?"));
+    }
+
+    fn serve(app: Router) -> Backend {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(api::serve_router(listener, app, std::future::pending()));
+        Backend::new(&url)
+    }
+
+    // A sidecar that streams the first piece of its answer and then never finishes
+    fn stalling_backend() -> Backend {
+        serve(Router::new().route("/ask", post(|| async {
+            let first = stream::iter([Ok::<_, Infallible>(Bytes::from("{\"response\":\"Check anonymous \"}\n"))]);
+            axum::response::Response::builder()
+                .header("content-type", "application/x-ndjson")
+                .body(Body::from_stream(first.chain(stream::pending())))
+                .unwrap()
+        })))
+    }
+
+    // Answers with the prompt it was sent
+    fn echo_backend() -> Backend {
+        serve(Router::new().route("/ask", post(|Json(body): Json<serde_json::Value>| async move {
+            Json(serde_json::json!({ "text": body["question"] }))
+        })))
+    }
+
+    #[test]
+    fn cancelled_answers_are_kept_in_the_conversation() {
+        let (_dir, store) = temp_store();
+        let requests = AiRequests::default();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let chat = ChatContext { type_: "workspace".to_string(), workspace_id: 1, machine_id: None, port_number: None };
+        let (reply, follow_up) = runtime.block_on(async {
+            let reply = ask_question(&store, &stalling_backend(), &requests, "chat-1", None, "What next?", &chat, |text| {
+                assert_eq!(text, "Check anonymous ");
+                assert!(requests.cancel("chat-1").unwrap());
+            }).await.unwrap();
+            let follow_up = ask_question(&store, &echo_backend(), &requests, "chat-2", Some(&reply.conversation_id), "And then?", &chat, |_| {})
+                .await
+                .unwrap();
+            (reply, follow_up)
+        });
+        assert_eq!(reply.text, "Check anonymous ");
+        assert!(!requests.cancel("chat-1").unwrap());
+
+        // The follow-up was asked with the earlier exchange
+        assert_eq!(follow_up.conversation_id, reply.conversation_id);
+        assert!(follow_up.text.contains("User: What next?\n\nAssistant: Check anonymous"));
+        let saved = store.conversations(1, |conversations| Ok(chat::get(conversations, &reply.conversation_id)?.clone())).unwrap();
+        assert_eq!(saved.messages.len(), 4);
+        assert_eq!(saved.title, "What next?");
+    }
+}
@@ -0,0 +1,85 @@
+use std::fs;
+
+use crate::chat::{self, Conversation, ConversationSummary};
+use crate::error::{AppError, AppResult};
+use crate::store::Store;
+
+// Most recently used first, optionally only those about one machine or port
+pub(crate) fn list(store: &Store, workspace_id: u32, machine_id: Option<u32>, port_number: Option<u16>) -> AppResult<Vec<ConversationSummary>> {
+    store.read(|db| db.workspace(workspace_id).map(|_| ()))?;
+    store.conversations(workspace_id, |conversations| {
+        let mut list: Vec<ConversationSummary> = conversations.iter()
+            .filter(|conversation| machine_id.is_none() || conversation.machine_id == machine_id)
+            .filter(|conversation| port_number.is_none() || conversation.port_number == port_number)
+            .map(Conversation::summary)
+            .collect();
+        list.sort_by(|a, b| b.updated.cmp(&a.updated));
+        Ok(list)
+    })
+}
+
+pub(crate) fn get(store: &Store, workspace_id: u32, conversation_id: &str) -> AppResult<Conversation> {
+    store.conversations(workspace_id, |conversations| Ok(chat::get(conversations, conversation_id)?.clone()))
+}
+
+pub(crate) fn rename(store: &Store, workspace_id: u32, conversation_id: &str, title: String) -> AppResult<()> {
+    let title = title.trim().to_string();
+    if title.is_empty() {
+        return Err(AppError::validation("Conversation title cannot be empty"));
+    }
+    store.update_conversations(workspace_id, |conversations| {
+        chat::find(conversations, conversation_id)?.title = title;
+        Ok(())
+    })
+}
+
+pub(crate) fn delete(store: &Store, workspace_id: u32, conversation_id: &str) -> AppResult<()> {
+    store.update_conversations(workspace_id, |conversations| {
+        chat::get(conversations, conversation_id)?;
+        conversations.retain(|conversation| conversation.id != conversation_id);
+        Ok(())
+    })
+}
+
+// Writes the conversation to `path` as Markdown
+pub(crate) fn export(store: &Store, workspace_id: u32, conversation_id: &str, path: &str) -> AppResult<()> {
+    let markdown = chat::to_markdown(&get(store, workspace_id, conversation_id)?);
+    fs::write(path, markdown).map_err(|e| AppError::storage("Failed to write conversation", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::Role;
+    use crate::services::workspaces;
+    use crate::store::tests::temp_store;
+    use crate::ChatContext;
+
+    #[test]
+    fn conversations_can_be_renamed_exported_and_deleted() {
+        let (dir, store) = temp_store();
+        let lab = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        let chat = ChatContext { type_: "workspace".to_string(), workspace_id: lab, machine_id: None, port_number: None };
+        let mut conversation = Conversation::new(&chat, "Where do I start?");
+        conversation.push(Role::User, "Where do I start?".to_string(), None);
+        conversation.push(Role::Assistant, "With a ping sweep".to_string(), None);
+        let id = conversation.id.clone();
+        store.update_conversations(lab, |conversations| {
+            conversations.push(conversation);
+            Ok(())
+        }).unwrap();
+
+        rename(&store, lab, &id, "Recon plan".to_string()).unwrap();
+        assert_eq!(list(&store, lab, None, None).unwrap()[0].title, "Recon plan");
+        assert!(list(&store, lab, Some(1), None).unwrap().is_empty());
+        assert!(list(&store, lab, None, Some(22)).unwrap().is_empty());
+
+        let path = dir.path().join("chat.md");
+        export(&store, lab, &id, path.to_str().unwrap()).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("With a ping sweep"));
+
+        delete(&store, lab, &id).unwrap();
+        assert!(matches!(get(&store, lab, &id), Err(AppError::NotFound { .. })));
+        assert!(rename(&store, lab, &id, "Gone".to_string()).is_err());
+    }
+}
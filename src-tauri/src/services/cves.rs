@@ -0,0 +1,83 @@
+use serde_json::json;
+
+use crate::audit;
+use crate::cve::{self, CveFeedStatus, CveIndex};
+use crate::error::{AppError, AppResult};
+use crate::store::Store;
+
+// Imports into a copy so a failed import or save leaves the index in use untouched
+fn update_index(store: &Store, f: impl FnOnce(&mut CveIndex) -> AppResult<usize>) -> AppResult<usize> {
+    let mut current = store.cve_index()?;
+    let mut index = current.clone();
+    let count = f(&mut index)?;
+    cve::save_index(store.dir(), &index)?;
+    *current = index;
+    Ok(count)
+}
+
+pub(crate) fn import_feed(store: &Store, path: &str) -> AppResult<usize> {
+    update_index(store, |index| index.import_feed(path))
+}
+
+pub(crate) fn import_dictionary(store: &Store, path: &str) -> AppResult<usize> {
+    update_index(store, |index| index.import_dictionary(path))
+}
+
+pub(crate) fn status(store: &Store) -> AppResult<CveFeedStatus> {
+    Ok(store.cve_index()?.status())
+}
+
+// Re-matches every port of the workspace (or one machine), returns the number of candidates
+pub(crate) fn match_cves(store: &Store, workspace_id: u32, machine_id: Option<u32>) -> AppResult<usize> {
+    let index = store.cve_index()?;
+    if index.is_empty() {
+        return Err(AppError::validation("No CVE feed imported"));
+    }
+
+    store.update_audited(|db, changes| {
+        let workspace = db.workspace_mut(workspace_id)?;
+        let mut matched = 0;
+        for machine in workspace.data.iter_mut().filter(|machine| machine_id.is_none_or(|id| machine.id == id)) {
+            for port in machine.ports.iter_mut() {
+                port.cves = index.match_port(port);
+                matched += port.cves.len();
+            }
+        }
+        changes.record(workspace_id, "match_cves", format!("workspace:{}", workspace_id), None,
+            audit::to_value(&json!({ "machine_id": machine_id, "matched": matched })));
+        Ok(matched)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::temp_store;
+
+    fn feed(dir: &std::path::Path, id: &str) -> String {
+        let path = dir.join(format!("{}.json", id));
+        let feed = json!({ "vulnerabilities": [{ "cve": {
+            "id": id,
+            "configurations": [{ "nodes": [{ "cpeMatch": [{
+                "vulnerable": true,
+                "criteria": "cpe:2.3:a:f5:nginx:*:*:*:*:*:*:*:*",
+                "versionEndExcluding": "1.21",
+            }] }] }],
+        } }] });
+        std::fs::write(&path, feed.to_string()).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn failed_saves_leave_the_index_alone() {
+        let (dir, store) = temp_store();
+        assert_eq!(import_feed(&store, &feed(dir.path(), "CVE-2021-23017")).unwrap(), 1);
+
+        // A directory in the way of the index file makes the save fail
+        let index = dir.path().join("cve_index.json");
+        std::fs::remove_file(&index).unwrap();
+        std::fs::create_dir(&index).unwrap();
+        assert!(import_feed(&store, &feed(dir.path(), "CVE-2013-2028")).is_err());
+        assert_eq!(status(&store).unwrap().cves, 1);
+    }
+}
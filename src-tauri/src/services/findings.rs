@@ -0,0 +1,156 @@
+use crate::audit;
+use crate::error::{AppError, AppResult, Entity};
+use crate::findings::{self, Finding, FindingInput, FindingStatus, FindingSummary, Severity};
+use crate::store::Store;
+use crate::PortNotes;
+
+// Most severe first
+pub(crate) fn list(store: &Store, workspace_id: u32, severity: Option<Severity>, status: Option<FindingStatus>) -> AppResult<Vec<Finding>> {
+    store.read(|db| {
+        let mut findings: Vec<Finding> = db.workspace(workspace_id)?.findings.iter()
+            .filter(|finding| severity.is_none_or(|s| finding.severity == s))
+            .filter(|finding| status.is_none_or(|s| finding.status == s))
+            .cloned()
+            .collect();
+        findings.sort_by(|a, b| a.severity.cmp(&b.severity).then_with(|| a.id.cmp(&b.id)));
+        Ok(findings)
+    })
+}
+
+pub(crate) fn get(store: &Store, workspace_id: u32, finding_id: u32) -> AppResult<Finding> {
+    store.read(|db| {
+        db.workspace(workspace_id).ok()
+            .and_then(|workspace| workspace.findings.iter().find(|finding| finding.id == finding_id))
+            .cloned()
+            .ok_or_else(|| AppError::not_found(Entity::Finding, finding_id))
+    })
+}
+
+pub(crate) fn add(store: &Store, workspace_id: u32, input: FindingInput) -> AppResult<u32> {
+    store.update_audited(|db, changes| {
+        let workspace = db.workspace_mut(workspace_id)?;
+        findings::validate(workspace, &input)?;
+        let mut finding = Finding {
+            id: findings::next_id(workspace),
+            title: String::new(),
+            severity: Severity::Info,
+            cvss_vector: None,
+            cvss_score: None,
+            affected: vec![],
+            description: String::new(),
+            evidence: String::new(),
+            remediation: String::new(),
+            status: FindingStatus::Open,
+        };
+        findings::apply(&mut finding, input)?;
+        let id = finding.id;
+        changes.record(workspace_id, "create", format!("finding:{}", id), None, audit::to_value(&finding));
+        workspace.findings.push(finding);
+        Ok(id)
+    })
+}
+
+pub(crate) fn update(store: &Store, workspace_id: u32, finding_id: u32, input: FindingInput) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let workspace = db.workspace_mut(workspace_id)?;
+        findings::validate(workspace, &input)?;
+        let existing = workspace.findings.iter_mut().find(|f| f.id == finding_id)
+            .ok_or_else(|| AppError::not_found(Entity::Finding, finding_id))?;
+        let before = audit::to_value(existing);
+        findings::apply(existing, input)?;
+        changes.record(workspace_id, "update", format!("finding:{}", finding_id), before, audit::to_value(existing));
+        Ok(())
+    })
+}
+
+// Notes referring to the finding go with it
+pub(crate) fn delete(store: &Store, workspace_id: u32, finding_id: u32) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let workspace = db.workspace_mut(workspace_id)?;
+        let index = workspace.findings.iter().position(|finding| finding.id == finding_id)
+            .ok_or_else(|| AppError::not_found(Entity::Finding, finding_id))?;
+        let finding = workspace.findings.remove(index);
+        for port in workspace.data.iter_mut().flat_map(|machine| machine.ports.iter_mut()) {
+            port.data.retain(|note| !matches!(note, PortNotes::FindingRef { finding_id: id, .. } if *id == finding_id));
+        }
+        changes.record(workspace_id, "delete", format!("finding:{}", finding_id), audit::to_value(&finding), None);
+        Ok(())
+    })
+}
+
+pub(crate) fn summary(store: &Store, workspace_id: u32) -> AppResult<FindingSummary> {
+    store.read(|db| Ok(findings::summarize(db.workspace(workspace_id)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::ApiPort;
+    use crate::findings::tests::input;
+    use crate::findings::AffectedAsset;
+    use crate::services::{machines, ports, scans, workspaces};
+    use crate::store::tests::temp_store;
+
+    fn lab() -> (tempfile::TempDir, Store) {
+        let (dir, store) = temp_store();
+        workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        machines::add(&store, 1, "web".to_string(), "10.0.0.2".to_string()).unwrap();
+        let port = ApiPort {
+            service: "http".to_string(),
+            application: "nginx".to_string(),
+            protocol: "tcp".to_string(),
+            number: 80,
+            state: "open".to_string(),
+            data: None,
+            details: None,
+        };
+        scans::apply_scan(&store, 1, 1, vec![port]).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn findings_lifecycle() {
+        let (_dir, store) = lab();
+        let low = add(&store, 1, input("Server banner", Some(Severity::Low), None)).unwrap();
+        let critical = add(&store, 1, input("Unauthenticated RCE", None, Some("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"))).unwrap();
+        assert!(add(&store, 1, input("", Some(Severity::Low), None)).is_err());
+        assert!(add(&store, 9, input("Server banner", Some(Severity::Low), None)).is_err());
+
+        let listed: Vec<u32> = list(&store, 1, None, None).unwrap().iter().map(|finding| finding.id).collect();
+        assert_eq!(listed, [critical, low]);
+        assert_eq!(list(&store, 1, Some(Severity::Low), None).unwrap().len(), 1);
+
+        let mut edited = input("Server banner", Some(Severity::Info), None);
+        edited.affected = vec![AffectedAsset { machine_id: 1, port_number: Some(80) }];
+        edited.status = Some(FindingStatus::Remediated);
+        update(&store, 1, low, edited).unwrap();
+        let finding = get(&store, 1, low).unwrap();
+        assert_eq!(finding.severity, Severity::Info);
+        assert_eq!(finding.affected.len(), 1);
+        assert_eq!(list(&store, 1, None, Some(FindingStatus::Remediated)).unwrap().len(), 1);
+        assert!(matches!(update(&store, 1, 7, input("Missing", Some(Severity::Low), None)), Err(AppError::NotFound { .. })));
+
+        let summary = summary(&store, 1).unwrap();
+        assert_eq!((summary.total, summary.unresolved.critical, summary.remediated), (2, 1, 1));
+        assert_eq!(store.audit_entries(1, Some(&format!("finding:{}", low)), None, None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn deleted_findings_take_their_references_along() {
+        let (_dir, store) = lab();
+        let first = add(&store, 1, input("Server banner", Some(Severity::Low), None)).unwrap();
+        let second = add(&store, 1, input("Directory listing", Some(Severity::Medium), None)).unwrap();
+        let mut notes = ports::get(&store, 1, 1, 80).unwrap().data;
+        notes.push(PortNotes::FindingRef { id: String::new(), finding_id: second });
+        ports::update_notes(&store, 1, 1, 80, notes).unwrap();
+
+        delete(&store, 1, second).unwrap();
+        assert!(matches!(get(&store, 1, second), Err(AppError::NotFound { .. })));
+        assert!(!ports::get(&store, 1, 1, 80).unwrap().data.iter().any(|note| matches!(note, PortNotes::FindingRef { .. })));
+        assert!(delete(&store, 1, second).is_err());
+
+        // The highest ID isn't handed out again
+        assert_eq!(add(&store, 1, input("Weak TLS", Some(Severity::Low), None)).unwrap(), 3);
+        assert_eq!(get(&store, 1, first).unwrap().title, "Server banner");
+    }
+}
@@ -0,0 +1,272 @@
+use serde_json::json;
+
+use crate::audit;
+use crate::compromise::{self, AccessLevel, CompromiseSummary, Evidence, StatusChange};
+use crate::error::{AppError, AppResult, Entity};
+use crate::fingerprint::{self, OsFingerprint};
+use crate::store::Store;
+use crate::{tags, JsMachine, Machine};
+
+// An unknown workspace simply has no machines
+pub(crate) fn list(store: &Store, workspace_id: u32, tag: Option<&str>) -> AppResult<Vec<JsMachine>> {
+    store.read(|db| {
+        Ok(db.workspace(workspace_id).map(|workspace| {
+            workspace.data.iter()
+                .filter(|machine| tags::has_tag(&machine.tags, tag))
+                .map(JsMachine::from)
+                .collect()
+        }).unwrap_or_default())
+    })
+}
+
+pub(crate) fn get(store: &Store, workspace_id: u32, machine_id: u32) -> AppResult<JsMachine> {
+    store.read(|db| db.machine(workspace_id, machine_id).map(JsMachine::from))
+}
+
+pub(crate) fn add(store: &Store, workspace_id: u32, hostname: String, ip: String) -> AppResult<u32> {
+    store.update_audited(|db, changes| {
+        let workspace = db.workspace_mut(workspace_id)?;
+        let machine = Machine::new(workspace.next_machine_id(), hostname, ip);
+        let id = machine.id;
+        changes.record(workspace_id, "create", format!("machine:{}", id), None, audit::to_value(&machine));
+        workspace.data.push(machine);
+        Ok(id)
+    })
+}
+
+// Fields left out keep their value
+pub(crate) fn update(store: &Store, workspace_id: u32, machine_id: u32, hostname: Option<String>, ip: Option<String>) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let machine = db.machine_mut(workspace_id, machine_id)?;
+        let before = audit::to_value(&json!({ "hostname": machine.hostname, "ip": machine.ip }));
+        if let Some(hostname) = hostname {
+            machine.hostname = hostname;
+        }
+        if let Some(ip) = ip {
+            machine.ip = ip;
+        }
+        let after = audit::to_value(&json!({ "hostname": machine.hostname, "ip": machine.ip }));
+        changes.record(workspace_id, "update", format!("machine:{}", machine_id), before, after);
+        Ok(())
+    })
+}
+
+// Findings and status changes stop referring to the machine
+pub(crate) fn delete(store: &Store, workspace_id: u32, machine_id: u32) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let workspace = db.workspace_mut(workspace_id)?;
+        let index = workspace.data.iter().position(|machine| machine.id == machine_id)
+            .ok_or_else(|| AppError::not_found(Entity::Machine, machine_id))?;
+        let machine = workspace.data.remove(index);
+        for finding in workspace.findings.iter_mut() {
+            finding.affected.retain(|asset| asset.machine_id != machine_id);
+        }
+        for change in workspace.data.iter_mut().flat_map(|machine| machine.status_history.iter_mut()) {
+            if matches!(change.evidence, Some(Evidence::Credential { machine_id: evidence_machine, .. }) if evidence_machine == machine_id) {
+                change.evidence = None;
+            }
+        }
+        changes.record(workspace_id, "delete", format!("machine:{}", machine_id), audit::to_value(&machine), None);
+        Ok(())
+    })
+}
+
+pub(crate) fn fingerprint(store: &Store, workspace_id: u32, machine_id: u32) -> AppResult<Option<OsFingerprint>> {
+    store.update_audited(|db, changes| {
+        let machine = db.machine_mut(workspace_id, machine_id)?;
+        let before = audit::to_value(&json!({ "icon": machine.icon, "fingerprint": machine.fingerprint }));
+        fingerprint::refresh(machine);
+        let after = audit::to_value(&json!({ "icon": machine.icon, "fingerprint": machine.fingerprint }));
+        changes.record(workspace_id, "update", format!("machine:{}", machine_id), before, after);
+        Ok(machine.fingerprint.clone())
+    })
+}
+
+// Pass no icon to go back to the detected one
+pub(crate) fn set_icon(store: &Store, workspace_id: u32, machine_id: u32, icon: Option<String>) -> AppResult<()> {
+    if let Some(icon) = &icon {
+        if !fingerprint::ICONS.contains(&icon.as_str()) {
+            return Err(AppError::validation(format!("Unknown icon '{}', expected one of {}", icon, fingerprint::ICONS.join(", "))));
+        }
+    }
+    store.update_audited(|db, changes| {
+        let machine = db.machine_mut(workspace_id, machine_id)?;
+        let before = audit::to_value(&machine.icon);
+        machine.icon_override = icon;
+        fingerprint::refresh(machine);
+        changes.record(workspace_id, "update", format!("machine:{}", machine_id), before, audit::to_value(&machine.icon));
+        Ok(())
+    })
+}
+
+pub(crate) fn set_status(
+    store: &Store,
+    workspace_id: u32,
+    machine_id: u32,
+    status: AccessLevel,
+    evidence: Option<Evidence>,
+    comment: Option<String>,
+) -> AppResult<StatusChange> {
+    store.update_audited(|db, changes| {
+        let change = compromise::set_status(db.workspace_mut(workspace_id)?, machine_id, status, evidence, comment)?;
+        changes.record(workspace_id, "status", format!("machine:{}", machine_id),
+            audit::to_value(&change.from), audit::to_value(&change));
+        Ok(change)
+    })
+}
+
+pub(crate) fn status_history(store: &Store, workspace_id: u32, machine_id: u32) -> AppResult<Vec<StatusChange>> {
+    store.read(|db| Ok(db.machine(workspace_id, machine_id)?.status_history.clone()))
+}
+
+pub(crate) fn compromise_summary(store: &Store, workspace_id: u32) -> AppResult<CompromiseSummary> {
+    store.read(|db| Ok(compromise::summarize(db.workspace(workspace_id)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::findings::{AffectedAsset, Finding, FindingStatus, Severity};
+    use crate::notes;
+    use crate::services::{ports, workspaces};
+    use crate::store::tests::{temp_store, test_port};
+
+    #[test]
+    fn add_and_list_machines() {
+        let (_dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        add(&store, workspace_id, "dc01".to_string(), "10.0.0.5".to_string()).unwrap();
+        let id = add(&store, workspace_id, "web".to_string(), "10.0.0.8".to_string()).unwrap();
+
+        assert_eq!(id, 2);
+        let machine = get(&store, workspace_id, id).unwrap();
+        assert_eq!(machine.ip, "10.0.0.8");
+        assert_eq!(machine.icon, fingerprint::DEFAULT_ICON);
+        assert_eq!(list(&store, workspace_id, None).unwrap().len(), 2);
+        assert!(list(&store, 9, None).unwrap().is_empty());
+        assert!(add(&store, 9, "x".to_string(), "10.0.0.9".to_string()).is_err());
+    }
+
+    #[test]
+    fn update_and_delete() {
+        let (_dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        let id = add(&store, workspace_id, "dc01".to_string(), "10.0.0.5".to_string()).unwrap();
+
+        update(&store, workspace_id, id, Some("dc02".to_string()), None).unwrap();
+        assert_eq!(get(&store, workspace_id, id).unwrap().hostname, "dc02");
+        delete(&store, workspace_id, id).unwrap();
+        assert!(matches!(get(&store, workspace_id, id), Err(AppError::NotFound { .. })));
+        assert_eq!(add(&store, workspace_id, "web".to_string(), "10.0.0.8".to_string()).unwrap(), 2);
+    }
+
+    #[test]
+    fn audit_filter_matches_whole_ids() {
+        let (_dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        for number in 1..=10 {
+            add(&store, workspace_id, format!("host{}", number), format!("10.0.0.{}", number)).unwrap();
+        }
+
+        let entries = store.audit_entries(workspace_id, Some("machine:1"), None, None).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].object, "machine:1");
+        assert_eq!(store.audit_entries(workspace_id, Some("machine:"), None, None).unwrap().len(), 10);
+    }
+
+    #[test]
+    fn deleting_a_machine_drops_references_to_it() {
+        let (_dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        let dc = add(&store, workspace_id, "dc01".to_string(), "10.0.0.5".to_string()).unwrap();
+        let web = add(&store, workspace_id, "web".to_string(), "10.0.0.8".to_string()).unwrap();
+        store.update(|db| {
+            let workspace = db.workspace_mut(workspace_id)?;
+            workspace.findings.push(Finding {
+                id: 1,
+                title: "SMB signing disabled".to_string(),
+                severity: Severity::Medium,
+                cvss_vector: None,
+                cvss_score: None,
+                affected: [dc, web].iter().map(|&machine_id| AffectedAsset { machine_id, port_number: None }).collect(),
+                description: String::new(),
+                evidence: String::new(),
+                remediation: String::new(),
+                status: FindingStatus::Open,
+            });
+            workspace.machine_mut(dc)?.status_history.push(StatusChange {
+                from: AccessLevel::Untouched,
+                to: AccessLevel::User,
+                timestamp: String::new(),
+                operator: String::new(),
+                evidence: Some(Evidence::Credential { machine_id: web, port_number: 80, credential_id: "c1".to_string() }),
+                comment: None,
+            });
+            Ok(())
+        }).unwrap();
+
+        // The highest ID isn't handed out again either
+        delete(&store, workspace_id, web).unwrap();
+        store.read(|db| {
+            let workspace = db.workspace(workspace_id)?;
+            assert_eq!(workspace.findings[0].affected.len(), 1);
+            assert!(workspace.machine(dc)?.status_history[0].evidence.is_none());
+            Ok(())
+        }).unwrap();
+        assert_eq!(add(&store, workspace_id, "mail".to_string(), "10.0.0.9".to_string()).unwrap(), 3);
+    }
+
+    #[test]
+    fn rejects_unknown_icon() {
+        let (_dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        let id = add(&store, workspace_id, "dc01".to_string(), "10.0.0.5".to_string()).unwrap();
+
+        assert!(matches!(set_icon(&store, workspace_id, id, Some("toaster".to_string())), Err(AppError::Validation(_))));
+        set_icon(&store, workspace_id, id, Some(fingerprint::ICONS[1].to_string())).unwrap();
+        assert_eq!(get(&store, workspace_id, id).unwrap().icon, fingerprint::ICONS[1]);
+    }
+
+    #[test]
+    fn fingerprinting_is_audited() {
+        let (_dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        let id = add(&store, workspace_id, "dc01".to_string(), "10.0.0.5".to_string()).unwrap();
+
+        assert!(fingerprint(&store, workspace_id, id).unwrap().is_none());
+        let entries = store.audit_entries(workspace_id, Some(&format!("machine:{}", id)), Some("update"), None).unwrap();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn status_changes_are_recorded() {
+        let (_dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        let id = add(&store, workspace_id, "dc01".to_string(), "10.0.0.5".to_string()).unwrap();
+
+        set_status(&store, workspace_id, id, AccessLevel::User, None, Some("phished".to_string())).unwrap();
+        assert!(set_status(&store, workspace_id, id, AccessLevel::User, None, None).is_err());
+        let history = status_history(&store, workspace_id, id).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].comment.as_deref(), Some("phished"));
+    }
+
+    #[test]
+    fn credential_evidence_names_the_exact_credential() {
+        let (_dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        let id = add(&store, workspace_id, "web".to_string(), "10.0.0.8".to_string()).unwrap();
+        store.update(|db| {
+            db.machine_mut(workspace_id, id)?.ports.push(test_port(22, "ssh", "", vec![]));
+            Ok(())
+        }).unwrap();
+        ports::add_credentials(&store, workspace_id, id, 22, Some("root".to_string()), None, Some("toor".to_string())).unwrap();
+        let credential_id = notes::port_note_id(ports::get(&store, workspace_id, id, 22).unwrap().data.last().unwrap()).unwrap().to_string();
+
+        let evidence = |credential_id: &str| Some(Evidence::Credential { machine_id: id, port_number: 22, credential_id: credential_id.to_string() });
+        for unknown in ["", "c1"] {
+            assert!(matches!(set_status(&store, workspace_id, id, AccessLevel::Root, evidence(unknown), None), Err(AppError::NotFound { .. })));
+        }
+        set_status(&store, workspace_id, id, AccessLevel::Root, evidence(&credential_id), None).unwrap();
+    }
+}
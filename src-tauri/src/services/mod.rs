@@ -0,0 +1,57 @@
+// Operations behind the Tauri commands, usable without Tauri: each takes the Store it works on
+// (and the Backend when it talks to the scanning service) and records its own audit entries
+pub(crate) mod ai;
+pub(crate) mod chat;
+pub(crate) mod cves;
+pub(crate) mod findings;
+pub(crate) mod machines;
+pub(crate) mod notes;
+pub(crate) mod ports;
+pub(crate) mod reports;
+pub(crate) mod scans;
+pub(crate) mod tags;
+pub(crate) mod workspaces;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::parse_scan;
+    use crate::findings::{AffectedAsset, FindingInput, Severity};
+    use crate::report::{ReportFormat, ReportOptions};
+    use crate::store::{tests::temp_store, Store};
+
+    // The path an engagement takes through the app, checked again after reopening the store
+    #[test]
+    fn engagement_flow() {
+        let (dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Acme".to_string(), "10.10.0.0/24".to_string()).unwrap();
+        let machine_id = machines::add(&store, workspace_id, "files01".to_string(), "10.10.0.20".to_string()).unwrap();
+        let scan = r#"[{"service":"microsoft-ds","application":"Samba smbd 4.6.2","protocol":"tcp","number":445,"state":"open","data":null,"details":null}]"#;
+        scans::apply_scan(&store, workspace_id, machine_id, parse_scan(scan).unwrap()).unwrap();
+
+        ports::add_note(&store, workspace_id, machine_id, 445, "Enumeration".to_string(), "Guest share readable".to_string()).unwrap();
+        notes::add(&store, workspace_id, Some(machine_id), "Exploitation".to_string(), "Dumped SAM".to_string()).unwrap();
+        let finding_id = findings::add(&store, workspace_id, FindingInput {
+            title: "Anonymous SMB access".to_string(),
+            severity: Some(Severity::High),
+            cvss_vector: None,
+            cvss_score: None,
+            affected: vec![AffectedAsset { machine_id, port_number: Some(445) }],
+            description: String::new(),
+            evidence: String::new(),
+            remediation: String::new(),
+            status: None,
+        }).unwrap();
+
+        let reopened = Store::open(dir.path()).unwrap();
+        let report = reports::generate(&reopened, workspace_id, ReportFormat::Markdown, ReportOptions::default()).unwrap();
+        assert!(report.contains("files01"));
+        assert!(report.contains("Guest share readable"));
+        assert_eq!(findings::get(&reopened, workspace_id, finding_id).unwrap().title, "Anonymous SMB access");
+        assert_eq!(workspaces::search(&reopened, "guest", None).unwrap().len(), 1);
+
+        let actions: Vec<String> = workspaces::audit_log(&reopened, workspace_id, None, None, None).unwrap()
+            .into_iter().map(|entry| entry.action).collect();
+        assert_eq!(actions, ["create", "create", "create", "scan", "create", "create"]);
+    }
+}
@@ -0,0 +1,126 @@
+use serde_json::json;
+
+use crate::audit;
+use crate::error::{AppError, AppResult, Entity};
+use crate::notes::{self, RevisionView};
+use crate::store::Store;
+use crate::{tags, Database, Note};
+
+// General notes of the machine when one is given, otherwise of the workspace
+fn notes_of(db: &Database, workspace_id: u32, machine_id: Option<u32>) -> AppResult<&Vec<Note>> {
+    match machine_id {
+        Some(machine_id) => Ok(&db.machine(workspace_id, machine_id)?.notes),
+        None => Ok(&db.workspace(workspace_id)?.notes),
+    }
+}
+
+fn notes_of_mut(db: &mut Database, workspace_id: u32, machine_id: Option<u32>) -> AppResult<&mut Vec<Note>> {
+    match machine_id {
+        Some(machine_id) => Ok(&mut db.machine_mut(workspace_id, machine_id)?.notes),
+        None => Ok(&mut db.workspace_mut(workspace_id)?.notes),
+    }
+}
+
+pub(crate) fn list(store: &Store, workspace_id: u32, machine_id: Option<u32>, tag: Option<&str>) -> AppResult<Vec<Note>> {
+    store.read(|db| {
+        Ok(notes_of(db, workspace_id, machine_id)?.iter()
+            .filter(|note| tags::has_tag(&note.tags, tag))
+            .cloned()
+            .collect())
+    })
+}
+
+pub(crate) fn add(store: &Store, workspace_id: u32, machine_id: Option<u32>, stage: String, content: String) -> AppResult<String> {
+    notes::validate(&stage, &content)?;
+    let note = notes::new_note(stage, content);
+    let id = note.id.clone();
+    store.update_audited(|db, changes| {
+        changes.record(workspace_id, "create", format!("note:{}", id), None, audit::to_value(&note));
+        notes_of_mut(db, workspace_id, machine_id)?.push(note);
+        Ok(())
+    })?;
+    Ok(id)
+}
+
+pub(crate) fn update(store: &Store, workspace_id: u32, machine_id: Option<u32>, note_id: &str, stage: String, content: String) -> AppResult<()> {
+    notes::validate(&stage, &content)?;
+    store.update_audited(|db, changes| {
+        let note = notes_of_mut(db, workspace_id, machine_id)?.iter_mut().find(|note| note.id == note_id)
+            .ok_or_else(|| AppError::not_found(Entity::Note, note_id))?;
+        let before = audit::to_value(note);
+        notes::note_fields(note).revise(stage, content);
+        changes.record(workspace_id, "update", format!("note:{}", note_id), before, audit::to_value(note));
+        Ok(())
+    })
+}
+
+pub(crate) fn delete(store: &Store, workspace_id: u32, machine_id: Option<u32>, note_id: &str) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let notes = notes_of_mut(db, workspace_id, machine_id)?;
+        let index = notes.iter().position(|note| note.id == note_id)
+            .ok_or_else(|| AppError::not_found(Entity::Note, note_id))?;
+        changes.record(workspace_id, "delete", format!("note:{}", note_id), audit::to_value(&notes.remove(index)), None);
+        Ok(())
+    })
+}
+
+// Works for workspace, machine and port notes alike
+pub(crate) fn revisions(store: &Store, workspace_id: u32, note_id: &str) -> AppResult<Vec<RevisionView>> {
+    store.read(|db| {
+        let note = notes::view_in_workspace(db.workspace(workspace_id)?, note_id)
+            .ok_or_else(|| AppError::not_found(Entity::Note, note_id))?;
+        Ok(note.history())
+    })
+}
+
+pub(crate) fn restore(store: &Store, workspace_id: u32, note_id: &str, revision: usize) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let mut note = notes::find_in_workspace(db.workspace_mut(workspace_id)?, note_id)
+            .ok_or_else(|| AppError::not_found(Entity::Note, note_id))?;
+        let before = audit::to_value(&json!({ "stage": note.stage, "content": note.content }));
+        note.restore(revision)?;
+        let after = audit::to_value(&json!({ "stage": note.stage, "content": note.content, "revision": revision }));
+        changes.record(workspace_id, "restore", format!("note:{}", note_id), before, after);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::{machines, workspaces};
+    use crate::store::tests::temp_store;
+
+    #[test]
+    fn workspace_and_machine_notes_are_separate() {
+        let (_dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        let machine_id = machines::add(&store, workspace_id, "dc01".to_string(), "10.0.0.5".to_string()).unwrap();
+
+        add(&store, workspace_id, None, "Planning".to_string(), "Scope agreed".to_string()).unwrap();
+        let id = add(&store, workspace_id, Some(machine_id), "Enumeration".to_string(), "SMB signing off".to_string()).unwrap();
+
+        assert_eq!(list(&store, workspace_id, None, None).unwrap().len(), 1);
+        assert_eq!(list(&store, workspace_id, Some(machine_id), None).unwrap()[0].id, id);
+        assert!(delete(&store, workspace_id, None, &id).is_err());
+        delete(&store, workspace_id, Some(machine_id), &id).unwrap();
+        assert!(list(&store, workspace_id, Some(machine_id), None).unwrap().is_empty());
+        assert!(list(&store, workspace_id, Some(42), None).is_err());
+    }
+
+    #[test]
+    fn edits_keep_revisions_that_can_be_restored() {
+        let (_dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        let id = add(&store, workspace_id, None, "Planning".to_string(), "first".to_string()).unwrap();
+        update(&store, workspace_id, None, &id, "Planning".to_string(), "second".to_string()).unwrap();
+
+        let history = revisions(&store, workspace_id, &id).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].content, "first");
+
+        restore(&store, workspace_id, &id, 0).unwrap();
+        assert_eq!(list(&store, workspace_id, None, None).unwrap()[0].content, "first");
+        assert_eq!(revisions(&store, workspace_id, &id).unwrap().len(), 2);
+    }
+}
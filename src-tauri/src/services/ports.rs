@@ -0,0 +1,462 @@
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::audit;
+use crate::compromise;
+use crate::error::{AppError, AppResult, Entity};
+use crate::fingerprint;
+use crate::notes;
+use crate::store::Store;
+use crate::{current_operator, now, tags, JsPort, Port, PortNotes};
+
+// A port reported by something other than the scanner, e.g. a script importing another tool's results
+#[derive(Deserialize)]
+pub(crate) struct PortInput {
+    #[serde(default = "default_protocol")]
+    pub protocol: String,
+    #[serde(default)]
+    pub service: String,
+    #[serde(default)]
+    pub application: String,
+    #[serde(default = "default_state")]
+    pub state: String,
+    // Stored as scan output, like the scanner's details
+    #[serde(default)]
+    pub details: Vec<String>,
+}
+
+fn default_protocol() -> String {
+    "tcp".to_string()
+}
+
+fn default_state() -> String {
+    "open".to_string()
+}
+
+// An unknown workspace or machine simply has no ports
+pub(crate) fn list(store: &Store, workspace_id: u32, machine_id: u32, tag: Option<&str>) -> AppResult<Vec<JsPort>> {
+    store.read(|db| {
+        Ok(db.machine(workspace_id, machine_id).map(|machine| {
+            machine.ports.iter()
+                .filter(|port| tags::has_tag(&port.tags, tag))
+                .map(JsPort::from)
+                .collect()
+        }).unwrap_or_default())
+    })
+}
+
+pub(crate) fn get(store: &Store, workspace_id: u32, machine_id: u32, port_number: u16) -> AppResult<JsPort> {
+    store.read(|db| db.port(workspace_id, machine_id, port_number).map(JsPort::from))
+}
+
+// Creates the port or updates its service fields; notes and tags of an existing port are kept.
+// Returns true when the port is new
+pub(crate) fn save(store: &Store, workspace_id: u32, machine_id: u32, port_number: u16, input: PortInput) -> AppResult<bool> {
+    let index = store.cve_index()?;
+    store.update_audited(|db, changes| {
+        let machine = db.machine_mut(workspace_id, machine_id)?;
+        let created = machine.port(port_number).is_err();
+        if created {
+            machine.ports.push(Port {
+                service: String::new(),
+                application: String::new(),
+                protocol: String::new(),
+                number: port_number,
+                state: String::new(),
+                data: vec![],
+                cves: vec![],
+                tags: vec![],
+            });
+        }
+        let port = machine.port_mut(port_number)?;
+        let before = if created { None } else { audit::to_value(&JsPort::from(&*port)) };
+        port.protocol = input.protocol;
+        port.service = input.service;
+        port.application = input.application;
+        port.state = input.state;
+        if !input.details.is_empty() {
+            port.data.push(PortNotes::NmapScan(input.details));
+        } else if port.data.is_empty() {
+            port.data.push(PortNotes::None);
+        }
+        if !index.is_empty() {
+            port.cves = index.match_port(port);
+        }
+        let after = audit::to_value(&JsPort::from(&*port));
+        compromise::mark_enumerated(machine);
+        fingerprint::refresh(machine);
+        let action = if created { "create" } else { "update" };
+        changes.record(workspace_id, action, format!("port:{}/{}", machine_id, port_number), before, after);
+        Ok(created)
+    })
+}
+
+pub(crate) fn delete(store: &Store, workspace_id: u32, machine_id: u32, port_number: u16) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let machine = db.machine_mut(workspace_id, machine_id)?;
+        let index = machine.ports.iter().position(|port| port.number == port_number)
+            .ok_or_else(|| AppError::not_found(Entity::Port, port_number))?;
+        let before = audit::to_value(&JsPort::from(&machine.ports.remove(index)));
+        fingerprint::refresh(machine);
+        changes.record(workspace_id, "delete", format!("port:{}/{}", machine_id, port_number), before, None);
+        Ok(())
+    })
+}
+
+// Replaces the port's notes with the client's list, keeping server-side metadata of known notes.
+// Notes added since the client read the port are lost, so adding goes through `add_note` and
+// `add_credentials` instead.
+pub(crate) fn update_notes(store: &Store, workspace_id: u32, machine_id: u32, port_number: u16, new_notes: Vec<PortNotes>) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let workspace = db.workspace_mut(workspace_id)?;
+        let finding_ids: Vec<u32> = workspace.findings.iter().map(|finding| finding.id).collect();
+        let port = workspace.machine_mut(machine_id)?.port_mut(port_number)?;
+        let before = audit::to_value(&port.data);
+        port.data = notes::merge_client_notes(&port.data, new_notes, &finding_ids)?;
+        changes.record(workspace_id, "update", format!("port:{}/{}", machine_id, port_number), before, audit::to_value(&port.data));
+        Ok(())
+    })
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.trim().is_empty())
+}
+
+fn validate_credentials(name: &Option<String>, hash: &Option<String>, password: &Option<String>) -> AppResult<()> {
+    if name.is_none() && hash.is_none() && password.is_none() {
+        return Err(AppError::validation("Credentials need a username, hash or password"));
+    }
+    Ok(())
+}
+
+// Stores credentials found on the service; at least one of the fields has to be set
+pub(crate) fn add_credentials(
+    store: &Store,
+    workspace_id: u32,
+    machine_id: u32,
+    port_number: u16,
+    name: Option<String>,
+    hash: Option<String>,
+    password: Option<String>,
+) -> AppResult<()> {
+    let (name, hash, password) = (non_empty(name), non_empty(hash), non_empty(password));
+    validate_credentials(&name, &hash, &password)?;
+    let entry = PortNotes::Credentials {
+        id: notes::new_note_id(),
+        name,
+        hash,
+        password,
+        author: Some(current_operator()),
+    };
+    store.update_audited(|db, changes| {
+        changes.record(workspace_id, "create", format!("port:{}/{}", machine_id, port_number), None, audit::to_value(&entry));
+        db.port_mut(workspace_id, machine_id, port_number)?.data.push(entry);
+        Ok(())
+    })
+}
+
+// Replaces the fields of recorded credentials; the audit log keeps the previous username but
+// not the secrets
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn edit_credentials(
+    store: &Store,
+    workspace_id: u32,
+    machine_id: u32,
+    port_number: u16,
+    note_id: &str,
+    name: Option<String>,
+    hash: Option<String>,
+    password: Option<String>,
+) -> AppResult<()> {
+    let (name, hash, password) = (non_empty(name), non_empty(hash), non_empty(password));
+    validate_credentials(&name, &hash, &password)?;
+    store.update_audited(|db, changes| {
+        let port = db.port_mut(workspace_id, machine_id, port_number)?;
+        let index = notes::position(port, note_id)?;
+        let note = &mut port.data[index];
+        let before = audit::to_value(&*note);
+        let PortNotes::Credentials { name: n, hash: h, password: p, .. } = note else {
+            return Err(AppError::validation("Only credentials can be edited this way"));
+        };
+        (*n, *h, *p) = (name, hash, password);
+        changes.record(workspace_id, "update", format!("note:{}", note_id), before, audit::to_value(&*note));
+        Ok(())
+    })
+}
+
+pub(crate) fn add_note(
+    store: &Store,
+    workspace_id: u32,
+    machine_id: u32,
+    port_number: u16,
+    stage: String,
+    content: String,
+) -> AppResult<String> {
+    notes::validate(&stage, &content)?;
+    let note = notes::new_port_note(stage, content);
+    let id = notes::port_note_id(&note).unwrap_or_default().to_string();
+    store.update_audited(|db, changes| {
+        changes.record(workspace_id, "create", format!("note:{}", id), None, audit::to_value(&note));
+        db.port_mut(workspace_id, machine_id, port_number)?.data.push(note);
+        Ok(())
+    })?;
+    Ok(id)
+}
+
+pub(crate) fn edit_note(
+    store: &Store,
+    workspace_id: u32,
+    machine_id: u32,
+    port_number: u16,
+    note_id: &str,
+    stage: Option<String>,
+    content: Option<String>,
+) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let port = db.port_mut(workspace_id, machine_id, port_number)?;
+        let index = notes::position(port, note_id)?;
+        let before = audit::to_value(&port.data[index]);
+        notes::edit_port_note(port, note_id, stage, content)?;
+        changes.record(workspace_id, "update", format!("note:{}", note_id), before, audit::to_value(&port.data[index]));
+        Ok(())
+    })
+}
+
+pub(crate) fn delete_note(store: &Store, workspace_id: u32, machine_id: u32, port_number: u16, note_id: &str) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let port = db.port_mut(workspace_id, machine_id, port_number)?;
+        let index = notes::position(port, note_id)?;
+        changes.record(workspace_id, "delete", format!("note:{}", note_id), audit::to_value(&port.data.remove(index)), None);
+        Ok(())
+    })
+}
+
+pub(crate) fn reorder_notes(store: &Store, workspace_id: u32, machine_id: u32, port_number: u16, note_ids: &[String]) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let port = db.port_mut(workspace_id, machine_id, port_number)?;
+        let before = audit::to_value(&port.data.iter().filter_map(notes::port_note_id).collect::<Vec<_>>());
+        notes::reorder_port_notes(port, note_ids)?;
+        changes.record(workspace_id, "reorder", format!("port:{}/{}", machine_id, port_number), before, audit::to_value(&note_ids));
+        Ok(())
+    })
+}
+
+pub(crate) fn move_note(
+    store: &Store,
+    workspace_id: u32,
+    machine_id: u32,
+    port_number: u16,
+    note_id: &str,
+    target_machine_id: u32,
+    target_port_number: u16,
+) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        // Make sure the target exists before taking the note off the source port
+        db.port_mut(workspace_id, target_machine_id, target_port_number)?;
+
+        let source = db.port_mut(workspace_id, machine_id, port_number)?;
+        let index = notes::position(source, note_id)?;
+        let mut note = source.data.remove(index);
+        if let PortNotes::PentestNote { updated_at, .. } = &mut note {
+            *updated_at = now();
+        }
+        db.port_mut(workspace_id, target_machine_id, target_port_number)?.data.push(note);
+        changes.record(workspace_id, "move", format!("note:{}", note_id),
+            audit::to_value(&json!({ "machine_id": machine_id, "port_number": port_number })),
+            audit::to_value(&json!({ "machine_id": target_machine_id, "port_number": target_port_number })));
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::ApiPort;
+    use crate::services::{machines, scans, workspaces};
+    use crate::store::{tests::temp_store, Store};
+
+    fn api_port(number: u16, service: &str) -> ApiPort {
+        ApiPort {
+            service: service.to_string(),
+            application: String::new(),
+            protocol: "tcp".to_string(),
+            number,
+            state: "open".to_string(),
+            data: None,
+            details: None,
+        }
+    }
+
+    // Workspace 1 with machine 1 exposing 22 and 80
+    fn scanned_store() -> (tempfile::TempDir, Store) {
+        let (dir, store) = temp_store();
+        workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        machines::add(&store, 1, "web".to_string(), "10.0.0.2".to_string()).unwrap();
+        scans::apply_scan(&store, 1, 1, vec![api_port(22, "ssh"), api_port(80, "http")]).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn note_lifecycle() {
+        let (_dir, store) = scanned_store();
+        let id = add_note(&store, 1, 1, 22, "Enumeration".to_string(), "Password auth enabled".to_string()).unwrap();
+        edit_note(&store, 1, 1, 22, &id, None, Some("Password auth disabled".to_string())).unwrap();
+
+        let port = get(&store, 1, 1, 22).unwrap();
+        match port.data.last().unwrap() {
+            PortNotes::PentestNote { content, author, revisions, .. } => {
+                assert_eq!(content, "Password auth disabled");
+                assert_eq!(*author, current_operator());
+                assert_eq!(revisions.len(), 1);
+            }
+            _ => panic!("expected a pentest note"),
+        }
+
+        delete_note(&store, 1, 1, 22, &id).unwrap();
+        assert!(matches!(delete_note(&store, 1, 1, 22, &id), Err(AppError::NotFound { .. })));
+    }
+
+    #[test]
+    fn rejects_empty_notes() {
+        let (_dir, store) = scanned_store();
+        assert!(matches!(add_note(&store, 1, 1, 22, "Enumeration".to_string(), "  ".to_string()), Err(AppError::Validation(_))));
+        assert!(matches!(add_note(&store, 1, 1, 443, "Enumeration".to_string(), "x".to_string()), Err(AppError::NotFound { .. })));
+    }
+
+    #[test]
+    fn moves_note_between_ports() {
+        let (_dir, store) = scanned_store();
+        let id = add_note(&store, 1, 1, 22, "Exploitation".to_string(), "Reused creds".to_string()).unwrap();
+
+        assert!(move_note(&store, 1, 1, 22, &id, 1, 8080).is_err());
+        move_note(&store, 1, 1, 22, &id, 1, 80).unwrap();
+        let has_note = |port: u16| get(&store, 1, 1, port).unwrap().data.iter().any(|note| notes::port_note_id(note) == Some(id.as_str()));
+        assert!(!has_note(22));
+        assert!(has_note(80));
+    }
+
+    #[test]
+    fn reorders_notes() {
+        let (_dir, store) = scanned_store();
+        let first = add_note(&store, 1, 1, 80, "Enumeration".to_string(), "one".to_string()).unwrap();
+        let second = add_note(&store, 1, 1, 80, "Enumeration".to_string(), "two".to_string()).unwrap();
+
+        assert!(reorder_notes(&store, 1, 1, 80, std::slice::from_ref(&first)).is_err());
+        reorder_notes(&store, 1, 1, 80, &[second.clone(), first.clone()]).unwrap();
+        let order: Vec<String> = get(&store, 1, 1, 80).unwrap().data.iter()
+            .filter_map(notes::port_note_id).map(str::to_string).collect();
+        assert_eq!(order, [second, first]);
+    }
+
+    #[test]
+    fn save_creates_then_updates() {
+        let (_dir, store) = scanned_store();
+        add_note(&store, 1, 1, 80, "Enumeration".to_string(), "Directory listing".to_string()).unwrap();
+        let input = |application: &str| PortInput {
+            protocol: default_protocol(),
+            service: "http".to_string(),
+            application: application.to_string(),
+            state: default_state(),
+            details: vec![],
+        };
+
+        assert!(save(&store, 1, 1, 8080, input("Jetty 9.4")).unwrap());
+        assert!(!save(&store, 1, 1, 80, input("nginx 1.25")).unwrap());
+        let port = get(&store, 1, 1, 80).unwrap();
+        assert_eq!(port.application, "nginx 1.25");
+        assert!(port.data.iter().any(|note| matches!(note, PortNotes::PentestNote { .. })));
+
+        delete(&store, 1, 1, 8080).unwrap();
+        assert_eq!(list(&store, 1, 1, None).unwrap().len(), 2);
+        assert!(delete(&store, 1, 1, 8080).is_err());
+    }
+
+    #[test]
+    fn credentials_need_a_value() {
+        let (_dir, store) = scanned_store();
+        assert!(matches!(add_credentials(&store, 1, 1, 22, Some(" ".to_string()), None, None), Err(AppError::Validation(_))));
+        add_credentials(&store, 1, 1, 22, Some("root".to_string()), None, Some("toor".to_string())).unwrap();
+        assert!(matches!(get(&store, 1, 1, 22).unwrap().data.last(), Some(PortNotes::Credentials { hash: None, .. })));
+
+        // Credentials have an ID like any other note, so they can be edited, looked up and deleted
+        let id = notes::port_note_id(get(&store, 1, 1, 22).unwrap().data.last().unwrap()).unwrap().to_string();
+        edit_credentials(&store, 1, 1, 22, &id, Some("root".to_string()), None, Some("hunter2".to_string())).unwrap();
+        assert!(matches!(get(&store, 1, 1, 22).unwrap().data.last(), Some(PortNotes::Credentials { password: Some(p), .. }) if p == "hunter2"));
+        assert!(crate::services::notes::revisions(&store, 1, &id).unwrap().is_empty());
+        let note = add_note(&store, 1, 1, 22, "Enumeration".to_string(), "root login".to_string()).unwrap();
+        assert!(edit_credentials(&store, 1, 1, 22, &note, Some("root".to_string()), None, None).is_err());
+        // and only pentest notes are edited as text, without an audit entry for nothing
+        let audited = store.audit_entries(1, Some(&format!("note:{}", id)), Some("update"), None).unwrap().len();
+        assert!(matches!(edit_note(&store, 1, 1, 22, &id, None, Some("root".to_string())), Err(AppError::Validation(_))));
+        assert_eq!(store.audit_entries(1, Some(&format!("note:{}", id)), Some("update"), None).unwrap().len(), audited);
+        delete_note(&store, 1, 1, 22, &id).unwrap();
+        assert!(!get(&store, 1, 1, 22).unwrap().data.iter().any(|note| matches!(note, PortNotes::Credentials { .. })));
+    }
+
+    #[test]
+    fn clients_cannot_change_who_found_credentials() {
+        let (_dir, store) = scanned_store();
+        add_credentials(&store, 1, 1, 22, Some("root".to_string()), None, Some("toor".to_string())).unwrap();
+        let mut data = get(&store, 1, 1, 22).unwrap().data;
+        let operator = match data.last() {
+            Some(PortNotes::Credentials { author, .. }) => author.clone(),
+            _ => panic!("expected credentials"),
+        };
+        if let Some(PortNotes::Credentials { author, .. }) = data.last_mut() {
+            *author = Some("mallory".to_string());
+        }
+        data.push(PortNotes::Credentials {
+            id: String::new(),
+            name: Some("admin".to_string()),
+            hash: None,
+            password: Some("admin".to_string()),
+            author: Some("mallory".to_string()),
+        });
+        update_notes(&store, 1, 1, 22, data).unwrap();
+
+        let authors: Vec<Option<String>> = get(&store, 1, 1, 22).unwrap().data.into_iter()
+            .filter_map(|note| match note {
+                PortNotes::Credentials { author, .. } => Some(author),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(authors, [operator.clone(), operator]);
+    }
+
+    #[test]
+    fn audit_log_never_holds_secrets() {
+        let (_dir, store) = scanned_store();
+        add_credentials(&store, 1, 1, 22, Some("root".to_string()), Some("aad3b435b51404ee".to_string()), Some("toor".to_string())).unwrap();
+        let id = notes::port_note_id(get(&store, 1, 1, 22).unwrap().data.last().unwrap()).unwrap().to_string();
+        edit_credentials(&store, 1, 1, 22, &id, Some("root".to_string()), None, Some("hunter2".to_string())).unwrap();
+        update_notes(&store, 1, 1, 22, get(&store, 1, 1, 22).unwrap().data).unwrap();
+        delete(&store, 1, 1, 22).unwrap();
+        crate::services::machines::delete(&store, 1, 1).unwrap();
+
+        let log = serde_json::to_string(&store.audit_entries(1, None, None, None).unwrap()).unwrap();
+        assert!(log.contains("root"));
+        for secret in ["aad3b435b51404ee", "toor", "hunter2"] {
+            assert!(!log.contains(secret), "{} ended up in the audit log", secret);
+        }
+    }
+
+    #[test]
+    fn clients_cannot_write_scan_output_or_dangling_references() {
+        let (_dir, store) = scanned_store();
+        add_note(&store, 1, 1, 80, "Enumeration".to_string(), "Default page".to_string()).unwrap();
+        let data = get(&store, 1, 1, 80).unwrap().data;
+        let with = |note: PortNotes| data.iter().cloned().chain([note]).collect::<Vec<_>>();
+
+        let dangling = with(PortNotes::FindingRef { id: String::new(), finding_id: 7 });
+        assert!(matches!(update_notes(&store, 1, 1, 80, dangling), Err(AppError::NotFound { .. })));
+        let script = with(PortNotes::ScriptOutput { id: String::new(), script_id: "http-title".to_string(), output: vec!["Pwned".to_string()] });
+        assert!(matches!(update_notes(&store, 1, 1, 80, script), Err(AppError::Validation(_))));
+        let scan = with(PortNotes::NmapScan(vec!["VULNERABLE".to_string()]));
+        assert!(matches!(update_notes(&store, 1, 1, 80, scan), Err(AppError::Validation(_))));
+        let twice = with(data.last().unwrap().clone());
+        assert!(matches!(update_notes(&store, 1, 1, 80, twice), Err(AppError::Validation(_))));
+
+        // Sending back what the server has is fine
+        update_notes(&store, 1, 1, 80, data).unwrap();
+    }
+}
@@ -0,0 +1,47 @@
+use std::fs;
+
+use crate::error::{AppError, AppResult};
+use crate::report::{self, ReportFormat, ReportOptions};
+use crate::store::Store;
+
+pub(crate) fn generate(store: &Store, workspace_id: u32, format: ReportFormat, options: ReportOptions) -> AppResult<String> {
+    let template = report::load_template(store.dir(), format)?;
+    store.read(|db| Ok(report::render(db.workspace(workspace_id)?, &template, format, options)))
+}
+
+pub(crate) fn export(store: &Store, workspace_id: u32, format: ReportFormat, options: ReportOptions, path: &str) -> AppResult<()> {
+    let rendered = generate(store, workspace_id, format, options)?;
+    fs::write(path, rendered).map_err(|e| AppError::storage("Failed to write report", e))
+}
+
+pub(crate) fn template(store: &Store, format: ReportFormat) -> AppResult<String> {
+    report::load_template(store.dir(), format)
+}
+
+pub(crate) fn save_template(store: &Store, format: ReportFormat, template: &str) -> AppResult<()> {
+    report::save_template(store.dir(), format, template)
+}
+
+pub(crate) fn reset_template(store: &Store, format: ReportFormat) -> AppResult<()> {
+    report::reset_template(store.dir(), format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::temp_store;
+    use crate::services::workspaces;
+
+    #[test]
+    fn custom_templates_are_used_until_reset() {
+        let (_dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Acme <internal>".to_string(), "10.0.0.0/24".to_string()).unwrap();
+
+        save_template(&store, ReportFormat::Html, "<h1>{{workspace_name}}</h1>").unwrap();
+        assert_eq!(generate(&store, workspace_id, ReportFormat::Html, ReportOptions::default()).unwrap(), "<h1>Acme &lt;internal&gt;</h1>");
+
+        reset_template(&store, ReportFormat::Html).unwrap();
+        assert!(template(&store, ReportFormat::Html).unwrap().contains("{{hosts}}"));
+        assert!(generate(&store, 2, ReportFormat::Markdown, ReportOptions::default()).is_err());
+    }
+}
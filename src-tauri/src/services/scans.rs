@@ -0,0 +1,186 @@
+use serde_json::json;
+
+use crate::audit;
+use crate::backend::{ApiPort, Backend, NetworkScan};
+use crate::compromise;
+use crate::error::AppResult;
+use crate::fingerprint;
+use crate::store::Store;
+use crate::{now, Machine, Port, PortNotes};
+
+fn to_port(api_port: ApiPort) -> Port {
+    let data = match api_port.details {
+        Some(details) if !details.is_empty() => vec![PortNotes::NmapScan(details)],
+        _ => vec![PortNotes::None],
+    };
+    Port {
+        service: api_port.service,
+        application: api_port.application,
+        protocol: api_port.protocol,
+        number: api_port.number,
+        state: api_port.state,
+        data,
+        cves: vec![],
+        tags: vec![],
+    }
+}
+
+// What a scan produces, replaced by the next one; notes and credentials stay
+fn from_scan(note: &PortNotes) -> bool {
+    matches!(note, PortNotes::NmapScan(_) | PortNotes::ScriptOutput { .. } | PortNotes::None)
+}
+
+// Keeps what was recorded on an old port, after the output of the scan that found it
+fn carry_over(data: &mut Vec<PortNotes>, old: Vec<PortNotes>) {
+    data.retain(|note| !matches!(note, PortNotes::None));
+    data.extend(old.into_iter().filter(|note| !from_scan(note)));
+    if data.is_empty() {
+        data.push(PortNotes::None);
+    }
+}
+
+// Scans the machine through the backend and stores the result, returns the number of open ports
+pub(crate) async fn scan_machine(store: &Store, backend: &Backend, workspace_id: u32, machine_id: u32) -> AppResult<usize> {
+    let machine_ip = store.read(|db| Ok(db.machine(workspace_id, machine_id)?.ip.clone()))?;
+    let api_ports = backend.scan_ports(&machine_ip).await?;
+    apply_scan(store, workspace_id, machine_id, api_ports)
+}
+
+// Updates the machine's ports from a scan result; an empty list means nothing is open. Ports
+// the scan doesn't find any more are kept as closed, with their notes.
+pub(crate) fn apply_scan(store: &Store, workspace_id: u32, machine_id: u32, api_ports: Vec<ApiPort>) -> AppResult<usize> {
+    let count = api_ports.len();
+    let mut ports: Vec<Port> = api_ports.into_iter().map(to_port).collect();
+
+    {
+        let index = store.cve_index()?;
+        if !index.is_empty() {
+            for port in ports.iter_mut() {
+                port.cves = index.match_port(port);
+            }
+        }
+    }
+
+    store.update_audited(|db, changes| {
+        let machine = db.machine_mut(workspace_id, machine_id)?;
+        let before = audit::to_value(&machine.ports.iter().map(|port| port.number).collect::<Vec<_>>());
+        let after = audit::to_value(&ports.iter().map(|port| port.number).collect::<Vec<_>>());
+        // Tags and notes belong to the service, not to a particular scan
+        let mut old_ports = std::mem::take(&mut machine.ports);
+        for port in ports.iter_mut() {
+            if let Some(index) = old_ports.iter().position(|old| old.number == port.number && old.protocol == port.protocol) {
+                let old = old_ports.remove(index);
+                port.tags = old.tags;
+                carry_over(&mut port.data, old.data);
+            }
+        }
+        for mut old in old_ports {
+            old.state = "closed".to_string();
+            let data = std::mem::take(&mut old.data);
+            carry_over(&mut old.data, data);
+            ports.push(old);
+        }
+        machine.ports = ports;
+        machine.last_scanned = Some(now());
+        compromise::mark_enumerated(machine);
+        fingerprint::refresh(machine);
+        changes.record(workspace_id, "scan", format!("machine:{}", machine_id), before, after);
+        Ok(())
+    })?;
+    Ok(count)
+}
+
+// Runs host discovery over the workspace's range, returns the number of hosts added
+pub(crate) async fn discover_hosts(store: &Store, backend: &Backend, workspace_id: u32) -> AppResult<usize> {
+    let ip_range = store.read(|db| Ok(db.workspace(workspace_id)?.ip_range.clone()))?;
+    let network_scan = backend.discover(&ip_range).await?;
+    apply_discovery(store, workspace_id, &network_scan)
+}
+
+pub(crate) fn apply_discovery(store: &Store, workspace_id: u32, network_scan: &NetworkScan) -> AppResult<usize> {
+    store.update_audited(|db, changes| {
+        let workspace = db.workspace_mut(workspace_id)?;
+        for host in &network_scan.active_hosts {
+            let id = workspace.next_machine_id();
+            workspace.data.push(Machine::new(id, format!("Host_{}", host), host.clone()));
+        }
+        workspace.last_discovery = Some(now());
+        changes.record(workspace_id, "discover", format!("workspace:{}", workspace_id), None,
+            audit::to_value(&json!({ "ip_range": workspace.ip_range, "hosts": network_scan.active_hosts })));
+        Ok(())
+    })?;
+    Ok(network_scan.active_hosts.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::parse_scan;
+    use crate::compromise::AccessLevel;
+    use crate::services::{machines, ports, workspaces};
+    use crate::store::tests::temp_store;
+
+    const SCAN: &str = r#"[
+        {"service":"ssh","application":"OpenSSH 8.2p1 Ubuntu 4ubuntu0.5","protocol":"tcp","number":22,"state":"open",
+         "data":null,"details":["ssh-hostkey: 3072 aa:bb"]},
+        {"service":"http","application":"nginx 1.18.0","protocol":"tcp","number":80,"state":"open","data":null,"details":[]}
+    ]"#;
+
+    #[test]
+    fn rescans_replace_scan_output_and_keep_notes() {
+        let (_dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        let machine_id = machines::add(&store, workspace_id, "web".to_string(), "10.0.0.2".to_string()).unwrap();
+
+        assert_eq!(apply_scan(&store, workspace_id, machine_id, parse_scan(SCAN).unwrap()).unwrap(), 2);
+        store.update(|db| {
+            db.port_mut(workspace_id, machine_id, 22)?.tags.push("creds".to_string());
+            Ok(())
+        }).unwrap();
+        ports::add_credentials(&store, workspace_id, machine_id, 22, Some("root".to_string()), None, Some("toor".to_string())).unwrap();
+        let note = ports::add_note(&store, workspace_id, machine_id, 80, "Enumeration".to_string(), "Default page".to_string()).unwrap();
+        apply_scan(&store, workspace_id, machine_id, parse_scan(SCAN).unwrap()).unwrap();
+
+        let ssh = ports::get(&store, workspace_id, machine_id, 22).unwrap();
+        assert_eq!(ssh.tags, ["creds"]);
+        assert_eq!(ssh.data.len(), 2);
+        assert!(matches!(&ssh.data[0], PortNotes::NmapScan(lines) if lines.len() == 1));
+        assert!(matches!(&ssh.data[1], PortNotes::Credentials { name: Some(name), .. } if name == "root"));
+        let http = ports::get(&store, workspace_id, machine_id, 80).unwrap();
+        assert!(matches!(&http.data[..], [PortNotes::PentestNote { id, .. }] if *id == note));
+
+        let machine = machines::get(&store, workspace_id, machine_id).unwrap();
+        assert!(machine.status == AccessLevel::Enumerated);
+        assert!(machine.fingerprint.is_some());
+    }
+
+    #[test]
+    fn ports_gone_from_a_scan_are_closed() {
+        let (_dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        let machine_id = machines::add(&store, workspace_id, "web".to_string(), "10.0.0.2".to_string()).unwrap();
+        apply_scan(&store, workspace_id, machine_id, parse_scan(SCAN).unwrap()).unwrap();
+        ports::add_note(&store, workspace_id, machine_id, 22, "Enumeration".to_string(), "Key only".to_string()).unwrap();
+
+        assert_eq!(apply_scan(&store, workspace_id, machine_id, parse_scan("N/A").unwrap()).unwrap(), 0);
+        let ports = ports::list(&store, workspace_id, machine_id, None).unwrap();
+        assert!(ports.iter().all(|port| port.state == "closed"));
+        assert!(matches!(&ports[0].data[..], [PortNotes::PentestNote { .. }]));
+        assert!(matches!(&ports[1].data[..], [PortNotes::None]));
+        assert_eq!(store.audit_entries(workspace_id, Some("machine:1"), Some("scan"), None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn discovery_adds_hosts() {
+        let (_dir, store) = temp_store();
+        let workspace_id = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        machines::add(&store, workspace_id, "gw".to_string(), "10.0.0.1".to_string()).unwrap();
+        let scan = NetworkScan { cidr: "10.0.0.0/24".to_string(), active_hosts: vec!["10.0.0.7".to_string(), "10.0.0.9".to_string()] };
+
+        assert_eq!(apply_discovery(&store, workspace_id, &scan).unwrap(), 2);
+        let hosts: Vec<(u32, String)> = machines::list(&store, workspace_id, None).unwrap().into_iter()
+            .map(|machine| (machine.id, machine.hostname)).collect();
+        assert_eq!(hosts, [(1, "gw".to_string()), (2, "Host_10.0.0.7".to_string()), (3, "Host_10.0.0.9".to_string())]);
+        assert!(apply_discovery(&store, 5, &scan).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+use crate::audit;
+use crate::error::AppResult;
+use crate::store::Store;
+use crate::tags::{self, Tag, TagTarget};
+use crate::Database;
+
+pub(crate) fn list(store: &Store) -> AppResult<Vec<Tag>> {
+    store.read(|db| Ok(db.tags.clone()))
+}
+
+fn find(db: &Database, name: &str) -> Option<Tag> {
+    db.tags.iter().find(|tag| tag.name.eq_ignore_ascii_case(name.trim())).cloned()
+}
+
+// Definitions are shared, so a change is recorded in every workspace using the tag
+pub(crate) fn save(store: &Store, name: &str, color: Option<String>) -> AppResult<Tag> {
+    store.update_audited(|db, changes| {
+        let before = find(db, name);
+        let tag = tags::save(db, name, color)?;
+        let action = if before.is_some() { "update" } else { "create" };
+        for workspace in db.data.iter().filter(|workspace| tags::in_use(workspace, &tag.name)) {
+            changes.record(workspace.id, action, format!("tag:{}", tag.name), before.as_ref().and_then(audit::to_value), audit::to_value(&tag));
+        }
+        Ok(tag)
+    })
+}
+
+pub(crate) fn delete(store: &Store, name: &str) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let before = find(db, name);
+        let using: Vec<u32> = db.data.iter().filter(|workspace| tags::in_use(workspace, name)).map(|workspace| workspace.id).collect();
+        tags::delete(db, name)?;
+        for workspace_id in using {
+            changes.record(workspace_id, "delete", format!("tag:{}", name), before.as_ref().and_then(audit::to_value), None);
+        }
+        Ok(())
+    })
+}
+
+pub(crate) fn tag(store: &Store, workspace_id: u32, target: &TagTarget, tag: &str) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        tags::apply(db, workspace_id, target, tag)?;
+        changes.record(workspace_id, "tag", target.object(workspace_id), None, audit::to_value(&tag));
+        Ok(())
+    })
+}
+
+pub(crate) fn untag(store: &Store, workspace_id: u32, target: &TagTarget, tag: &str) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        tags::remove(db, workspace_id, target, tag)?;
+        changes.record(workspace_id, "untag", target.object(workspace_id), audit::to_value(&tag), None);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::workspaces;
+    use crate::store::tests::temp_store;
+
+    #[test]
+    fn shared_definitions_are_audited_where_used() {
+        let (_dir, store) = temp_store();
+        let lab = workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        let dmz = workspaces::add(&store, "DMZ".to_string(), "192.168.1.0/24".to_string()).unwrap();
+        tag(&store, lab, &TagTarget::Workspace, "critical").unwrap();
+
+        save(&store, "Critical", Some("#ff0000".to_string())).unwrap();
+        delete(&store, "critical").unwrap();
+        let actions = |workspace_id| workspaces::audit_log(&store, workspace_id, Some("tag:"), None, None).unwrap()
+            .into_iter().map(|entry| entry.action).collect::<Vec<_>>();
+        assert_eq!(actions(lab), ["delete", "update"]);
+        assert!(actions(dmz).is_empty());
+    }
+}
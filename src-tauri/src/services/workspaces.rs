@@ -0,0 +1,180 @@
+use serde_json::json;
+use std::fs;
+
+use crate::audit::{self, AuditEntry};
+use crate::chat;
+use crate::error::{AppError, AppResult};
+use crate::merge::{self, Import, MergeReport};
+use crate::query::{self, PortPage, PortQuery};
+use crate::redact::RedactionPolicy;
+use crate::search::SearchHit;
+use crate::store::Store;
+use crate::{tags, JsWorkspace, Workspace};
+
+pub(crate) fn list(store: &Store, tag: Option<&str>) -> AppResult<Vec<JsWorkspace>> {
+    store.read(|db| {
+        Ok(db.data.iter()
+            .filter(|workspace| tags::has_tag(&workspace.tags, tag))
+            .map(JsWorkspace::from)
+            .collect())
+    })
+}
+
+pub(crate) fn get(store: &Store, workspace_id: u32) -> AppResult<JsWorkspace> {
+    store.read(|db| db.workspace(workspace_id).map(JsWorkspace::from))
+}
+
+pub(crate) fn add(store: &Store, name: String, ip_range: String) -> AppResult<u32> {
+    store.update_audited(|db, changes| {
+        let id = db.next_workspace_id();
+        changes.record(id, "create", format!("workspace:{}", id), None, audit::to_value(&json!({ "name": name, "ip_range": ip_range })));
+        db.data.push(Workspace::new(id, name, ip_range));
+        Ok(id)
+    })
+}
+
+// Fields left out keep their value
+pub(crate) fn update(store: &Store, workspace_id: u32, name: Option<String>, ip_range: Option<String>) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let workspace = db.workspace_mut(workspace_id)?;
+        let before = audit::to_value(&json!({ "name": workspace.name, "ip_range": workspace.ip_range }));
+        if let Some(name) = name {
+            workspace.name = name;
+        }
+        if let Some(ip_range) = ip_range {
+            workspace.ip_range = ip_range;
+        }
+        let after = audit::to_value(&json!({ "name": workspace.name, "ip_range": workspace.ip_range }));
+        changes.record(workspace_id, "update", format!("workspace:{}", workspace_id), before, after);
+        Ok(())
+    })
+}
+
+pub(crate) fn set_redaction(store: &Store, workspace_id: u32, policy: RedactionPolicy) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let workspace = db.workspace_mut(workspace_id)?;
+        let before = audit::to_value(&json!({ "redaction": workspace.redaction }));
+        workspace.redaction = policy;
+        changes.record(workspace_id, "update", format!("workspace:{}", workspace_id), before, audit::to_value(&json!({ "redaction": policy })));
+        Ok(())
+    })
+}
+
+pub(crate) fn delete(store: &Store, workspace_id: u32) -> AppResult<()> {
+    store.update_audited(|db, changes| {
+        let workspace = db.workspace(workspace_id)?;
+        let before = audit::to_value(&json!({ "name": workspace.name, "ip_range": workspace.ip_range, "machines": workspace.data.len() }));
+        changes.record(workspace_id, "delete", format!("workspace:{}", workspace_id), before, None);
+        db.data.retain(|workspace| workspace.id != workspace_id);
+        Ok(())
+    })?;
+    chat::remove(store.dir(), workspace_id)
+}
+
+// Merges an exported database.json or workspace file into this database
+pub(crate) fn merge(store: &Store, path: &str, workspace_id: Option<u32>) -> AppResult<MergeReport> {
+    let data = fs::read_to_string(path)
+        .map_err(|e| AppError::storage("Failed to read merge file", e))?;
+    let import: Import = serde_json::from_str(&data)
+        .map_err(|e| AppError::validation(format!("Not an exported database or workspace: {}", e)))?;
+    let incoming = import.into_database();
+    let names: Vec<String> = incoming.data.iter().map(|workspace| workspace.name.to_lowercase()).collect();
+    store.update_audited(|db, changes| {
+        let report = merge::merge_database(db, incoming, workspace_id)?;
+        // The workspaces that were merged into or added
+        let merged = match workspace_id {
+            Some(id) => vec![id],
+            None => db.data.iter()
+                .filter(|workspace| names.contains(&workspace.name.to_lowercase()))
+                .map(|workspace| workspace.id)
+                .collect(),
+        };
+        let after = audit::to_value(&json!({
+            "file": path,
+            "machines_added": report.machines_added,
+            "ports_added": report.ports_added,
+            "notes_added": report.notes_added,
+            "workspaces_added": report.workspaces_added,
+            "conflicts": report.conflicts.len(),
+        }));
+        for id in merged {
+            changes.record(id, "merge", format!("workspace:{}", id), None, after.clone());
+        }
+        Ok(report)
+    })
+}
+
+// Workspace-wide port listing, e.g. every host with 445 open or every Apache 2.4 service
+pub(crate) fn query_ports(store: &Store, workspace_id: u32, query: &PortQuery) -> AppResult<PortPage> {
+    store.read(|db| Ok(query::run(db.workspace(workspace_id)?, query)))
+}
+
+pub(crate) fn search(store: &Store, query: &str, limit: Option<usize>) -> AppResult<Vec<SearchHit>> {
+    store.search(query, limit.unwrap_or(50))
+}
+
+pub(crate) fn audit_log(store: &Store, workspace_id: u32, object: Option<&str>, action: Option<&str>, limit: Option<usize>) -> AppResult<Vec<AuditEntry>> {
+    store.audit_entries(workspace_id, object, action, limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::temp_store;
+
+    #[test]
+    fn add_assigns_ids_and_audits() {
+        let (_dir, store) = temp_store();
+        assert_eq!(add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap(), 1);
+        assert_eq!(add(&store, "DMZ".to_string(), "192.168.1.0/24".to_string()).unwrap(), 2);
+
+        let names: Vec<String> = list(&store, None).unwrap().into_iter().map(|w| w.name).collect();
+        assert_eq!(names, ["Lab", "DMZ"]);
+        let log = audit_log(&store, 2, None, Some("create"), None).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].object, "workspace:2");
+    }
+
+    #[test]
+    fn deleted_ids_are_not_reused() {
+        let (_dir, store) = temp_store();
+        add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        add(&store, "DMZ".to_string(), "192.168.1.0/24".to_string()).unwrap();
+        delete(&store, 1).unwrap();
+        assert_eq!(add(&store, "Cloud".to_string(), "172.16.0.0/16".to_string()).unwrap(), 3);
+
+        update(&store, 3, None, Some("172.16.0.0/24".to_string())).unwrap();
+        let workspace = get(&store, 3).unwrap();
+        assert_eq!((workspace.name.as_str(), workspace.ip_range.as_str()), ("Cloud", "172.16.0.0/24"));
+        assert!(delete(&store, 1).is_err());
+
+        delete(&store, 3).unwrap();
+        assert_eq!(add(&store, "Cloud".to_string(), "172.16.0.0/16".to_string()).unwrap(), 4);
+    }
+
+    #[test]
+    fn merges_exported_workspace_file() {
+        let (dir, store) = temp_store();
+        let lab = add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        let mut exported = Workspace::new(9, "Client copy".to_string(), "10.0.0.0/24".to_string());
+        exported.data.push(crate::Machine::new(1, "dc01".to_string(), "10.0.0.5".to_string()));
+        let path = dir.path().join("export.json");
+        fs::write(&path, serde_json::to_string(&exported).unwrap()).unwrap();
+
+        let report = merge(&store, path.to_str().unwrap(), Some(lab)).unwrap();
+        assert_eq!((report.workspaces_added, report.machines_added), (0, 1));
+        assert_eq!(report.conflicts[0].field, "name");
+        assert_eq!(get(&store, lab).unwrap().name, "Lab");
+        assert_eq!(audit_log(&store, lab, None, Some("merge"), None).unwrap().len(), 1);
+
+        fs::write(&path, "[]").unwrap();
+        assert!(matches!(merge(&store, path.to_str().unwrap(), None), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn get_missing_workspace_is_not_found() {
+        let (_dir, store) = temp_store();
+        assert!(matches!(get(&store, 3), Err(AppError::NotFound { .. })));
+        assert!(query_ports(&store, 3, &PortQuery::default()).is_err());
+    }
+}
@@ -0,0 +1,513 @@
+use serde_json::Value;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use crate::audit::{self, AuditEntry};
+use crate::chat::{self, Conversation};
+use crate::cve::{self, CveIndex};
+use crate::error::{AppError, AppResult};
+use crate::search::{SearchHit, SearchIndex};
+use crate::{fingerprint, notes, Database, PortNotes};
+
+// Overrides the per-OS location, e.g. for a second install or the test suite
+const DATA_DIR_VAR: &str = "NETVISION_DATA_DIR";
+
+fn env_path(name: &str) -> AppResult<PathBuf> {
+    env::var(name)
+        .map(PathBuf::from)
+        .map_err(|e| AppError::storage(&format!("Failed to get {} directory", name), e))
+}
+
+#[cfg(target_os = "windows")]
+fn platform_data_dir() -> AppResult<PathBuf> {
+    Ok(env_path("APPDATA")?.join("NetVision").join("workspaces"))
+}
+
+#[cfg(target_os = "linux")]
+fn platform_data_dir() -> AppResult<PathBuf> {
+    Ok(env_path("HOME")?.join(".local").join("share").join("netvision").join("workspaces"))
+}
+
+#[cfg(target_os = "macos")]
+fn platform_data_dir() -> AppResult<PathBuf> {
+    Ok(env_path("HOME")?.join("Library").join("Application Support").join("NetVision").join("workspaces"))
+}
+
+pub(crate) fn default_data_dir() -> AppResult<PathBuf> {
+    match env::var_os(DATA_DIR_VAR) {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => platform_data_dir(),
+    }
+}
+
+fn database_path(dir: &Path) -> PathBuf {
+    dir.join("database.json")
+}
+
+fn read_database_file(dir: &Path) -> AppResult<Option<String>> {
+    let path = database_path(dir);
+    if !path.exists() {
+        return Ok(None);
+    }
+    fs::read_to_string(&path)
+        .map(Some)
+        .map_err(|e| AppError::storage("Failed to read database file", e))
+}
+
+// Leaves the file alone, an unreadable one is an error. Migrations are only made in memory.
+fn read_database(dir: &Path) -> AppResult<Database> {
+    let Some(data) = read_database_file(dir)? else {
+        return Ok(Database::default());
+    };
+    let mut database = serde_json::from_str(&data)
+        .map_err(|e| AppError::storage("Failed to parse database file", e))?;
+    migrate_database(&mut database);
+    Ok(database)
+}
+
+// Only called with the database locked. An unreadable file is set aside rather than overwritten
+// by the next save, so nothing is lost, and None returned. Migrations are saved straight away.
+fn load_database(dir: &Path) -> AppResult<Option<Database>> {
+    let Some(data) = read_database_file(dir)? else {
+        return Ok(Some(Database::default()));
+    };
+    let mut database = match serde_json::from_str(&data) {
+        Ok(database) => database,
+        Err(e) => {
+            let kept = dir.join(format!("database.json.corrupt-{}", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+            fs::rename(database_path(dir), &kept).map_err(|e| AppError::storage("Failed to set aside unreadable database file", e))?;
+            eprintln!("Database file could not be read ({}); moved to {}", e, kept.display());
+            return Ok(None);
+        }
+    };
+    if migrate_database(&mut database) {
+        if let Err(e) = save_database(dir, &database) {
+            eprintln!("Failed to save migrated database: {}", e);
+        }
+    }
+    Ok(Some(database))
+}
+
+// Brings databases written by older versions up to date, returns true if anything changed
+pub(crate) fn migrate_database(database: &mut Database) -> bool {
+    let mut changed = false;
+    for port in database.data.iter_mut()
+        .flat_map(|workspace| workspace.data.iter_mut())
+        .flat_map(|machine| machine.ports.iter_mut())
+    {
+        for note in port.data.iter_mut() {
+            // AI output used to be stored as an NmapScan whose first line was a marker
+            if let PortNotes::NmapScan(lines) = note {
+                if lines.first().map(String::as_str) == Some("AI Security Analysis") {
+                    *note = PortNotes::AiAnalysis {
+                        id: notes::new_note_id(),
+                        model: None,
+                        prompt: None,
+                        content: lines[1..].join("\n"),
+                        timestamp: String::new(),
+                    };
+                    changed = true;
+                }
+            }
+        }
+    }
+    // Machines scanned before fingerprinting existed get their OS and icon detected
+    for machine in database.data.iter_mut().flat_map(|workspace| workspace.data.iter_mut()) {
+        if machine.fingerprint.is_none() && !machine.ports.is_empty() {
+            fingerprint::refresh(machine);
+            changed = true;
+        }
+    }
+    // IDs used to be the highest one plus one, so counters start from what is there
+    let highest = database.data.iter().map(|workspace| workspace.id).max().unwrap_or(0);
+    if database.last_workspace_id < highest {
+        database.last_workspace_id = highest;
+        changed = true;
+    }
+    for workspace in database.data.iter_mut() {
+        let highest = workspace.data.iter().map(|machine| machine.id).max().unwrap_or(0);
+        if workspace.last_machine_id < highest {
+            workspace.last_machine_id = highest;
+            changed = true;
+        }
+        let highest = workspace.findings.iter().map(|finding| finding.id).max().unwrap_or(0);
+        if workspace.last_finding_id < highest {
+            workspace.last_finding_id = highest;
+            changed = true;
+        }
+    }
+    changed |= notes::migrate(database);
+    changed
+}
+
+// Written next to the database and renamed over it, so nobody reads half a file
+pub(crate) fn save_database(dir: &Path, database: &Database) -> AppResult<()> {
+    let json = serde_json::to_string_pretty(database)?;
+    let temp = dir.join("database.json.tmp");
+    fs::write(&temp, json)
+        .map_err(|e| AppError::storage("Failed to write database file", e))?;
+    fs::rename(&temp, database_path(dir))
+        .map_err(|e| AppError::storage("Failed to replace database file", e))
+}
+
+// When the database file was last written and how long it is, to notice saves by another
+// process such as the CLI
+type Stamp = (SystemTime, u64);
+
+fn stamp(dir: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(database_path(dir)).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+// Held while a process reads the database file, changes it and writes it back. The OS lets go
+// of it when the file is closed, even if the process dies.
+fn lock_database(dir: &Path) -> AppResult<File> {
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(dir.join("database.lock"))
+        .map_err(|e| AppError::storage("Failed to open database lock file", e))?;
+    file.lock().map_err(|e| AppError::storage("Failed to lock database", e))?;
+    Ok(file)
+}
+
+// The database as this process last read or wrote it
+struct Loaded {
+    database: Database,
+    stamp: Option<Stamp>,
+    // Bumped on every change so the search index knows when to rebuild
+    revision: u64,
+}
+
+// The audit entries of one update, by workspace
+#[derive(Default)]
+pub(crate) struct Changes {
+    entries: Vec<(u32, AuditEntry)>,
+}
+
+impl Changes {
+    pub(crate) fn record(&mut self, workspace_id: u32, action: &str, object: String, before: Option<Value>, after: Option<Value>) {
+        self.entries.push((workspace_id, AuditEntry::new(action, object, before, after)));
+    }
+}
+
+// Called with the workspace ID after every recorded change
+type Watcher = Box<dyn Fn(u32) + Send>;
+
+// Everything kept under one data directory: the database, CVE index, templates, audit logs and
+// chat conversations. Clones share the same state, so the app and the local API see the same data
+#[derive(Clone)]
+pub(crate) struct Store {
+    dir: PathBuf,
+    database: Arc<Mutex<Loaded>>,
+    cve_index: Arc<Mutex<CveIndex>>,
+    search: Arc<Mutex<SearchIndex>>,
+    watchers: Arc<Mutex<Vec<Watcher>>>,
+    // Held while a workspace's conversations file is read and rewritten
+    chats: Arc<Mutex<()>>,
+}
+
+impl Store {
+    pub(crate) fn open(dir: impl Into<PathBuf>) -> AppResult<Store> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|e| AppError::storage("Failed to create workspaces directory", e))?;
+        let database = {
+            let _lock = lock_database(&dir)?;
+            Loaded { database: load_database(&dir)?.unwrap_or_default(), stamp: stamp(&dir), revision: 1 }
+        };
+        let cve_index = cve::load_index(&dir);
+        Ok(Store {
+            dir,
+            database: Arc::new(Mutex::new(database)),
+            cve_index: Arc::new(Mutex::new(cve_index)),
+            search: Arc::new(Mutex::new(SearchIndex::default())),
+            watchers: Arc::new(Mutex::new(vec![])),
+            chats: Arc::new(Mutex::new(())),
+        })
+    }
+
+    pub(crate) fn open_default() -> AppResult<Store> {
+        Store::open(default_data_dir()?)
+    }
+
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Picks up what another process saved since this one last looked. Without the file lock a
+    // file that can't be read is an error and the last good state is kept; with it the file is
+    // set aside and the update writes the last good state back.
+    fn reload(&self, loaded: &mut Loaded, locked: bool) -> AppResult<()> {
+        let current = stamp(&self.dir);
+        if current.is_none() || current == loaded.stamp {
+            return Ok(());
+        }
+        let database = if locked { load_database(&self.dir)? } else { Some(read_database(&self.dir)?) };
+        if let Some(database) = database {
+            loaded.database = database;
+            loaded.revision += 1;
+        }
+        loaded.stamp = stamp(&self.dir);
+        Ok(())
+    }
+
+    pub(crate) fn read<T>(&self, f: impl FnOnce(&Database) -> AppResult<T>) -> AppResult<T> {
+        let mut loaded = self.database.lock()?;
+        self.reload(&mut loaded, false)?;
+        f(&loaded.database)
+    }
+
+    // The closure works on a copy, which replaces the database and is written to disk only
+    // when it succeeds. The file stays locked from reading to writing, so two processes can't
+    // overwrite each other's changes.
+    pub(crate) fn update<T>(&self, f: impl FnOnce(&mut Database) -> AppResult<T>) -> AppResult<T> {
+        self.update_audited(|db, _| f(db))
+    }
+
+    // Like `update`, for changes to workspace data. What `f` records is written to the audit
+    // logs before the database, with the file still locked, and nothing is saved if that fails.
+    pub(crate) fn update_audited<T>(&self, f: impl FnOnce(&mut Database, &mut Changes) -> AppResult<T>) -> AppResult<T> {
+        let mut changes = Changes::default();
+        let result = {
+            let mut loaded = self.database.lock()?;
+            let _lock = lock_database(&self.dir)?;
+            self.reload(&mut loaded, true)?;
+            let mut database = loaded.database.clone();
+            let result = f(&mut database, &mut changes)?;
+            for (workspace_id, entry) in &changes.entries {
+                audit::record(&self.dir, *workspace_id, entry)?;
+            }
+            save_database(&self.dir, &database)?;
+            loaded.database = database;
+            loaded.stamp = stamp(&self.dir);
+            loaded.revision += 1;
+            result
+        };
+        // Every change made through the services is recorded, which makes this the place to
+        // announce it. Watchers may use the store, so this waits until it is unlocked.
+        let mut workspaces: Vec<u32> = changes.entries.iter().map(|(workspace_id, _)| *workspace_id).collect();
+        workspaces.sort_unstable();
+        workspaces.dedup();
+        for watcher in self.watchers.lock()?.iter() {
+            workspaces.iter().for_each(|workspace_id| watcher(*workspace_id));
+        }
+        Ok(result)
+    }
+
+    pub(crate) fn watch(&self, watcher: impl Fn(u32) + Send + 'static) -> AppResult<()> {
+        self.watchers.lock()?.push(Box::new(watcher));
+        Ok(())
+    }
+
+    pub(crate) fn audit_entries(&self, workspace_id: u32, object: Option<&str>, action: Option<&str>, limit: Option<usize>) -> AppResult<Vec<AuditEntry>> {
+        audit::entries(&self.dir, workspace_id, object, action, limit)
+    }
+
+    pub(crate) fn conversations<T>(&self, workspace_id: u32, f: impl FnOnce(&[Conversation]) -> AppResult<T>) -> AppResult<T> {
+        let _lock = self.chats.lock()?;
+        f(&chat::load(&self.dir, workspace_id)?)
+    }
+
+    // Saves the workspace's conversations if `f` succeeds. They aren't workspace data, so
+    // nothing is recorded.
+    pub(crate) fn update_conversations<T>(&self, workspace_id: u32, f: impl FnOnce(&mut Vec<Conversation>) -> AppResult<T>) -> AppResult<T> {
+        let _lock = self.chats.lock()?;
+        let mut conversations = chat::load(&self.dir, workspace_id)?;
+        let result = f(&mut conversations)?;
+        chat::save(&self.dir, workspace_id, &conversations)?;
+        Ok(result)
+    }
+
+    pub(crate) fn cve_index(&self) -> AppResult<MutexGuard<'_, CveIndex>> {
+        Ok(self.cve_index.lock()?)
+    }
+
+    pub(crate) fn search(&self, query: &str, limit: usize) -> AppResult<Vec<SearchHit>> {
+        let mut loaded = self.database.lock()?;
+        self.reload(&mut loaded, false)?;
+        let mut index = self.search.lock()?;
+        index.refresh(&loaded.database, loaded.revision);
+        Ok(index.search(query, limit))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{Machine, Port, PortNotes, Workspace};
+
+    // Store in a fresh temporary directory; keep the TempDir alive for the test's duration
+    pub(crate) fn temp_store() -> (tempfile::TempDir, Store) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        (dir, store)
+    }
+
+    // Open TCP port with no CVEs or tags
+    pub(crate) fn test_port(number: u16, service: &str, application: &str, data: Vec<PortNotes>) -> Port {
+        Port {
+            service: service.to_string(),
+            application: application.to_string(),
+            protocol: "tcp".to_string(),
+            number,
+            state: "open".to_string(),
+            data,
+            cves: vec![],
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn opens_empty_directory() {
+        let (_dir, store) = temp_store();
+        store.read(|db| {
+            assert_eq!(db.name, "Default");
+            assert!(db.data.is_empty());
+            Ok(())
+        }).unwrap();
+        assert!(!store.dir().join("database.json").exists());
+    }
+
+    #[test]
+    fn update_persists_and_reopens() {
+        let (dir, store) = temp_store();
+        store.update(|db| {
+            let mut workspace = Workspace::new(1, "Lab".to_string(), "10.0.0.0/24".to_string());
+            workspace.data.push(Machine::new(1, "dc01".to_string(), "10.0.0.5".to_string()));
+            db.data.push(workspace);
+            Ok(())
+        }).unwrap();
+
+        let reopened = Store::open(dir.path()).unwrap();
+        reopened.read(|db| {
+            assert_eq!(db.workspace(1)?.name, "Lab");
+            assert_eq!(db.machine(1, 1)?.hostname, "dc01");
+            Ok(())
+        }).unwrap();
+    }
+
+    #[test]
+    fn stores_see_each_others_changes() {
+        let (dir, app) = temp_store();
+        let cli = Store::open(dir.path()).unwrap();
+        crate::services::workspaces::add(&app, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        crate::services::workspaces::add(&cli, "DMZ".to_string(), "192.168.1.0/24".to_string()).unwrap();
+        crate::services::workspaces::add(&app, "Cloud".to_string(), "172.16.0.0/16".to_string()).unwrap();
+
+        for store in [&app, &cli] {
+            let names = store.read(|db| Ok(db.data.iter().map(|workspace| workspace.name.clone()).collect::<Vec<_>>())).unwrap();
+            assert_eq!(names, ["Lab", "DMZ", "Cloud"]);
+        }
+        assert!(!dir.path().join("database.json.tmp").exists());
+    }
+
+    #[test]
+    fn changes_are_not_saved_without_their_audit_entry() {
+        let (dir, store) = temp_store();
+        let notified = Arc::new(Mutex::new(vec![]));
+        let seen = notified.clone();
+        store.watch(move |workspace_id| seen.lock().unwrap().push(workspace_id)).unwrap();
+        // A file where the audit directory should be
+        fs::write(dir.path().join("audit"), "").unwrap();
+
+        let result = crate::services::workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string());
+        assert!(matches!(result, Err(AppError::Storage(_))));
+        assert!(Store::open(dir.path()).unwrap().read(|db| Ok(db.data.is_empty())).unwrap());
+        assert!(notified.lock().unwrap().is_empty());
+
+        fs::remove_file(dir.path().join("audit")).unwrap();
+        let id = crate::services::workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        assert_eq!(store.audit_entries(id, None, None, None).unwrap().len(), 1);
+        assert_eq!(*notified.lock().unwrap(), [id]);
+    }
+
+    #[test]
+    fn failed_update_is_not_saved() {
+        let (_dir, store) = temp_store();
+        let result: AppResult<()> = store.update(|db| {
+            db.data.push(Workspace::new(1, "Lab".to_string(), "10.0.0.0/24".to_string()));
+            db.workspace(7)?;
+            Ok(())
+        });
+        assert!(matches!(result, Err(AppError::NotFound { .. })));
+        assert!(!store.dir().join("database.json").exists());
+
+        // Nor is what it changed before failing kept for the next save
+        store.update(|db| {
+            db.name = "Engagement".to_string();
+            Ok(())
+        }).unwrap();
+        assert!(Store::open(store.dir()).unwrap().read(|db| Ok(db.data.is_empty())).unwrap());
+    }
+
+    #[test]
+    fn corrupt_database_is_set_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("database.json"), "{ not json").unwrap();
+        let store = Store::open(dir.path()).unwrap();
+        store.read(|db| {
+            assert!(db.data.is_empty());
+            Ok(())
+        }).unwrap();
+
+        // Saving the new database leaves the unreadable one alone
+        store.update(|db| {
+            db.data.push(Workspace::new(1, "Lab".to_string(), "10.0.0.0/24".to_string()));
+            Ok(())
+        }).unwrap();
+        let kept: Vec<String> = fs::read_dir(dir.path()).unwrap()
+            .filter_map(|entry| entry.unwrap().file_name().into_string().ok())
+            .filter(|name| name.starts_with("database.json.corrupt-"))
+            .collect();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(fs::read_to_string(dir.path().join(&kept[0])).unwrap(), "{ not json");
+    }
+
+    #[test]
+    fn unreadable_save_by_another_process_keeps_the_last_state() {
+        let (dir, store) = temp_store();
+        crate::services::workspaces::add(&store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        fs::write(dir.path().join("database.json"), "{ not json").unwrap();
+
+        // Reading doesn't touch the file
+        assert!(matches!(store.read(|_| Ok(())), Err(AppError::Storage(_))));
+        assert_eq!(fs::read_to_string(dir.path().join("database.json")).unwrap(), "{ not json");
+
+        // The next update sets it aside and saves what was last read
+        crate::services::workspaces::add(&store, "DMZ".to_string(), "192.168.1.0/24".to_string()).unwrap();
+        let names = Store::open(dir.path()).unwrap()
+            .read(|db| Ok(db.data.iter().map(|workspace| workspace.name.clone()).collect::<Vec<_>>())).unwrap();
+        assert_eq!(names, ["Lab", "DMZ"]);
+        assert!(fs::read_dir(dir.path()).unwrap()
+            .any(|entry| entry.unwrap().file_name().to_string_lossy().starts_with("database.json.corrupt-")));
+    }
+
+    #[test]
+    fn migrates_legacy_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = r#"{"name":"Default","data":[{"name":"Lab","id":1,"ip_range":"10.0.0.0/24","data":[
+            {"id":1,"hostname":"web","icon":"server","ip":"10.0.0.2","ports":[
+                {"service":"http","application":"nginx","protocol":"tcp","number":80,"state":"open",
+                 "data":[{"NmapScan":["AI Security Analysis","Looks outdated"]},
+                         {"PentestNote":{"stage":"Enumeration","content":"Default page","timestamp":"2024-01-01 10:00"}}]}]}]}]}"#;
+        fs::write(dir.path().join("database.json"), legacy).unwrap();
+
+        let store = Store::open(dir.path()).unwrap();
+        store.read(|db| {
+            match &db.port(1, 1, 80)?.data[0] {
+                PortNotes::AiAnalysis { content, .. } => assert_eq!(content, "Looks outdated"),
+                _ => panic!("legacy AI note was not migrated"),
+            }
+            // Nobody is credited with a note written before authors were recorded
+            match &db.port(1, 1, 80)?.data[1] {
+                PortNotes::PentestNote { id, author, updated_at, .. } => {
+                    assert!(!id.is_empty());
+                    assert!(author.is_empty());
+                    assert_eq!(updated_at, "2024-01-01 10:00");
+                }
+                _ => panic!("legacy note was not kept"),
+            }
+            Ok(())
+        }).unwrap();
+    }
+}
@@ -0,0 +1,415 @@
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use specta::Type;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::error::{AppError, AppResult};
+use crate::merge;
+use crate::store::Store;
+use crate::team::{Push, RemoteWorkspace, Snapshot, SyncEvent};
+use crate::Workspace;
+
+// How long to wait before reconnecting to a team server that went away
+const RETRY: Duration = Duration::from_secs(5);
+
+// A local workspace kept in sync with one on the team server
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct SyncLink {
+    pub workspace_id: u32,
+    pub remote_id: u32,
+    // Last server revision merged into the local copy
+    pub revision: u64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SyncSettings {
+    #[serde(default)]
+    server: Option<String>,
+    #[serde(default)]
+    token: String,
+    #[serde(default)]
+    user: String,
+    #[serde(default)]
+    links: Vec<SyncLink>,
+}
+
+fn settings_path(dir: &Path) -> PathBuf {
+    dir.join("sync.json")
+}
+
+fn load_settings(dir: &Path) -> SyncSettings {
+    fs::read_to_string(settings_path(dir))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_settings(dir: &Path, settings: &SyncSettings) -> AppResult<()> {
+    let json = serde_json::to_string_pretty(settings)?;
+    fs::write(settings_path(dir), json).map_err(|e| AppError::storage("Failed to write sync settings", e))
+}
+
+#[derive(Serialize, Type)]
+pub(crate) struct SyncStatus {
+    pub server: Option<String>,
+    pub user: Option<String>,
+    // Whether live updates are currently coming in
+    pub connected: bool,
+    pub links: Vec<SyncLink>,
+}
+
+enum Job {
+    // A linked local workspace changed
+    Push(u32),
+    // A remote workspace has a new revision
+    Pull(u32, u64),
+    Stop,
+}
+
+struct Shared {
+    store: Store,
+    http: reqwest::Client,
+    settings: Mutex<SyncSettings>,
+    // Last version exchanged with the server per remote workspace, the base for merging locally
+    synced: Mutex<HashMap<u32, Workspace>>,
+    jobs: Arc<Mutex<Option<mpsc::UnboundedSender<Job>>>>,
+    connected: AtomicBool,
+    on_sync: Box<dyn Fn(u32) + Send + Sync>,
+}
+
+// Keeps linked workspaces in sync with a team server. Local changes are pushed as they are
+// recorded and changes from the rest of the team are merged in as the server announces them.
+#[derive(Clone)]
+pub(crate) struct SyncClient {
+    shared: Arc<Shared>,
+}
+
+impl SyncClient {
+    // `on_sync` is called with the local workspace ID whenever remote changes were merged in
+    pub(crate) fn new(store: Store, on_sync: impl Fn(u32) + Send + Sync + 'static) -> AppResult<SyncClient> {
+        let jobs: Arc<Mutex<Option<mpsc::UnboundedSender<Job>>>> = Arc::new(Mutex::new(None));
+        let watcher_jobs = jobs.clone();
+        store.watch(move |workspace_id| {
+            if let Ok(jobs) = watcher_jobs.lock() {
+                if let Some(jobs) = jobs.as_ref() {
+                    let _ = jobs.send(Job::Push(workspace_id));
+                }
+            }
+        })?;
+        let settings = load_settings(store.dir());
+        // Requests come from the app's runtime and the sync thread's own; a pooled connection
+        // only makes progress while the runtime that opened it runs, so none are kept
+        let http = reqwest::Client::builder().pool_max_idle_per_host(0).build()?;
+        Ok(SyncClient {
+            shared: Arc::new(Shared {
+                store,
+                http,
+                settings: Mutex::new(settings),
+                synced: Mutex::new(HashMap::new()),
+                jobs,
+                connected: AtomicBool::new(false),
+                on_sync: Box::new(on_sync),
+            }),
+        })
+    }
+
+    pub(crate) fn status(&self) -> AppResult<SyncStatus> {
+        let settings = self.shared.settings.lock()?;
+        Ok(SyncStatus {
+            server: settings.server.clone(),
+            user: settings.server.as_ref().map(|_| settings.user.clone()),
+            connected: self.shared.connected.load(Ordering::SeqCst),
+            links: settings.links.clone(),
+        })
+    }
+
+    // Checks the token and remembers the server without starting the background sync
+    pub(crate) async fn login(&self, url: &str, token: &str) -> AppResult<()> {
+        let url = url.trim().trim_end_matches('/').to_string();
+        let request = self.shared.http.get(format!("{}/team/me", url)).bearer_auth(token);
+        let user: Value = send(request).await?;
+        self.stop()?;
+        let mut settings = self.shared.settings.lock()?;
+        // Links only make sense for the server they were made with
+        if settings.server.as_deref() != Some(url.as_str()) {
+            settings.links.clear();
+            self.shared.synced.lock()?.clear();
+        }
+        settings.server = Some(url);
+        settings.token = token.to_string();
+        settings.user = user["name"].as_str().unwrap_or_default().to_string();
+        save_settings(self.shared.store.dir(), &settings)
+    }
+
+    pub(crate) async fn connect(&self, url: &str, token: &str) -> AppResult<SyncStatus> {
+        self.login(url, token).await?;
+        self.start()?;
+        self.status()
+    }
+
+    // Local copies of linked workspaces are kept, they just stop syncing
+    pub(crate) fn disconnect(&self) -> AppResult<SyncStatus> {
+        self.stop()?;
+        *self.shared.settings.lock()? = SyncSettings::default();
+        self.shared.synced.lock()?.clear();
+        save_settings(self.shared.store.dir(), &SyncSettings::default())?;
+        self.status()
+    }
+
+    // Runs the background sync if a server is configured
+    pub(crate) fn start(&self) -> AppResult<()> {
+        self.stop()?;
+        if self.shared.settings.lock()?.server.is_none() {
+            return Ok(());
+        }
+        let (sender, receiver) = mpsc::unbounded_channel();
+        *self.shared.jobs.lock()? = Some(sender.clone());
+        let shared = self.shared.clone();
+        thread::spawn(move || {
+            match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                Ok(runtime) => runtime.block_on(shared.work(receiver, sender)),
+                Err(e) => eprintln!("Failed to start sync runtime: {}", e),
+            }
+        });
+        Ok(())
+    }
+
+    pub(crate) fn stop(&self) -> AppResult<()> {
+        if let Some(jobs) = self.shared.jobs.lock()?.take() {
+            let _ = jobs.send(Job::Stop);
+        }
+        self.shared.connected.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub(crate) async fn remote_workspaces(&self) -> AppResult<Vec<RemoteWorkspace>> {
+        send(self.shared.request(Method::GET, "/team/workspaces")?).await
+    }
+
+    // Uploads a local workspace and links it to the shared copy
+    pub(crate) async fn share(&self, workspace_id: u32) -> AppResult<SyncLink> {
+        if self.shared.link(|link| link.workspace_id == workspace_id)?.is_some() {
+            return Err(AppError::validation(format!("Workspace {} is already shared", workspace_id)));
+        }
+        let workspace = self.shared.store.read(|db| Ok(db.workspace(workspace_id)?.clone()))?;
+        let snapshot: Snapshot = send(self.shared.request(Method::POST, "/team/workspaces")?.json(&workspace)).await?;
+        let link = SyncLink { workspace_id, remote_id: snapshot.workspace.id, revision: 0 };
+        self.shared.settings.lock()?.links.push(link);
+        self.shared.apply(workspace_id, Some(&workspace), snapshot)?;
+        self.shared.link(|link| link.workspace_id == workspace_id)?
+            .ok_or_else(|| AppError::Internal("Link disappeared while sharing".to_string()))
+    }
+
+    // Downloads a shared workspace as a new local one and returns its local ID
+    pub(crate) async fn join(&self, remote_id: u32) -> AppResult<u32> {
+        if let Some(link) = self.shared.link(|link| link.remote_id == remote_id)? {
+            return Err(AppError::validation(format!("Already synced as workspace {}", link.workspace_id)));
+        }
+        let snapshot: Snapshot = send(self.shared.request(Method::GET, &format!("/team/workspaces/{}", remote_id))?).await?;
+        let workspace_id = self.shared.store.update(|db| {
+            let mut workspace = snapshot.workspace.clone();
+            workspace.id = db.next_workspace_id();
+            db.data.push(workspace);
+            Ok(db.data.last().map(|workspace| workspace.id).unwrap_or_default())
+        })?;
+        self.shared.synced.lock()?.insert(remote_id, snapshot.workspace);
+        let mut settings = self.shared.settings.lock()?;
+        settings.links.push(SyncLink { workspace_id, remote_id, revision: snapshot.revision });
+        save_settings(self.shared.store.dir(), &settings)?;
+        Ok(workspace_id)
+    }
+
+    pub(crate) fn unlink(&self, workspace_id: u32) -> AppResult<()> {
+        let mut settings = self.shared.settings.lock()?;
+        settings.links.retain(|link| link.workspace_id != workspace_id);
+        save_settings(self.shared.store.dir(), &settings)
+    }
+}
+
+impl Shared {
+    fn request(&self, method: Method, path: &str) -> AppResult<RequestBuilder> {
+        let settings = self.settings.lock()?;
+        let server = settings.server.as_deref().ok_or_else(|| AppError::validation("Not connected to a team server"))?;
+        Ok(self.http.request(method, format!("{}{}", server, path)).bearer_auth(&settings.token))
+    }
+
+    fn link(&self, matches: impl Fn(&SyncLink) -> bool) -> AppResult<Option<SyncLink>> {
+        Ok(self.settings.lock()?.links.iter().find(|link| matches(link)).cloned())
+    }
+
+    async fn push(&self, workspace_id: u32) -> AppResult<()> {
+        let Some(link) = self.link(|link| link.workspace_id == workspace_id)? else {
+            return Ok(());
+        };
+        let workspace = self.store.read(|db| Ok(db.workspace(workspace_id)?.clone()))?;
+        let push = Push { base_revision: link.revision, workspace };
+        let request = self.request(Method::PUT, &format!("/team/workspaces/{}", link.remote_id))?.json(&push);
+        let snapshot: Snapshot = send(request).await?;
+        // What was sent is the base, so edits made while the request was out are kept
+        self.apply(workspace_id, Some(&push.workspace), snapshot)
+    }
+
+    async fn pull(&self, remote_id: u32, revision: u64) -> AppResult<()> {
+        let Some(link) = self.link(|link| link.remote_id == remote_id)? else {
+            return Ok(());
+        };
+        if revision <= link.revision {
+            return Ok(());
+        }
+        let snapshot: Snapshot = send(self.request(Method::GET, &format!("/team/workspaces/{}", remote_id))?).await?;
+        let base = self.synced.lock()?.get(&remote_id).cloned();
+        self.apply(link.workspace_id, base.as_ref(), snapshot)
+    }
+
+    // Merges the server's version into the local copy. Nothing is recorded, so this doesn't
+    // trigger another push.
+    fn apply(&self, workspace_id: u32, base: Option<&Workspace>, snapshot: Snapshot) -> AppResult<()> {
+        self.store.update(|db| {
+            let local = db.workspace_mut(workspace_id)?;
+            *local = merge::merge_workspace(local, base, &snapshot.workspace)?;
+            Ok(())
+        })?;
+        let remote_id = snapshot.workspace.id;
+        self.synced.lock()?.insert(remote_id, snapshot.workspace);
+        let mut settings = self.settings.lock()?;
+        if let Some(link) = settings.links.iter_mut().find(|link| link.remote_id == remote_id) {
+            link.revision = snapshot.revision;
+        }
+        save_settings(self.store.dir(), &settings)?;
+        drop(settings);
+        (self.on_sync)(workspace_id);
+        Ok(())
+    }
+
+    async fn work(self: Arc<Self>, mut jobs: mpsc::UnboundedReceiver<Job>, sender: mpsc::UnboundedSender<Job>) {
+        let listener = tokio::spawn(self.clone().listen(sender));
+        while let Some(job) = jobs.recv().await {
+            let result = match job {
+                Job::Push(workspace_id) => self.push(workspace_id).await,
+                Job::Pull(remote_id, revision) => self.pull(remote_id, revision).await,
+                Job::Stop => break,
+            };
+            if let Err(e) = result {
+                eprintln!("Team sync failed: {}", e);
+            }
+        }
+        listener.abort();
+    }
+
+    async fn listen(self: Arc<Self>, jobs: mpsc::UnboundedSender<Job>) {
+        loop {
+            if let Err(e) = self.events(&jobs).await {
+                eprintln!("Lost connection to team server: {}", e);
+            }
+            self.connected.store(false, Ordering::SeqCst);
+            tokio::time::sleep(RETRY).await;
+        }
+    }
+
+    // Follows the server's event stream, turning announcements into pulls
+    async fn events(&self, jobs: &mpsc::UnboundedSender<Job>) -> AppResult<()> {
+        let mut response = self.request(Method::GET, "/team/events")?.send().await?;
+        if !response.status().is_success() {
+            return Err(AppError::backend("Team server refused the event stream", response.status()));
+        }
+        self.connected.store(true, Ordering::SeqCst);
+        // Catch up on both sides after (re)connecting
+        for link in self.settings.lock()?.links.iter() {
+            let _ = jobs.send(Job::Push(link.workspace_id));
+        }
+        let mut buffer = vec![];
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.windows(2).position(|window| window == b"\n\n") {
+                let message: Vec<u8> = buffer.drain(..end + 2).collect();
+                for line in String::from_utf8_lossy(&message).lines() {
+                    if let Some(Ok(event)) = line.strip_prefix("data:").map(|data| serde_json::from_str::<SyncEvent>(data.trim())) {
+                        let _ = jobs.send(Job::Pull(event.workspace, event.revision));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn send<T: DeserializeOwned>(request: RequestBuilder) -> AppResult<T> {
+    let response = request.send().await?;
+    let status = response.status();
+    if status == StatusCode::UNAUTHORIZED {
+        return Err(AppError::validation("The team server rejected the token"));
+    }
+    if !status.is_success() {
+        let body: Value = response.json().await.unwrap_or_default();
+        let message = body["message"].as_str().map(str::to_string).unwrap_or_else(|| status.to_string());
+        return Err(AppError::backend("Team server error", message));
+    }
+    response.json().await.map_err(|e| AppError::backend("Failed to parse team server response", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::{machines, notes, workspaces};
+    use crate::store::tests::temp_store;
+    use crate::team;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap()
+    }
+
+    fn machine_notes(store: &Store, workspace_id: u32) -> Vec<(String, String)> {
+        notes::list(store, workspace_id, Some(1), None).unwrap()
+            .into_iter()
+            .map(|note| (note.content, note.author))
+            .collect()
+    }
+
+    #[test]
+    fn changes_reach_the_other_client() {
+        let (server_dir, server_store) = temp_store();
+        let alice_token = team::add_user(server_dir.path(), "alice").unwrap().token;
+        let bob_token = team::add_user(server_dir.path(), "bob").unwrap().token;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || runtime().block_on(team::serve(listener, server_store, std::future::pending())));
+
+        let (_alice_dir, alice_store) = temp_store();
+        let (_bob_dir, bob_store) = temp_store();
+        let lab = workspaces::add(&alice_store, "Lab".to_string(), "10.0.0.0/24".to_string()).unwrap();
+        machines::add(&alice_store, lab, "dc01".to_string(), "10.0.0.5".to_string()).unwrap();
+        let alice = SyncClient::new(alice_store.clone(), |_| {}).unwrap();
+        let bob = SyncClient::new(bob_store.clone(), |_| {}).unwrap();
+
+        let runtime = runtime();
+        let link = runtime.block_on(async {
+            alice.login(&url, &alice_token).await.unwrap();
+            let link = alice.share(lab).await.unwrap();
+            bob.login(&url, &bob_token).await.unwrap();
+            link
+        });
+        alice.start().unwrap();
+        let bobs_lab = runtime.block_on(bob.join(link.remote_id)).unwrap();
+
+        // Bob's change is pushed by hand, alice receives it through the event stream
+        notes::add(&bob_store, bobs_lab, Some(1), "Enumeration".to_string(), "SMB signing disabled".to_string()).unwrap();
+        runtime.block_on(bob.shared.push(bobs_lab)).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while machine_notes(&alice_store, lab).is_empty() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(50));
+        }
+        assert_eq!(machine_notes(&alice_store, lab), vec![("SMB signing disabled".to_string(), "bob".to_string())]);
+        alice.stop().unwrap();
+    }
+}
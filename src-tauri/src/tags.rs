@@ -0,0 +1,165 @@
+use serde::{Serialize, Deserialize};
+use specta::Type;
+
+use crate::error::{AppError, AppResult, Entity};
+use crate::{notes, Database, PortNotes, Workspace};
+
+const DEFAULT_COLOR: &str = "#64748b";
+
+// Tag definitions are shared by every workspace; objects only store the tag name
+#[derive(Clone, Serialize, Deserialize, Type)]
+pub(crate) struct Tag {
+    pub name: String,
+    pub color: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Type)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub(crate) enum TagTarget {
+    Workspace,
+    Machine { machine_id: u32 },
+    Port { machine_id: u32, port_number: u16 },
+    Note { note_id: String },
+}
+
+impl TagTarget {
+    // Object string used in the audit log
+    pub(crate) fn object(&self, workspace_id: u32) -> String {
+        match self {
+            TagTarget::Workspace => format!("workspace:{}", workspace_id),
+            TagTarget::Machine { machine_id } => format!("machine:{}", machine_id),
+            TagTarget::Port { machine_id, port_number } => format!("port:{}/{}", machine_id, port_number),
+            TagTarget::Note { note_id } => format!("note:{}", note_id),
+        }
+    }
+}
+
+fn validate_color(color: &str) -> AppResult<()> {
+    let hex = color.strip_prefix('#').unwrap_or("");
+    if (hex.len() == 3 || hex.len() == 6) && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err(AppError::validation(format!("Invalid tag color '{}', expected #rgb or #rrggbb", color)))
+    }
+}
+
+fn validate_name(name: &str) -> AppResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::validation("Tag name cannot be empty"));
+    }
+    Ok(name.to_string())
+}
+
+// Tag filters are case-insensitive; no filter matches everything
+pub(crate) fn has_tag(tags: &[String], filter: Option<&str>) -> bool {
+    filter.is_none_or(|filter| tags.iter().any(|tag| tag.eq_ignore_ascii_case(filter)))
+}
+
+// Creates the tag or changes its color
+pub(crate) fn save(database: &mut Database, name: &str, color: Option<String>) -> AppResult<Tag> {
+    let name = validate_name(name)?;
+    if let Some(color) = &color {
+        validate_color(color)?;
+    }
+    match database.tags.iter_mut().find(|tag| tag.name.eq_ignore_ascii_case(&name)) {
+        Some(tag) => {
+            if let Some(color) = color {
+                tag.color = color;
+            }
+            Ok(tag.clone())
+        }
+        None => {
+            let tag = Tag { name, color: color.unwrap_or_else(|| DEFAULT_COLOR.to_string()) };
+            database.tags.push(tag.clone());
+            Ok(tag)
+        }
+    }
+}
+
+// Whether anything in the workspace carries the tag
+pub(crate) fn in_use(workspace: &Workspace, name: &str) -> bool {
+    let tagged = |tags: &[String]| has_tag(tags, Some(name));
+    tagged(&workspace.tags)
+        || workspace.notes.iter().any(|note| tagged(&note.tags))
+        || workspace.data.iter().any(|machine| {
+            tagged(&machine.tags)
+                || machine.notes.iter().any(|note| tagged(&note.tags))
+                || machine.ports.iter().any(|port| {
+                    tagged(&port.tags)
+                        || port.data.iter().any(|note| matches!(note, PortNotes::PentestNote { tags, .. } if tagged(tags)))
+                })
+        })
+}
+
+fn strip(tags: &mut Vec<String>, name: &str) {
+    tags.retain(|tag| !tag.eq_ignore_ascii_case(name));
+}
+
+// Removes the definition and the tag from every object carrying it
+pub(crate) fn delete(database: &mut Database, name: &str) -> AppResult<()> {
+    let before = database.tags.len();
+    database.tags.retain(|tag| !tag.name.eq_ignore_ascii_case(name));
+    if database.tags.len() == before {
+        return Err(AppError::not_found(Entity::Tag, name));
+    }
+
+    for workspace in database.data.iter_mut() {
+        strip(&mut workspace.tags, name);
+        for note in workspace.notes.iter_mut() {
+            strip(&mut note.tags, name);
+        }
+        for machine in workspace.data.iter_mut() {
+            strip(&mut machine.tags, name);
+            for note in machine.notes.iter_mut() {
+                strip(&mut note.tags, name);
+            }
+            for port in machine.ports.iter_mut() {
+                strip(&mut port.tags, name);
+                for note in port.data.iter_mut() {
+                    if let Some(fields) = notes::port_note_fields(note) {
+                        strip(fields.tags, name);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn target_tags<'a>(workspace: &'a mut Workspace, target: &TagTarget) -> AppResult<&'a mut Vec<String>> {
+    match target {
+        TagTarget::Workspace => Ok(&mut workspace.tags),
+        TagTarget::Machine { machine_id } => workspace.data.iter_mut()
+            .find(|machine| machine.id == *machine_id)
+            .map(|machine| &mut machine.tags)
+            .ok_or_else(|| AppError::not_found(Entity::Machine, machine_id)),
+        TagTarget::Port { machine_id, port_number } => workspace.data.iter_mut()
+            .find(|machine| machine.id == *machine_id)
+            .and_then(|machine| machine.ports.iter_mut().find(|port| port.number == *port_number))
+            .map(|port| &mut port.tags)
+            .ok_or_else(|| AppError::not_found(Entity::Port, port_number)),
+        TagTarget::Note { note_id } => notes::find_in_workspace(workspace, note_id)
+            .map(|fields| fields.tags)
+            .ok_or_else(|| AppError::not_found(Entity::Note, note_id)),
+    }
+}
+
+// Tags an object, defining the tag with the default color if it doesn't exist yet
+pub(crate) fn apply(database: &mut Database, workspace_id: u32, target: &TagTarget, name: &str) -> AppResult<()> {
+    let name = save(database, name, None)?.name;
+    let workspace = database.data.iter_mut().find(|workspace| workspace.id == workspace_id)
+        .ok_or_else(|| AppError::not_found(Entity::Workspace, workspace_id))?;
+    let tags = target_tags(workspace, target)?;
+    if !has_tag(tags, Some(&name)) {
+        tags.push(name);
+    }
+    Ok(())
+}
+
+pub(crate) fn remove(database: &mut Database, workspace_id: u32, target: &TagTarget, name: &str) -> AppResult<()> {
+    let workspace = database.data.iter_mut().find(|workspace| workspace.id == workspace_id)
+        .ok_or_else(|| AppError::not_found(Entity::Workspace, workspace_id))?;
+    strip(target_tags(workspace, target)?, name);
+    Ok(())
+}
@@ -0,0 +1,444 @@
+use axum::extract::{Path, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use futures::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use specta::Type;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::Infallible;
+use std::fs;
+use std::future::Future;
+use std::net::TcpListener;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+use crate::api;
+use crate::error::{AppError, AppResult};
+use crate::merge;
+use crate::store::{Changes, Store};
+use crate::{PortNotes, Workspace};
+
+pub(crate) const DEFAULT_PORT: u16 = 8086;
+
+// Past versions kept per workspace as merge bases for clients that are a few changes behind
+const HISTORY: usize = 32;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct TeamUser {
+    pub name: String,
+    pub token: String,
+}
+
+fn users_path(dir: &FsPath) -> PathBuf {
+    dir.join("team.json")
+}
+
+fn revisions_path(dir: &FsPath) -> PathBuf {
+    dir.join("revisions.json")
+}
+
+pub(crate) fn load_users(dir: &FsPath) -> AppResult<Vec<TeamUser>> {
+    match fs::read_to_string(users_path(dir)) {
+        Ok(data) => serde_json::from_str(&data).map_err(|e| AppError::storage("Failed to parse team users", e)),
+        Err(_) => Ok(vec![]),
+    }
+}
+
+// Returns the new user with their token; the server has to be restarted to accept it
+pub(crate) fn add_user(dir: &FsPath, name: &str) -> AppResult<TeamUser> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::validation("User name must not be empty"));
+    }
+    let mut users = load_users(dir)?;
+    if users.iter().any(|user| user.name == name) {
+        return Err(AppError::validation(format!("User '{}' already exists", name)));
+    }
+    let user = TeamUser { name: name.to_string(), token: uuid::Uuid::new_v4().simple().to_string() };
+    users.push(user.clone());
+    let json = serde_json::to_string_pretty(&users)?;
+    fs::write(users_path(dir), json).map_err(|e| AppError::storage("Failed to write team users", e))?;
+    Ok(user)
+}
+
+// What goes over the wire between the team server and the apps
+
+#[derive(Serialize, Deserialize, Type)]
+pub(crate) struct RemoteWorkspace {
+    pub id: u32,
+    pub name: String,
+    pub ip_range: String,
+    pub revision: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    pub revision: u64,
+    pub workspace: Workspace,
+}
+
+// A client's copy of a workspace and the server revision it was last merged with
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Push {
+    pub base_revision: u64,
+    pub workspace: Workspace,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct SyncEvent {
+    pub workspace: u32,
+    pub revision: u64,
+    pub user: String,
+}
+
+#[derive(Default)]
+struct Revisions {
+    current: HashMap<u32, u64>,
+    history: HashMap<u32, VecDeque<(u64, Workspace)>>,
+}
+
+impl Revisions {
+    fn revision(&self, workspace_id: u32) -> u64 {
+        self.current.get(&workspace_id).copied().unwrap_or(0)
+    }
+
+    fn base(&self, workspace_id: u32, revision: u64) -> Option<&Workspace> {
+        self.history.get(&workspace_id)?
+            .iter()
+            .find(|(known, _)| *known == revision)
+            .map(|(_, workspace)| workspace)
+    }
+
+    fn remember(&mut self, revision: u64, workspace: &Workspace) {
+        let history = self.history.entry(workspace.id).or_default();
+        history.push_back((revision, workspace.clone()));
+        if history.len() > HISTORY {
+            history.pop_front();
+        }
+    }
+}
+
+#[derive(Clone)]
+struct TeamState {
+    store: Store,
+    users: Arc<Vec<TeamUser>>,
+    revisions: Arc<Mutex<Revisions>>,
+    events: broadcast::Sender<SyncEvent>,
+}
+
+impl TeamState {
+    fn open(store: Store, users: Vec<TeamUser>) -> AppResult<TeamState> {
+        let mut revisions = Revisions {
+            current: fs::read_to_string(revisions_path(store.dir()))
+                .ok()
+                .and_then(|data| serde_json::from_str(&data).ok())
+                .unwrap_or_default(),
+            history: HashMap::new(),
+        };
+        // Clients that were up to date before a restart can still merge against the current version
+        store.read(|db| {
+            for workspace in &db.data {
+                revisions.remember(revisions.revision(workspace.id), workspace);
+            }
+            Ok(())
+        })?;
+        let (events, _) = broadcast::channel(256);
+        Ok(TeamState { store, users: Arc::new(users), revisions: Arc::new(Mutex::new(revisions)), events })
+    }
+
+    // Bumps the workspace's revision and tells every connected client. The change has been
+    // recorded with `record_sync` by then.
+    fn commit(&self, revisions: &mut Revisions, workspace: &Workspace, user: &str) -> AppResult<u64> {
+        let revision = revisions.revision(workspace.id) + 1;
+        revisions.current.insert(workspace.id, revision);
+        revisions.remember(revision, workspace);
+        let json = serde_json::to_string(&revisions.current)?;
+        fs::write(revisions_path(self.store.dir()), json).map_err(|e| AppError::storage("Failed to write revisions", e))?;
+        // Nobody listening is fine
+        let _ = self.events.send(SyncEvent { workspace: workspace.id, revision, user: user.to_string() });
+        Ok(revision)
+    }
+}
+
+// Audit entry for the revision `commit` is about to make
+fn record_sync(changes: &mut Changes, revisions: &Revisions, workspace_id: u32, user: &str) {
+    let revision = revisions.revision(workspace_id) + 1;
+    changes.record(workspace_id, "sync", format!("workspace:{}", workspace_id), None, Some(json!({ "user": user, "revision": revision })));
+}
+
+fn credential_key(name: &Option<String>, hash: &Option<String>, password: &Option<String>) -> String {
+    format!("credentials:{:?}:{:?}:{:?}", name, hash, password)
+}
+
+fn note_keys(workspace: &Workspace) -> HashSet<String> {
+    let mut keys: HashSet<String> = workspace.notes.iter()
+        .chain(workspace.data.iter().flat_map(|machine| machine.notes.iter()))
+        .map(|note| note.id.clone())
+        .collect();
+    for note in workspace.data.iter().flat_map(|machine| machine.ports.iter()).flat_map(|port| port.data.iter()) {
+        match note {
+            PortNotes::PentestNote { id, .. } => {
+                keys.insert(id.clone());
+            }
+            PortNotes::Credentials { name, hash, password, .. } => {
+                keys.insert(credential_key(name, hash, password));
+            }
+            _ => {}
+        }
+    }
+    keys
+}
+
+// Credits notes and credentials to `user` unless `keep` says the existing author stands
+fn attribute(workspace: &mut Workspace, keep: impl Fn(&str, &str) -> bool, user: &str) {
+    let Workspace { notes, data, .. } = workspace;
+    for note in notes.iter_mut().chain(data.iter_mut().flat_map(|machine| machine.notes.iter_mut())) {
+        if !keep(&note.id, &note.author) {
+            note.author = user.to_string();
+        }
+    }
+    for note in data.iter_mut().flat_map(|machine| machine.ports.iter_mut()).flat_map(|port| port.data.iter_mut()) {
+        match note {
+            PortNotes::PentestNote { id, author, .. } if !keep(id, author) => *author = user.to_string(),
+            PortNotes::Credentials { name, hash, password, author, .. }
+                if !keep(&credential_key(name, hash, password), author.as_deref().unwrap_or_default()) =>
+            {
+                *author = Some(user.to_string());
+            }
+            _ => {}
+        }
+    }
+}
+
+async fn authorize(State(state): State<TeamState>, mut request: Request, next: Next) -> Response {
+    let user = request.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| state.users.iter().find(|user| api::tokens_match(token, &user.token)))
+        .cloned();
+    match user {
+        Some(user) => {
+            request.extensions_mut().insert(user);
+            next.run(request).await
+        }
+        None => {
+            let body = json!({ "code": "unauthorized", "message": "Missing or invalid team token" });
+            (StatusCode::UNAUTHORIZED, Json(body)).into_response()
+        }
+    }
+}
+
+async fn me(Extension(user): Extension<TeamUser>) -> Response {
+    Json(json!({ "name": user.name })).into_response()
+}
+
+async fn list(State(state): State<TeamState>) -> AppResult<Json<Vec<RemoteWorkspace>>> {
+    let revisions = state.revisions.lock()?;
+    let list = state.store.read(|db| {
+        Ok(db.data.iter().map(|workspace| RemoteWorkspace {
+            id: workspace.id,
+            name: workspace.name.clone(),
+            ip_range: workspace.ip_range.clone(),
+            revision: revisions.revision(workspace.id),
+        }).collect())
+    })?;
+    Ok(Json(list))
+}
+
+// Shares a workspace; everything in it is credited to whoever shared it
+async fn create(
+    State(state): State<TeamState>,
+    Extension(user): Extension<TeamUser>,
+    Json(mut workspace): Json<Workspace>,
+) -> AppResult<(StatusCode, Json<Snapshot>)> {
+    attribute(&mut workspace, |_, _| false, &user.name);
+    let mut revisions = state.revisions.lock()?;
+    let workspace = state.store.update_audited(|db, changes| {
+        workspace.id = db.next_workspace_id();
+        record_sync(changes, &revisions, workspace.id, &user.name);
+        db.data.push(workspace.clone());
+        Ok(workspace)
+    })?;
+    let revision = state.commit(&mut revisions, &workspace, &user.name)?;
+    Ok((StatusCode::CREATED, Json(Snapshot { revision, workspace })))
+}
+
+async fn snapshot(State(state): State<TeamState>, Path(workspace_id): Path<u32>) -> AppResult<Json<Snapshot>> {
+    let revisions = state.revisions.lock()?;
+    let workspace = state.store.read(|db| Ok(db.workspace(workspace_id)?.clone()))?;
+    Ok(Json(Snapshot { revision: revisions.revision(workspace_id), workspace }))
+}
+
+// Merges a client's copy into the shared one. Whatever the server hasn't seen before is
+// credited to the sender, so authors can't be spoofed by editing the JSON.
+async fn push(
+    State(state): State<TeamState>,
+    Extension(user): Extension<TeamUser>,
+    Path(workspace_id): Path<u32>,
+    Json(push): Json<Push>,
+) -> AppResult<Json<Snapshot>> {
+    let mut revisions = state.revisions.lock()?;
+    let base = revisions.base(workspace_id, push.base_revision).cloned();
+    let mut incoming = push.workspace;
+    let (workspace, changed) = state.store.update_audited(|db, changes| {
+        let current = db.workspace_mut(workspace_id)?;
+        let mut known = note_keys(current);
+        if let Some(base) = &base {
+            known.extend(note_keys(base));
+        }
+        attribute(&mut incoming, |key, _| known.contains(key), &user.name);
+        let merged = merge::merge_workspace(current, base.as_ref(), &incoming)?;
+        let changed = serde_json::to_value(&merged)? != serde_json::to_value(&*current)?;
+        if changed {
+            record_sync(changes, &revisions, workspace_id, &user.name);
+        }
+        *current = merged;
+        Ok((current.clone(), changed))
+    })?;
+    let revision = match changed {
+        true => state.commit(&mut revisions, &workspace, &user.name)?,
+        false => revisions.revision(workspace_id),
+    };
+    Ok(Json(Snapshot { revision, workspace }))
+}
+
+// Server-sent events announcing every new revision
+async fn events(State(state): State<TeamState>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = stream::unfold(state.events.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let event = Event::default().event("workspace").json_data(&event).unwrap_or_default();
+                    return Some((Ok(event), receiver));
+                }
+                // A client that fell behind catches up with the next event for the workspace
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn router(state: TeamState) -> Router {
+    Router::new()
+        .route("/team/me", get(me))
+        .route("/team/workspaces", get(list).post(create))
+        .route("/team/workspaces/{workspace}", get(snapshot).put(push))
+        .route("/team/events", get(events))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state)
+}
+
+// Serves the store's workspaces to the users in its team.json until `shutdown` completes
+pub(crate) async fn serve(listener: TcpListener, store: Store, shutdown: impl Future<Output = ()> + Send + 'static) -> AppResult<()> {
+    let users = load_users(store.dir())?;
+    if users.is_empty() {
+        return Err(AppError::validation("The team server has no users yet, add one first"));
+    }
+    let state = TeamState::open(store, users)?;
+    if listener.local_addr().is_ok_and(|address| !address.ip().is_loopback()) {
+        eprintln!("Warning: the team server is reachable from other machines over plain HTTP, so tokens and workspaces can be read on the network. Put it behind a TLS proxy or a VPN.");
+    }
+    api::serve_router(listener, router(state), shutdown).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notes::new_port_note;
+    use crate::store::tests::{temp_store, test_port};
+    use crate::Machine;
+    use axum::body::{to_bytes, Body};
+    use futures::executor::block_on;
+    use serde::de::DeserializeOwned;
+    use tower::ServiceExt;
+
+    fn call<T: DeserializeOwned>(app: &Router, method: &str, uri: &str, token: &str, body: Option<String>) -> (StatusCode, Option<T>) {
+        let request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.map(Body::from).unwrap_or_default())
+            .unwrap();
+        block_on(async {
+            let response = app.clone().oneshot(request).await.unwrap();
+            let status = response.status();
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice(&bytes).ok())
+        })
+    }
+
+    fn lab() -> Workspace {
+        let mut workspace = Workspace::new(1, "Lab".to_string(), "10.0.0.0/24".to_string());
+        let mut machine = Machine::new(1, "web".to_string(), "10.0.0.2".to_string());
+        machine.ports.push(test_port(80, "http", "nginx", vec![]));
+        workspace.data.push(machine);
+        workspace
+    }
+
+    // A note that claims to be written by someone else, as an edited export would
+    fn spoofed_note(content: &str) -> PortNotes {
+        let mut note = new_port_note("Enumeration".to_string(), content.to_string());
+        if let PortNotes::PentestNote { author, .. } = &mut note {
+            *author = "mallory".to_string();
+        }
+        note
+    }
+
+    #[test]
+    fn sharing_credits_the_sharer() {
+        let (_dir, store) = temp_store();
+        let alice = add_user(store.dir(), "alice").unwrap();
+        let app = router(TeamState::open(store, load_users(_dir.path()).unwrap()).unwrap());
+
+        let mut workspace = lab();
+        workspace.data[0].ports[0].data.push(spoofed_note("Alice"));
+        let (_, shared) = call::<Snapshot>(&app, "POST", "/team/workspaces", &alice.token, Some(serde_json::to_string(&workspace).unwrap()));
+        match &shared.unwrap().workspace.data[0].ports[0].data[0] {
+            PortNotes::PentestNote { author, .. } => assert_eq!(author, "alice"),
+            _ => panic!("expected a pentest note"),
+        }
+    }
+
+    #[test]
+    fn push_merges_and_credits_the_sender() {
+        let (_dir, store) = temp_store();
+        let alice = add_user(store.dir(), "alice").unwrap();
+        let bob = add_user(store.dir(), "bob").unwrap();
+        let app = router(TeamState::open(store, load_users(_dir.path()).unwrap()).unwrap());
+
+        assert_eq!(call::<Snapshot>(&app, "GET", "/team/workspaces", "guess", None).0, StatusCode::UNAUTHORIZED);
+        let (status, shared) = call::<Snapshot>(&app, "POST", "/team/workspaces", &alice.token, Some(serde_json::to_string(&lab()).unwrap()));
+        assert_eq!(status, StatusCode::CREATED);
+        let shared = shared.unwrap();
+
+        // Both start from revision 1 and add a note; bob claims to be someone else
+        let mut bobs = shared.workspace.clone();
+        bobs.data[0].ports[0].data.push(spoofed_note("Bob"));
+        let mut alices = shared.workspace.clone();
+        alices.data[0].ports[0].data.push(new_port_note("Enumeration".to_string(), "Alice".to_string()));
+        let uri = format!("/team/workspaces/{}", shared.workspace.id);
+        for (user, workspace) in [(&bob, bobs), (&alice, alices)] {
+            let body = serde_json::to_string(&Push { base_revision: shared.revision, workspace }).unwrap();
+            assert_eq!(call::<Snapshot>(&app, "PUT", &uri, &user.token, Some(body)).0, StatusCode::OK);
+        }
+
+        let (_, latest) = call::<Snapshot>(&app, "GET", &uri, &bob.token, None);
+        let latest = latest.unwrap();
+        assert_eq!(latest.revision, 3);
+        let authors: Vec<String> = latest.workspace.data[0].ports[0].data.iter().filter_map(|note| match note {
+            PortNotes::PentestNote { author, .. } => Some(author.clone()),
+            _ => None,
+        }).collect();
+        assert_eq!(authors, vec!["bob", "alice"]);
+    }
+}
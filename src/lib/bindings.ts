@@ -0,0 +1,382 @@
+// This file was generated by [tauri-specta](https://github.com/oscartbeaumont/tauri-specta). Do not edit this file manually.
+
+/** user-defined commands **/
+
+
+export const commands = {
+async workspaces(tag: string | null) : Promise<JsWorkspace[]> {
+    return await TAURI_INVOKE("workspaces", { tag });
+},
+async machines(workspaceId: number, tag: string | null) : Promise<JsMachine[]> {
+    return await TAURI_INVOKE("machines", { workspaceId, tag });
+},
+async ports(workspaceId: number, machineId: number, tag: string | null) : Promise<JsPort[]> {
+    return await TAURI_INVOKE("ports", { workspaceId, machineId, tag });
+},
+async getMachine(workspaceId: number, machineId: number) : Promise<JsMachine> {
+    return await TAURI_INVOKE("get_machine", { workspaceId, machineId });
+},
+async getWorkspace(workspaceId: number) : Promise<JsWorkspace> {
+    return await TAURI_INVOKE("get_workspace", { workspaceId });
+},
+async getPort(workspaceId: number, machineId: number, portNumber: number) : Promise<JsPort> {
+    return await TAURI_INVOKE("get_port", { workspaceId, machineId, portNumber });
+},
+async scanIp(ip: string) : Promise<string> {
+    return await TAURI_INVOKE("scan_ip", { ip });
+},
+async scanMachine(workspaceId: number, machineId: number) : Promise<string> {
+    return await TAURI_INVOKE("scan_machine", { workspaceId, machineId });
+},
+async addWorkspace(name: string, ipRange: string) : Promise<string> {
+    return await TAURI_INVOKE("add_workspace", { name, ipRange });
+},
+async addMachine(workspaceId: number, name: string, ip: string) : Promise<string> {
+    return await TAURI_INVOKE("add_machine", { workspaceId, name, ip });
+},
+async discoverHosts(workspaceId: number) : Promise<string> {
+    return await TAURI_INVOKE("discover_hosts", { workspaceId });
+},
+async addCredentials(workspaceId: number, machineId: number, portNumber: number, name: string | null, hash: string | null, password: string | null) : Promise<string> {
+    return await TAURI_INVOKE("add_credentials", { workspaceId, machineId, portNumber, name, hash, password });
+},
+async addPortNote(workspaceId: number, machineId: number, portNumber: number, stage: string, content: string) : Promise<string> {
+    return await TAURI_INVOKE("add_port_note", { workspaceId, machineId, portNumber, stage, content });
+},
+async editPortNote(workspaceId: number, machineId: number, portNumber: number, noteId: string, stage: string | null, content: string | null) : Promise<string> {
+    return await TAURI_INVOKE("edit_port_note", { workspaceId, machineId, portNumber, noteId, stage, content });
+},
+async deletePortNote(workspaceId: number, machineId: number, portNumber: number, noteId: string) : Promise<string> {
+    return await TAURI_INVOKE("delete_port_note", { workspaceId, machineId, portNumber, noteId });
+},
+async editCredentials(workspaceId: number, machineId: number, portNumber: number, noteId: string, name: string | null, hash: string | null, password: string | null) : Promise<null> {
+    return await TAURI_INVOKE("edit_credentials", { workspaceId, machineId, portNumber, noteId, name, hash, password });
+},
+async reorderPortNotes(workspaceId: number, machineId: number, portNumber: number, noteIds: string[]) : Promise<string> {
+    return await TAURI_INVOKE("reorder_port_notes", { workspaceId, machineId, portNumber, noteIds });
+},
+async movePortNote(workspaceId: number, machineId: number, portNumber: number, noteId: string, targetMachineId: number, targetPortNumber: number) : Promise<string> {
+    return await TAURI_INVOKE("move_port_note", { workspaceId, machineId, portNumber, noteId, targetMachineId, targetPortNumber });
+},
+async askQuestion(requestId: string, conversationId: string | null, question: string, context: ChatContext) : Promise<ChatReply> {
+    return await TAURI_INVOKE("ask_question", { requestId, conversationId, question, context });
+},
+async cancelQuestion(requestId: string) : Promise<boolean> {
+    return await TAURI_INVOKE("cancel_question", { requestId });
+},
+async checkTools() : Promise<ToolStatus> {
+    return await TAURI_INVOKE("check_tools");
+},
+async conversations(workspaceId: number, machineId: number | null, portNumber: number | null) : Promise<ConversationSummary[]> {
+    return await TAURI_INVOKE("conversations", { workspaceId, machineId, portNumber });
+},
+async getConversation(workspaceId: number, conversationId: string) : Promise<Conversation> {
+    return await TAURI_INVOKE("get_conversation", { workspaceId, conversationId });
+},
+async renameConversation(workspaceId: number, conversationId: string, title: string) : Promise<string> {
+    return await TAURI_INVOKE("rename_conversation", { workspaceId, conversationId, title });
+},
+async deleteConversation(workspaceId: number, conversationId: string) : Promise<string> {
+    return await TAURI_INVOKE("delete_conversation", { workspaceId, conversationId });
+},
+async exportConversation(workspaceId: number, conversationId: string, path: string) : Promise<string> {
+    return await TAURI_INVOKE("export_conversation", { workspaceId, conversationId, path });
+},
+async aiSettings() : Promise<AiSettings> {
+    return await TAURI_INVOKE("ai_settings");
+},
+async updateAiSettings(settings: AiSettings) : Promise<AiSettings> {
+    return await TAURI_INVOKE("update_ai_settings", { settings });
+},
+async previewPrompt(conversationId: string | null, question: string, context: ChatContext) : Promise<PromptPreview> {
+    return await TAURI_INVOKE("preview_prompt", { conversationId, question, context });
+},
+async setRedactionPolicy(workspaceId: number, policy: RedactionPolicy) : Promise<null> {
+    return await TAURI_INVOKE("set_redaction_policy", { workspaceId, policy });
+},
+async analyzePort(workspaceId: number, machineId: number, portNumber: number) : Promise<string> {
+    return await TAURI_INVOKE("analyze_port", { workspaceId, machineId, portNumber });
+},
+async generateReport(workspaceId: number, format: ReportFormat, includeCredentials: boolean | null) : Promise<string> {
+    return await TAURI_INVOKE("generate_report", { workspaceId, format, includeCredentials });
+},
+async exportReport(workspaceId: number, format: ReportFormat, path: string, includeCredentials: boolean | null) : Promise<string> {
+    return await TAURI_INVOKE("export_report", { workspaceId, format, path, includeCredentials });
+},
+async reportTemplate(format: ReportFormat) : Promise<string> {
+    return await TAURI_INVOKE("report_template", { format });
+},
+async saveReportTemplate(format: ReportFormat, template: string) : Promise<string> {
+    return await TAURI_INVOKE("save_report_template", { format, template });
+},
+async resetReportTemplate(format: ReportFormat) : Promise<string> {
+    return await TAURI_INVOKE("reset_report_template", { format });
+},
+async findings(workspaceId: number, severity: Severity | null, status: FindingStatus | null) : Promise<Finding[]> {
+    return await TAURI_INVOKE("findings", { workspaceId, severity, status });
+},
+async getFinding(workspaceId: number, findingId: number) : Promise<Finding> {
+    return await TAURI_INVOKE("get_finding", { workspaceId, findingId });
+},
+async addFinding(workspaceId: number, finding: FindingInput) : Promise<number> {
+    return await TAURI_INVOKE("add_finding", { workspaceId, finding });
+},
+async updateFinding(workspaceId: number, findingId: number, finding: FindingInput) : Promise<string> {
+    return await TAURI_INVOKE("update_finding", { workspaceId, findingId, finding });
+},
+async deleteFinding(workspaceId: number, findingId: number) : Promise<string> {
+    return await TAURI_INVOKE("delete_finding", { workspaceId, findingId });
+},
+async findingSummary(workspaceId: number) : Promise<FindingSummary> {
+    return await TAURI_INVOKE("finding_summary", { workspaceId });
+},
+async cvssScore(vector: string) : Promise<CvssScore> {
+    return await TAURI_INVOKE("cvss_score", { vector });
+},
+async importCveFeed(path: string) : Promise<string> {
+    return await TAURI_INVOKE("import_cve_feed", { path });
+},
+async importCpeDictionary(path: string) : Promise<string> {
+    return await TAURI_INVOKE("import_cpe_dictionary", { path });
+},
+async cveFeedStatus() : Promise<CveFeedStatus> {
+    return await TAURI_INVOKE("cve_feed_status");
+},
+async matchCves(workspaceId: number, machineId: number | null) : Promise<string> {
+    return await TAURI_INVOKE("match_cves", { workspaceId, machineId });
+},
+async machineNotes(workspaceId: number, machineId: number, tag: string | null) : Promise<Note[]> {
+    return await TAURI_INVOKE("machine_notes", { workspaceId, machineId, tag });
+},
+async addMachineNote(workspaceId: number, machineId: number, stage: string, content: string) : Promise<string> {
+    return await TAURI_INVOKE("add_machine_note", { workspaceId, machineId, stage, content });
+},
+async updateMachineNote(workspaceId: number, machineId: number, noteId: string, stage: string, content: string) : Promise<string> {
+    return await TAURI_INVOKE("update_machine_note", { workspaceId, machineId, noteId, stage, content });
+},
+async deleteMachineNote(workspaceId: number, machineId: number, noteId: string) : Promise<string> {
+    return await TAURI_INVOKE("delete_machine_note", { workspaceId, machineId, noteId });
+},
+async workspaceNotes(workspaceId: number, tag: string | null) : Promise<Note[]> {
+    return await TAURI_INVOKE("workspace_notes", { workspaceId, tag });
+},
+async addWorkspaceNote(workspaceId: number, stage: string, content: string) : Promise<string> {
+    return await TAURI_INVOKE("add_workspace_note", { workspaceId, stage, content });
+},
+async updateWorkspaceNote(workspaceId: number, noteId: string, stage: string, content: string) : Promise<string> {
+    return await TAURI_INVOKE("update_workspace_note", { workspaceId, noteId, stage, content });
+},
+async deleteWorkspaceNote(workspaceId: number, noteId: string) : Promise<string> {
+    return await TAURI_INVOKE("delete_workspace_note", { workspaceId, noteId });
+},
+async noteRevisions(workspaceId: number, noteId: string) : Promise<RevisionView[]> {
+    return await TAURI_INVOKE("note_revisions", { workspaceId, noteId });
+},
+async restoreNoteRevision(workspaceId: number, noteId: string, revision: number) : Promise<string> {
+    return await TAURI_INVOKE("restore_note_revision", { workspaceId, noteId, revision });
+},
+async search(query: string, limit: number | null) : Promise<SearchHit[]> {
+    return await TAURI_INVOKE("search", { query, limit });
+},
+async queryPorts(workspaceId: number, query: PortQuery | null) : Promise<PortPage> {
+    return await TAURI_INVOKE("query_ports", { workspaceId, query });
+},
+async tags() : Promise<Tag[]> {
+    return await TAURI_INVOKE("tags");
+},
+async saveTag(name: string, color: string | null) : Promise<Tag> {
+    return await TAURI_INVOKE("save_tag", { name, color });
+},
+async deleteTag(name: string) : Promise<string> {
+    return await TAURI_INVOKE("delete_tag", { name });
+},
+async tagObject(workspaceId: number, target: TagTarget, tag: string) : Promise<string> {
+    return await TAURI_INVOKE("tag_object", { workspaceId, target, tag });
+},
+async untagObject(workspaceId: number, target: TagTarget, tag: string) : Promise<string> {
+    return await TAURI_INVOKE("untag_object", { workspaceId, target, tag });
+},
+async setMachineStatus(workspaceId: number, machineId: number, status: AccessLevel, evidence: Evidence | null, comment: string | null) : Promise<string> {
+    return await TAURI_INVOKE("set_machine_status", { workspaceId, machineId, status, evidence, comment });
+},
+async machineStatusHistory(workspaceId: number, machineId: number) : Promise<StatusChange[]> {
+    return await TAURI_INVOKE("machine_status_history", { workspaceId, machineId });
+},
+async compromiseSummary(workspaceId: number) : Promise<CompromiseSummary> {
+    return await TAURI_INVOKE("compromise_summary", { workspaceId });
+},
+async fingerprintMachine(workspaceId: number, machineId: number) : Promise<OsFingerprint | null> {
+    return await TAURI_INVOKE("fingerprint_machine", { workspaceId, machineId });
+},
+async setMachineIcon(workspaceId: number, machineId: number, icon: string | null) : Promise<string> {
+    return await TAURI_INVOKE("set_machine_icon", { workspaceId, machineId, icon });
+},
+async auditLog(workspaceId: number, object: string | null, action: string | null, limit: number | null) : Promise<AuditEntry[]> {
+    return await TAURI_INVOKE("audit_log", { workspaceId, object, action, limit });
+},
+async mergeDatabase(path: string, workspaceId: number | null) : Promise<MergeReport> {
+    return await TAURI_INVOKE("merge_database", { path, workspaceId });
+},
+async apiSettings() : Promise<ApiStatus> {
+    return await TAURI_INVOKE("api_settings");
+},
+async updateApiSettings(enabled: boolean, port: number | null) : Promise<ApiStatus> {
+    return await TAURI_INVOKE("update_api_settings", { enabled, port });
+},
+async regenerateApiToken() : Promise<ApiStatus> {
+    return await TAURI_INVOKE("regenerate_api_token");
+},
+async syncStatus() : Promise<SyncStatus> {
+    return await TAURI_INVOKE("sync_status");
+},
+async connectTeamServer(url: string, token: string) : Promise<SyncStatus> {
+    return await TAURI_INVOKE("connect_team_server", { url, token });
+},
+async disconnectTeamServer() : Promise<SyncStatus> {
+    return await TAURI_INVOKE("disconnect_team_server");
+},
+async teamWorkspaces() : Promise<RemoteWorkspace[]> {
+    return await TAURI_INVOKE("team_workspaces");
+},
+async shareWorkspace(workspaceId: number) : Promise<SyncLink> {
+    return await TAURI_INVOKE("share_workspace", { workspaceId });
+},
+async joinTeamWorkspace(remoteId: number) : Promise<number> {
+    return await TAURI_INVOKE("join_team_workspace", { remoteId });
+},
+async unlinkWorkspace(workspaceId: number) : Promise<null> {
+    return await TAURI_INVOKE("unlink_workspace", { workspaceId });
+}
+}
+
+/** user-defined events **/
+
+
+
+/** user-defined constants **/
+
+
+
+/** user-defined types **/
+
+export type AccessLevel = "Untouched" | "Enumerated" | "Foothold" | "User" | "Root" | "Pivot"
+export type AffectedAsset = { machine_id: number; port_number: number | null }
+export type AiChunk = { request_id: string; text: string }
+export type AiSettings = { provider: Provider; url: string; model: string; api_key: string | null; temperature: number; system_prompt: string; context_window: number; sidecar_is_local: boolean }
+export type ApiStatus = { enabled: boolean; port: number; token: string; running: boolean; url: string }
+export type AppError = { code: ErrorCode; message: string; entity?: Entity | null; id?: string | null }
+export type AuditEntry = { timestamp: string; operator: string; action: string; object: string; before: JsonValue | null; after: JsonValue | null }
+export type ChatContext = { type_: string; workspace_id: number; machine_id: number | null; port_number: number | null }
+export type ChatMessage = { role: Role; content: string; timestamp: string; model: string | null }
+export type ChatReply = { conversation_id: string; text: string; model: string | null }
+export type CompromiseSummary = { total: number; compromised: number; percent_compromised: number; by_status: Partial<{ [key in string]: number }>; machines: MachineProgress[] }
+export type Conflict = { workspace: string; field: string; ours: JsonValue; theirs: JsonValue }
+export type Conversation = { id: string; title: string; workspace_id: number; machine_id: number | null; port_number: number | null; created: string; updated: string; messages: ChatMessage[] }
+export type ConversationSummary = { id: string; title: string; machine_id: number | null; port_number: number | null; updated: string; message_count: number }
+export type CveFeedStatus = { imported_at: string | null; cves: number; products: number; dictionary_entries: number }
+export type CveMatch = { id: string; score: number | null; rating: Rating | null; vector: string | null; description: string; cpe: string }
+export type CvssScore = { version: CvssVersion; vector: string; base_score: number; temporal_score: number | null; environmental_score: number | null; score: number; rating: Rating }
+export type CvssVersion = "3.1" | "4.0"
+export type DeviceType = "Unknown" | "Workstation" | "Server" | "DomainController" | "NetworkDevice" | "Printer" | "Storage" | "Mobile"
+export type Entity = "workspace" | "machine" | "port" | "note" | "revision" | "finding" | "tag" | "credential" | "conversation"
+export type ErrorCode = "not_found" | "validation" | "backend_unavailable" | "storage" | "timeout" | "internal"
+export type Evidence = { kind: "note"; note_id: string } | { kind: "credential"; machine_id: number; port_number: number; credential_id?: string }
+export type Finding = { id: number; title: string; severity: Severity; cvss_vector: string | null; cvss_score: number | null; affected: AffectedAsset[]; description: string; evidence: string; remediation: string; status: FindingStatus }
+export type FindingInput = { title: string; severity: Severity | null; cvss_vector: string | null; cvss_score: number | null; affected?: AffectedAsset[]; description?: string; evidence?: string; remediation?: string; status: FindingStatus | null }
+export type FindingStatus = "Open" | "Confirmed" | "Remediated" | "Accepted" | "FalsePositive"
+export type FindingSummary = { workspace_id: number; total: number; by_severity: SeverityCounts; unresolved: SeverityCounts; remediated: number; accepted: number; false_positive: number }
+export type HitPath = { workspace_id: number; workspace_name: string; machine_id: number | null; hostname: string | null; ip: string | null; port_number: number | null; service: string | null; note_id: string | null; tags: string[] }
+export type JsMachine = { id: number; hostname: string; icon: string; ip: string; tags: string[]; status: AccessLevel; fingerprint: OsFingerprint | null }
+export type JsPort = { service: string; number: number; protocol: string; state: string; application: string; data: PortNotes[]; cves: CveMatch[]; tags: string[] }
+export type JsWorkspace = { name: string; id: number; ip_range: string; tags: string[]; redaction: RedactionPolicy }
+export type JsonValue = null | boolean | number | string | JsonValue[] | Partial<{ [key in string]: JsonValue }>
+export type MachineProgress = { machine_id: number; hostname: string; ip: string; status: AccessLevel; since: string | null }
+export type MergeReport = { workspaces_added: number; machines_added: number; ports_added: number; notes_added: number; conflicts: Conflict[] }
+export type Note = { id?: string; stage: string; content: string; timestamp: string; author?: string; updated_at?: string; revisions?: NoteRevision[]; tags?: string[] }
+export type NoteRevision = { stage: string; content: string; timestamp: string }
+export type OsFamily = "Unknown" | "Windows" | "Linux" | "MacOs" | "Bsd" | "NetworkOs" | "Ios" | "Android"
+export type OsFingerprint = { os: OsFamily; os_name: string | null; device: DeviceType; confidence: number; evidence: string[] }
+export type PortNotes = { NmapScan: string[] } | { Credentials: { id?: string; name: string | null; hash: string | null; password: string | null; author?: string | null } } | { PentestNote: { id?: string; stage: string; content: string; timestamp: string; author?: string; updated_at?: string; revisions?: NoteRevision[]; tags?: string[] } } | { AiAnalysis: { id?: string; model: string | null; prompt: string | null; content: string; timestamp: string } } | { ScriptOutput: { id?: string; script_id: string; output: string[] } } | { Screenshot: { id?: string; path: string; caption: string | null; timestamp: string } } | { CommandOutput: { id?: string; command: string; output: string; exit_code: number | null; timestamp: string } } | { FindingRef: { id?: string; finding_id: number } } | "None"
+export type PortPage = { total: number; offset: number; rows: PortRow[] }
+export type PortQuery = { port?: number | null; service?: string | null; application?: string | null; host?: string | null; protocol?: string | null; state?: string | null; tag?: string | null; has_cves?: boolean | null; unscanned?: boolean; sort?: PortSort; descending?: boolean; offset?: number; limit?: number | null }
+export type PortRow = { machine_id: number; hostname: string; ip: string; last_scanned: string | null; tags: string[]; port: PortSummary | null }
+export type PortSort = "ip" | "hostname" | "port" | "service" | "application"
+export type PortSummary = { number: number; protocol: string; service: string; application: string; state: string; note_count: number; cve_count: number }
+export type PromptPreview = { url: string; body: JsonValue; local: boolean; redaction: RedactionPolicy }
+export type Provider = "sidecar" | "ollama" | "open_ai"
+export type Rating = "None" | "Low" | "Medium" | "High" | "Critical"
+export type RedactionPolicy = "never" | "masked" | "local_only"
+export type RemoteWorkspace = { id: number; name: string; ip_range: string; revision: number }
+export type ReportFormat = "markdown" | "html"
+export type RevisionView = { revision: number; stage: string; content: string; timestamp: string; diff: string }
+export type Role = "user" | "assistant"
+export type SearchHit = { kind: string; path: HitPath; location: string; stage: string | null; snippet: string; score: number }
+export type Severity = "Critical" | "High" | "Medium" | "Low" | "Info"
+export type SeverityCounts = { critical: number; high: number; medium: number; low: number; info: number }
+export type StatusChange = { from: AccessLevel; to: AccessLevel; timestamp: string; operator: string; evidence: Evidence | null; comment: string | null }
+export type SyncLink = { workspace_id: number; remote_id: number; revision: number }
+export type SyncStatus = { server: string | null; user: string | null; connected: boolean; links: SyncLink[] }
+export type Tag = { name: string; color: string }
+export type TagTarget = { kind: "workspace" } | { kind: "machine"; machine_id: number } | { kind: "port"; machine_id: number; port_number: number } | { kind: "note"; note_id: string }
+export type ToolState = { installed: boolean; running?: boolean }
+export type ToolStatus = { rustscan: ToolState; nmap: ToolState; ollama: ToolState }
+
+/** tauri-specta globals **/
+
+import {
+	invoke as TAURI_INVOKE,
+	Channel as TAURI_CHANNEL,
+} from "@tauri-apps/api/core";
+import * as TAURI_API_EVENT from "@tauri-apps/api/event";
+import { type WebviewWindow as __WebviewWindow__ } from "@tauri-apps/api/webviewWindow";
+
+type __EventObj__<T> = {
+	listen: (
+		cb: TAURI_API_EVENT.EventCallback<T>,
+	) => ReturnType<typeof TAURI_API_EVENT.listen<T>>;
+	once: (
+		cb: TAURI_API_EVENT.EventCallback<T>,
+	) => ReturnType<typeof TAURI_API_EVENT.once<T>>;
+	emit: null extends T
+		? (payload?: T) => ReturnType<typeof TAURI_API_EVENT.emit>
+		: (payload: T) => ReturnType<typeof TAURI_API_EVENT.emit>;
+};
+
+export type Result<T, E> =
+	| { status: "ok"; data: T }
+	| { status: "error"; error: E };
+
+function __makeEvents__<T extends Record<string, any>>(
+	mappings: Record<keyof T, string>,
+) {
+	return new Proxy(
+		{} as unknown as {
+			[K in keyof T]: __EventObj__<T[K]> & {
+				(handle: __WebviewWindow__): __EventObj__<T[K]>;
+			};
+		},
+		{
+			get: (_, event) => {
+				const name = mappings[event as keyof T];
+
+				return new Proxy((() => {}) as any, {
+					apply: (_, __, [window]: [__WebviewWindow__]) => ({
+						listen: (arg: any) => window.listen(name, arg),
+						once: (arg: any) => window.once(name, arg),
+						emit: (arg: any) => window.emit(name, arg),
+					}),
+					get: (_, command: keyof __EventObj__<any>) => {
+						switch (command) {
+							case "listen":
+								return (arg: any) => TAURI_API_EVENT.listen(name, arg);
+							case "once":
+								return (arg: any) => TAURI_API_EVENT.once(name, arg);
+							case "emit":
+								return (arg: any) => TAURI_API_EVENT.emit(name, arg);
+						}
+					},
+				});
+			},
+		},
+	);
+}